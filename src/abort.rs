@@ -0,0 +1,60 @@
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Handle to abort requests from another thread.
+///
+/// Set on the agent or request with
+/// [`ConfigBuilder::abort_handle()`](crate::config::ConfigBuilder::abort_handle).
+/// Once [`AbortHandle::abort()`] is called, the next time the request sends or
+/// waits for data on the connection, it fails with [`Error::Aborted`](crate::Error::Aborted).
+/// This includes reading the response body.
+///
+/// The abort is cooperative. A connection blocked waiting for input notices it
+/// once the read returns, at the latest when the current timeout elapses.
+///
+/// ```
+/// use ureq::{Agent, AbortHandle};
+///
+/// let handle = AbortHandle::new();
+///
+/// let agent: Agent = Agent::config_builder()
+///     .abort_handle(handle.clone())
+///     .build()
+///     .into();
+///
+/// // From for instance a GUI thread.
+/// handle.abort();
+///
+/// let err = agent.get("http://httpbin.org/get").call().unwrap_err();
+/// assert!(matches!(err, ureq::Error::Aborted));
+/// ```
+#[derive(Clone, Default)]
+pub struct AbortHandle(Arc<AtomicBool>);
+
+impl AbortHandle {
+    /// Create a new handle that is not aborted.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Abort all requests using this handle.
+    ///
+    /// This can't be undone. Requests started after this fail before sending anything.
+    pub fn abort(&self) {
+        self.0.store(true, Ordering::Release);
+    }
+
+    /// Whether [`AbortHandle::abort()`] has been called.
+    pub fn is_aborted(&self) -> bool {
+        self.0.load(Ordering::Acquire)
+    }
+}
+
+impl fmt::Debug for AbortHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("AbortHandle")
+            .field(&self.is_aborted())
+            .finish()
+    }
+}
@@ -11,7 +11,9 @@ use crate::http;
 use crate::middleware::MiddlewareNext;
 use crate::pool::ConnectionPool;
 use crate::resolver::{DefaultResolver, Resolver};
+use crate::run;
 use crate::send_body::AsSendBody;
+use crate::transport::time::Instant;
 use crate::transport::{Connector, DefaultConnector};
 use crate::{Error, RequestBuilder, SendBody};
 use crate::{WithBody, WithoutBody};
@@ -125,13 +127,16 @@ impl Agent {
     pub fn with_parts(config: Config, connector: impl Connector, resolver: impl Resolver) -> Self {
         let pool = Arc::new(ConnectionPool::new(connector, &config));
 
+        #[cfg(feature = "cookies")]
+        let jar = config.cookie_jar().cloned().unwrap_or_default();
+
         Agent {
             config: Arc::new(config),
             pool,
             resolver: Arc::new(resolver),
 
             #[cfg(feature = "cookies")]
-            jar: Arc::new(crate::cookies::SharedCookieJar::new()),
+            jar,
         }
     }
 
@@ -169,6 +174,15 @@ impl Agent {
         self.jar.lock()
     }
 
+    /// Remove all cookies of this agent.
+    ///
+    /// Shorthand for locking the [cookie jar](Agent::cookie_jar_lock) and clearing it.
+    /// Like the lock, this blocks while a request of the agent is using the jar.
+    #[cfg(feature = "cookies")]
+    pub fn clear_cookies(&self) {
+        self.jar.lock().clear();
+    }
+
     /// Run a [`http::Request<impl AsSendBody>`].
     ///
     /// Used to execute http crate [`http::Request`] directly on this agent.
@@ -197,6 +211,64 @@ impl Agent {
         self.run_via_middleware(request, body)
     }
 
+    /// Run a [`http::Request<()>`] with a separate [`SendBody`].
+    ///
+    /// For when the request is built separately from the body, or the body is a
+    /// reader that doesn't fit well in [`http::Request`], such as
+    /// [`SendBody::from_owned_reader()`]. Any body in the request is ignored.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::io::Cursor;
+    /// use ureq::{http, Agent, SendBody};
+    ///
+    /// let agent: Agent = Agent::new_with_defaults();
+    ///
+    /// let request = http::Request::post("http://httpbin.org/post")
+    ///     .body(())?;
+    ///
+    /// let reader = Cursor::new(b"Hello World!".to_vec());
+    /// let body = SendBody::from_owned_reader(reader);
+    ///
+    /// let res = agent.run_with_body(request, body)?;
+    /// # Ok::<(), ureq::Error>(())
+    /// ```
+    pub fn run_with_body(
+        &self,
+        request: Request<()>,
+        body: SendBody,
+    ) -> Result<Response<Body>, Error> {
+        self.run_via_middleware(request, body)
+    }
+
+    /// Open a connection to the host of the uri and put it in the connection pool.
+    ///
+    /// This resolves the host, connects, and completes any TLS handshake, without
+    /// sending a request. A following request to the same host reuses the connection,
+    /// which is useful to take the connection setup out of the first request, such
+    /// as at startup.
+    ///
+    /// Does nothing if there is already an idle connection in the pool.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use ureq::{http::Uri, Agent};
+    ///
+    /// let agent: Agent = Agent::new_with_defaults();
+    ///
+    /// let uri = Uri::from_static("http://httpbin.org/get");
+    /// agent.warm_up(&uri)?;
+    ///
+    /// // Uses the connection opened above.
+    /// let res = agent.get(&uri).call()?;
+    /// # Ok::<(), ureq::Error>(())
+    /// ```
+    pub fn warm_up(&self, uri: &Uri) -> Result<(), Error> {
+        run::warm_up(self, uri)
+    }
+
     pub(crate) fn run_via_middleware(
         &self,
         request: Request<()>,
@@ -234,6 +306,16 @@ impl Agent {
         ConfigBuilder(HttpCrateScope(request))
     }
 
+    /// Close idle pooled connections that are older than
+    /// [`max_idle_age`](crate::config::Config::max_idle_age).
+    ///
+    /// Old connections are otherwise pruned when making a new request. For a
+    /// long-lived agent with quiet periods, this can be called from a timer to not
+    /// keep sockets open that the server most likely has closed already.
+    pub fn reap_idle(&self) {
+        self.pool.purge(Instant::now());
+    }
+
     pub(crate) fn new_request_level_config(&self) -> RequestLevelConfig {
         RequestLevelConfig(self.config.as_ref().clone())
     }
@@ -293,4 +375,240 @@ mod test {
         let a = Agent::new_with_defaults();
         assert_no_alloc(|| a.clone());
     }
+
+    #[derive(Debug, Default, Clone)]
+    struct RecordAddrs(Arc<std::sync::Mutex<Vec<std::net::SocketAddr>>>);
+
+    impl Connector for RecordAddrs {
+        fn connect(
+            &self,
+            details: &crate::transport::ConnectionDetails,
+            _chained: Option<Box<dyn crate::transport::Transport>>,
+        ) -> Result<Option<Box<dyn crate::transport::Transport>>, Error> {
+            self.0.lock().unwrap().extend(details.addrs.iter());
+            Ok(None)
+        }
+    }
+
+    impl RecordAddrs {
+        fn addrs(&self) -> Vec<String> {
+            self.0
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|a| a.to_string())
+                .collect()
+        }
+    }
+
+    #[test]
+    fn address_sort_reorders_connect() {
+        use crate::resolver::ResolvedSocketAddrs;
+        use crate::transport::NextTimeout;
+        use std::net::SocketAddr;
+
+        #[derive(Debug)]
+        struct ThreeAddrs;
+
+        impl Resolver for ThreeAddrs {
+            fn resolve(
+                &self,
+                _uri: &Uri,
+                _config: &Config,
+                _timeout: NextTimeout,
+            ) -> Result<ResolvedSocketAddrs, Error> {
+                let mut v = ResolvedSocketAddrs::from_fn(|_| "0.0.0.0:1".parse().unwrap());
+                v.push("10.0.0.1:80".parse().unwrap());
+                v.push("10.0.0.2:80".parse().unwrap());
+                v.push("10.0.0.3:80".parse().unwrap());
+                Ok(v)
+            }
+        }
+
+        let config = Config::builder()
+            .address_sort(Arc::new(|addrs: &mut Vec<SocketAddr>| addrs.reverse()))
+            .build();
+
+        let record = RecordAddrs::default();
+        let agent = Agent::with_parts(config, record.clone(), ThreeAddrs);
+
+        let err = agent.get("http://example.test").call().unwrap_err();
+        assert!(matches!(err, Error::ConnectionFailed));
+
+        assert_eq!(
+            record.addrs(),
+            ["10.0.0.3:80", "10.0.0.2:80", "10.0.0.1:80"]
+        );
+    }
+
+    #[test]
+    fn resolve_override_bypasses_resolver() {
+        use crate::resolver::ResolvedSocketAddrs;
+        use crate::transport::NextTimeout;
+
+        #[derive(Debug)]
+        struct NoResolver;
+
+        impl Resolver for NoResolver {
+            fn resolve(
+                &self,
+                _uri: &Uri,
+                _config: &Config,
+                _timeout: NextTimeout,
+            ) -> Result<ResolvedSocketAddrs, Error> {
+                Err(Error::HostNotFound)
+            }
+        }
+
+        let config = Config::builder()
+            .resolve_override("Example.test", 443, "10.0.0.5:8443".parse().unwrap())
+            .build();
+
+        let record = RecordAddrs::default();
+        let agent = Agent::with_parts(config, record.clone(), NoResolver);
+
+        let err = agent.get("https://example.test/x").call().unwrap_err();
+        assert!(matches!(err, Error::ConnectionFailed));
+        assert_eq!(record.addrs(), ["10.0.0.5:8443"]);
+
+        // Other ports go to the resolver.
+        let err = agent.get("http://example.test/x").call().unwrap_err();
+        assert!(matches!(err, Error::HostNotFound));
+    }
+
+    #[test]
+    #[cfg(feature = "_test")]
+    fn default_connector_builder() {
+        use crate::resolver::DefaultResolver;
+        use crate::transport::{ConnectionDetails, Transport};
+
+        /// Records whether there was a chained transport.
+        #[derive(Debug, Default, Clone)]
+        struct SeeChained(Arc<std::sync::Mutex<Vec<bool>>>);
+
+        impl Connector for SeeChained {
+            fn connect(
+                &self,
+                _details: &ConnectionDetails,
+                chained: Option<Box<dyn Transport>>,
+            ) -> Result<Option<Box<dyn Transport>>, Error> {
+                self.0.lock().unwrap().push(chained.is_some());
+                Ok(chained)
+            }
+        }
+
+        let seen = SeeChained::default();
+        let connector = DefaultConnector::builder()
+            .after_tcp(seen.clone())
+            .after_tls(seen.clone())
+            .build();
+        let agent = Agent::with_parts(Config::default(), connector, DefaultResolver::default());
+
+        agent.get("https://example.test/get").call().unwrap();
+        assert_eq!(*seen.0.lock().unwrap(), [true, true]);
+    }
+
+    #[test]
+    #[cfg(feature = "_test")]
+    fn throttle_connector() {
+        use crate::resolver::DefaultResolver;
+        use crate::transport::ThrottleConnector;
+        use std::time::Duration;
+
+        let agent_with = |bytes_per_second| {
+            let config = Config::builder()
+                .timeout_global(Some(Duration::from_millis(500)))
+                .build();
+            let connector = DefaultConnector::builder()
+                .after_tcp(ThrottleConnector::new(bytes_per_second))
+                .build();
+            Agent::with_parts(config, connector, DefaultResolver::default())
+        };
+
+        let agent = agent_with(1024 * 1024);
+        agent
+            .post("https://example.test/post")
+            .send("hello")
+            .unwrap();
+
+        // Sending the request header would take seconds before the body can go.
+        // The body is chunked to not go in the same write as the header.
+        let agent = agent_with(10);
+        let mut body = "hello".as_bytes();
+        let err = agent
+            .post("https://example.test/post")
+            .send(SendBody::from_reader(&mut body))
+            .unwrap_err();
+        assert!(matches!(err, Error::Timeout(_)));
+    }
+
+    #[test]
+    #[cfg(feature = "_test")]
+    fn small_request_single_write() {
+        use crate::resolver::DefaultResolver;
+        use crate::transport::{Buffers, ConnectionDetails, NextTimeout, Transport};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        /// Counts the calls to transmit_output().
+        #[derive(Debug, Default, Clone)]
+        struct CountWrites(Arc<AtomicUsize>);
+
+        #[derive(Debug)]
+        struct CountTransport(Box<dyn Transport>, Arc<AtomicUsize>);
+
+        impl Connector for CountWrites {
+            fn connect(
+                &self,
+                _details: &ConnectionDetails,
+                chained: Option<Box<dyn Transport>>,
+            ) -> Result<Option<Box<dyn Transport>>, Error> {
+                Ok(chained
+                    .map(|t| Box::new(CountTransport(t, self.0.clone())) as Box<dyn Transport>))
+            }
+        }
+
+        impl Transport for CountTransport {
+            fn buffers(&mut self) -> &mut dyn Buffers {
+                self.0.buffers()
+            }
+
+            fn transmit_output(
+                &mut self,
+                amount: usize,
+                timeout: NextTimeout,
+            ) -> Result<(), Error> {
+                self.1.fetch_add(1, Ordering::SeqCst);
+                self.0.transmit_output(amount, timeout)
+            }
+
+            fn await_input(&mut self, timeout: NextTimeout) -> Result<bool, Error> {
+                self.0.await_input(timeout)
+            }
+
+            fn is_open(&mut self) -> bool {
+                self.0.is_open()
+            }
+        }
+
+        let count = CountWrites::default();
+        let connector = DefaultConnector::builder().after_tls(count.clone()).build();
+        let agent = Agent::with_parts(Config::default(), connector, DefaultResolver::default());
+
+        agent.get("https://example.test/get").call().unwrap();
+        assert_eq!(count.0.swap(0, Ordering::SeqCst), 1);
+
+        agent
+            .post("https://example.test/post")
+            .send("hello")
+            .unwrap();
+        assert_eq!(count.0.swap(0, Ordering::SeqCst), 1);
+
+        // An unknown size is not held back.
+        let mut body = "hello".as_bytes();
+        agent
+            .post("https://example.test/post")
+            .send(SendBody::from_reader(&mut body))
+            .unwrap();
+        assert!(count.0.load(Ordering::SeqCst) > 1);
+    }
 }
@@ -45,7 +45,9 @@ impl BodyBuilder {
                 content_encoding: ContentEncoding::None,
                 mime_type: None,
                 charset: None,
+                boundary: None,
                 body_mode: BodyMode::NoBody,
+                max_body_size: None,
             },
             limit: None,
         }
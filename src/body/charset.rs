@@ -139,7 +139,7 @@ mod test {
     #[cfg(feature = "charset")]
     fn non_ascii_reason() {
         use crate::test::init_test_log;
-        use crate::Agent;
+        use crate::{Agent, ResponseExt};
 
         init_test_log();
         let agent: Agent = Agent::config_builder().max_redirects(0).build().into();
@@ -149,5 +149,9 @@ mod test {
             .call()
             .unwrap();
         assert_eq!(res.status(), 302);
+        assert_eq!(
+            res.reason_phrase(),
+            Some("D\u{FFFD}plac\u{FFFD} Temporairement")
+        );
     }
 }
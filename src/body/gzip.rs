@@ -66,4 +66,48 @@ mod test {
 
         assert_eq!(agent.pool_count(), 1);
     }
+
+    #[test]
+    fn gz_decompressed_limit() {
+        use std::io::Write;
+
+        use flate2::write::GzEncoder;
+        use flate2::Compression;
+
+        use crate::Error;
+
+        init_test_log();
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&[0; 100_000]).unwrap();
+        let gz_body = encoder.finish().unwrap();
+        let len = gz_body.len().to_string();
+
+        set_handler(
+            "/gz_bomb",
+            200,
+            &[("content-length", &len), ("content-encoding", "gzip")],
+            &gz_body,
+        );
+
+        let mut res = crate::get("https://example.test/gz_bomb").call().unwrap();
+        assert_eq!(res.body().was_decompressed(), Some("gzip"));
+        let err = res
+            .body_mut()
+            .with_config()
+            .decompressed_limit(1000)
+            .read_to_vec()
+            .unwrap_err();
+        assert!(matches!(err, Error::DecompressionLimit(1000)));
+
+        // Exactly at the limit is fine.
+        let mut res = crate::get("https://example.test/gz_bomb").call().unwrap();
+        let body = res
+            .body_mut()
+            .with_config()
+            .decompressed_limit(100_000)
+            .read_to_vec()
+            .unwrap();
+        assert_eq!(body.len(), 100_000);
+    }
 }
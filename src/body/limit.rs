@@ -35,6 +35,46 @@ impl<R: io::Read> io::Read for LimitReader<R> {
     }
 }
 
+/// Limits the output of a decompressing reader.
+///
+/// Unlike [`LimitReader`], reaching the limit exactly is fine. It's
+/// only an error if the output goes past it.
+#[cfg(any(feature = "gzip", feature = "brotli"))]
+pub(crate) struct DecompressionLimitReader<R> {
+    reader: R,
+    limit: u64,
+    left: u64,
+}
+
+#[cfg(any(feature = "gzip", feature = "brotli"))]
+impl<R> DecompressionLimitReader<R> {
+    pub fn new(reader: R, limit: u64) -> Self {
+        DecompressionLimitReader {
+            reader,
+            limit,
+            left: limit,
+        }
+    }
+}
+
+#[cfg(any(feature = "gzip", feature = "brotli"))]
+impl<R: io::Read> io::Read for DecompressionLimitReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // Allow one byte more than what's left to detect going past the limit.
+        let max = (self.left.saturating_add(1).min(usize::MAX as u64) as usize).min(buf.len());
+
+        let n = self.reader.read(&mut buf[..max])?;
+
+        if n as u64 > self.left {
+            return Err(Error::DecompressionLimit(self.limit).into_io());
+        }
+
+        self.left -= n as u64;
+
+        Ok(n)
+    }
+}
+
 #[cfg(all(test, feature = "_test"))]
 mod test {
     use std::io;
@@ -1,20 +1,26 @@
+use std::borrow::Cow;
 use std::fmt;
 use std::io;
 use std::sync::Arc;
 
 pub use build::BodyBuilder;
+pub use multipart::{MultipartPart, MultipartReader};
 use ureq_proto::BodyMode;
 
 use crate::http;
 use crate::run::BodyHandler;
+use crate::transport::Transport;
 use crate::Error;
 
+#[cfg(any(feature = "gzip", feature = "brotli"))]
+use self::limit::DecompressionLimitReader;
 use self::limit::LimitReader;
 use self::lossy::LossyUtf8Reader;
 
 mod build;
 mod limit;
 mod lossy;
+mod multipart;
 
 #[cfg(feature = "charset")]
 mod charset;
@@ -25,7 +31,8 @@ mod gzip;
 #[cfg(feature = "brotli")]
 mod brotli;
 
-/// Default max body size for read_to_string() and read_to_vec().
+/// Default max body size for read_to_string() and read_to_vec(), unless
+/// configured with `max_response_body_size`.
 const MAX_BODY_SIZE: u64 = 10 * 1024 * 1024;
 
 /// A response body returned as [`http::Response<Body>`].
@@ -63,7 +70,9 @@ pub(crate) struct ResponseInfo {
     content_encoding: ContentEncoding,
     mime_type: Option<String>,
     charset: Option<String>,
+    boundary: Option<String>,
     body_mode: BodyMode,
+    max_body_size: Option<u64>,
 }
 
 impl Body {
@@ -82,6 +91,14 @@ impl Body {
         }
     }
 
+    /// A body reading the bytes as sent by the server, see [`Body::into_raw_reader()`].
+    pub(crate) fn from_raw(info: ResponseInfo, raw: impl io::Read + Send + Sync + 'static) -> Self {
+        Body {
+            source: BodyDataSource::Reader(Box::new(raw)),
+            info: Arc::new(info),
+        }
+    }
+
     /// The mime-type of the `content-type` header.
     ///
     /// For the below header, we would get `Some("text/plain")`:
@@ -124,13 +141,99 @@ impl Body {
         self.info.charset.as_deref()
     }
 
+    /// The length of the body, if known before reading.
+    ///
+    /// This is the `content-length` of the response, unless ureq decodes the body
+    /// (such as gzip or a charset other than utf-8), in which case the length read
+    /// differs and this gives `None`. Also `None` for chunked bodies and bodies
+    /// delimited by the server closing the connection.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let res = ureq::get("http://httpbin.org/bytes/100")
+    ///     .call()?;
+    ///
+    /// assert_eq!(res.body().content_length(), Some(100));
+    /// # Ok::<_, ureq::Error>(())
+    /// ```
+    pub fn content_length(&self) -> Option<u64> {
+        let BodyMode::LengthDelimited(len) = self.info.body_mode else {
+            return None;
+        };
+
+        if self.info.is_decoded() {
+            return None;
+        }
+
+        Some(len)
+    }
+
+    /// The `content-encoding` that ureq decompresses when reading the body.
+    ///
+    /// `"gzip"` or `"br"` when the body is decompressed. `None` if the server didn't
+    /// compress the body, or the encoding isn't decoded because the corresponding
+    /// feature (**gzip** or **brotli**) is disabled. Check the `content-encoding` header
+    /// to tell these apart.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let res = ureq::get("http://httpbin.org/bytes/100")
+    ///     .call()?;
+    ///
+    /// assert_eq!(res.body().was_decompressed(), None);
+    /// # Ok::<_, ureq::Error>(())
+    /// ```
+    pub fn was_decompressed(&self) -> Option<&str> {
+        match self.info.content_encoding {
+            ContentEncoding::Gzip if cfg!(feature = "gzip") => Some("gzip"),
+            ContentEncoding::Brotli if cfg!(feature = "brotli") => Some("br"),
+            _ => None,
+        }
+    }
+
+    /// The connection of a `101 Switching Protocols` response.
+    ///
+    /// A server accepting an `Upgrade` request, such as a WebSocket handshake, responds
+    /// with 101, after which the connection speaks the new protocol. This returns the
+    /// raw transport for that, including any data the server already sent after the
+    /// response header. Read and write it using
+    /// [`TransportAdapter`](crate::unversioned::transport::TransportAdapter).
+    ///
+    /// `None` for any other response.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use ureq::unversioned::transport::TransportAdapter;
+    ///
+    /// let res = ureq::get("http://example.com/chat")
+    ///     .header("connection", "upgrade")
+    ///     .header("upgrade", "websocket")
+    ///     .call()?;
+    ///
+    /// if let Some(transport) = res.into_body().into_upgraded() {
+    ///     let stream = TransportAdapter::new(transport);
+    ///     // Speak the new protocol over the stream.
+    /// }
+    /// # Ok::<_, ureq::Error>(())
+    /// ```
+    pub fn into_upgraded(self) -> Option<Box<dyn Transport>> {
+        match self.source {
+            BodyDataSource::Handler(mut handler) => handler.take_upgraded(),
+            BodyDataSource::Reader(_) => None,
+        }
+    }
+
     /// Handle this body as a shared `impl Read` of the body.
     ///
     /// This is the regular API which goes via [`http::Response::body_mut()`] to get a
     /// mut reference to the `Body`, and then use `as_reader()`. It is also possible to
     /// get a non-shared, owned reader via [`Body::into_reader()`].
     ///
-    /// * Reader is not limited. To set a limit use [`Body::with_config()`].
+    /// * Reader is not limited, unless [`Config::max_response_body_size()`](crate::config::Config::max_response_body_size)
+    ///   is set. To set a limit use [`Body::with_config()`].
     ///
     /// # Example
     ///
@@ -156,7 +259,8 @@ impl Body {
     /// variant consumes the body and turns it into a reader with lifetime `'static`.
     /// The reader can for instance be sent to another thread.
     ///
-    /// * Reader is not limited. To set a limit use [`Body::into_with_config()`].
+    /// * Reader is not limited, unless [`Config::max_response_body_size()`](crate::config::Config::max_response_body_size)
+    ///   is set. To set a limit use [`Body::into_with_config()`].
     ///
     /// ```
     /// use std::io::Read;
@@ -175,9 +279,34 @@ impl Body {
         self.into_with_config().reader()
     }
 
+    /// Turn this response into a reader of the body bytes as sent by the server.
+    ///
+    /// Unlike [`Body::into_reader()`], there is no decoding of `Content-Encoding` (gzip,
+    /// brotli), charset or lossy utf-8. Only the transfer framing, such as
+    /// `transfer-encoding: chunked`, is removed. This is useful for proxies and debugging.
+    ///
+    /// * Reader is not limited, unless [`Config::max_response_body_size()`](crate::config::Config::max_response_body_size)
+    ///   is set.
+    ///
+    /// ```
+    /// use std::io::Read;
+    ///
+    /// let res = ureq::get("http://httpbin.org/bytes/100")
+    ///     .call()?;
+    ///
+    /// // The bytes as sent, i.e. still compressed for a gzip response.
+    /// let mut bytes = vec![];
+    /// res.into_body().into_raw_reader().read_to_end(&mut bytes)?;
+    /// # Ok::<_, ureq::Error>(())
+    /// ```
+    pub fn into_raw_reader(self) -> BodyReader<'static> {
+        let info = Arc::new(self.info.raw());
+        BodyWithConfig::new(self.source.into(), info).reader()
+    }
+
     /// Read the response as a string.
     ///
-    /// * Response is limited to 10MB
+    /// * Response is limited to 10MB (or `max_response_body_size`)
     /// * Replaces incorrect utf-8 chars to `?`
     ///
     /// To change these defaults use [`Body::with_config()`].
@@ -191,15 +320,60 @@ impl Body {
     /// # Ok::<_, ureq::Error>(())
     /// ```
     pub fn read_to_string(&mut self) -> Result<String, Error> {
+        let limit = self.info.default_limit();
         self.with_config()
-            .limit(MAX_BODY_SIZE)
+            .limit(limit)
             .lossy_utf8(true)
             .read_to_string()
     }
 
+    /// Read the response as a string, without allocating if possible.
+    ///
+    /// If the entire body is already buffered, has a `Content-Length`, and is valid utf-8,
+    /// this returns `Cow::Borrowed` straight from the connection input buffer. Otherwise
+    /// it falls back to [`Body::read_to_string()`] and returns `Cow::Owned`. This is an
+    /// optimization for small textual responses.
+    ///
+    /// * Response is limited to 10MB (or `max_response_body_size`)
+    /// * Replaces incorrect utf-8 chars to `?`
+    ///
+    /// ```
+    /// let mut res = ureq::get("http://httpbin.org/robots.txt")
+    ///     .call()?;
+    ///
+    /// let s = res.body_mut().read_to_cow_str()?;
+    /// assert_eq!(s, "User-agent: *\nDisallow: /deny\n");
+    /// # Ok::<_, ureq::Error>(())
+    /// ```
+    pub fn read_to_cow_str(&mut self) -> Result<Cow<'_, str>, Error> {
+        let is_handler = matches!(self.source, BodyDataSource::Handler(_));
+
+        if !is_handler || !self.info.is_plain_utf8() {
+            return self.read_to_string().map(Cow::Owned);
+        }
+
+        let BodyDataSource::Handler(handler) = &mut self.source else {
+            unreachable!("checked above");
+        };
+
+        let limit = self.info.default_limit();
+
+        match handler.buffered_str(limit) {
+            Ok(s) => Ok(Cow::Borrowed(s)),
+            Err(handler) => {
+                let handler = BodySourceRef::HandlerShared(handler);
+                BodyWithConfig::new(handler, self.info.clone())
+                    .limit(limit)
+                    .lossy_utf8(true)
+                    .read_to_string()
+                    .map(Cow::Owned)
+            }
+        }
+    }
+
     /// Read the response to a vec.
     ///
-    /// * Response is limited to 10MB.
+    /// * Response is limited to 10MB (or `max_response_body_size`).
     ///
     /// To change this default use [`Body::with_config()`].
     /// ```
@@ -211,15 +385,13 @@ impl Body {
     /// # Ok::<_, ureq::Error>(())
     /// ```
     pub fn read_to_vec(&mut self) -> Result<Vec<u8>, Error> {
-        self.with_config()
-            //
-            .limit(MAX_BODY_SIZE)
-            .read_to_vec()
+        let limit = self.info.default_limit();
+        self.with_config().limit(limit).read_to_vec()
     }
 
     /// Read the response from JSON.
     ///
-    /// * Response is limited to 10MB.
+    /// * Response is limited to 10MB (or `max_response_body_size`).
     ///
     /// To change this default use [`Body::as_reader()`] and deserialize JSON manually.
     ///
@@ -249,11 +421,78 @@ impl Body {
     /// ```
     #[cfg(feature = "json")]
     pub fn read_json<T: serde::de::DeserializeOwned>(&mut self) -> Result<T, Error> {
-        let reader = self.with_config().limit(MAX_BODY_SIZE).reader();
+        let limit = self.info.default_limit();
+        let reader = self.with_config().limit(limit).reader();
         let value: T = serde_json::from_reader(reader)?;
         Ok(value)
     }
 
+    /// Read the response as newline-delimited JSON (NDJSON/JSON lines).
+    ///
+    /// Each line is deserialized lazily as the iterator is consumed, which makes
+    /// this suitable for streaming APIs. Empty lines are skipped.
+    ///
+    /// * Response is not limited, unless [`Config::max_response_body_size()`](crate::config::Config::max_response_body_size)
+    ///   is set. It might never end.
+    ///
+    /// ```
+    /// use serde::Deserialize;
+    ///
+    /// #[derive(Deserialize)]
+    /// struct Event {
+    ///     id: u64,
+    /// }
+    ///
+    /// let mut res = ureq::get("http://httpbin.org/stream/3")
+    ///     .call()?;
+    ///
+    /// for event in res.body_mut().read_json_lines::<Event>() {
+    ///     println!("Event: {}", event?.id);
+    /// }
+    /// # Ok::<_, ureq::Error>(())
+    /// ```
+    #[cfg(feature = "json")]
+    pub fn read_json_lines<T: serde::de::DeserializeOwned>(
+        &mut self,
+    ) -> impl Iterator<Item = Result<T, Error>> + '_ {
+        use std::io::BufRead;
+
+        io::BufReader::new(self.as_reader())
+            .lines()
+            .filter(|line| !matches!(line, Ok(l) if l.trim().is_empty()))
+            .map(|line| Ok(serde_json::from_str(&line?)?))
+    }
+
+    /// Turn this response into an owned reader for streaming JSON.
+    ///
+    /// For use with the streaming API of serde_json, such as
+    /// [`StreamDeserializer`](serde_json::StreamDeserializer) over a sequence of JSON
+    /// values. The reader is limited to `limit` bytes, which is important when the
+    /// server isn't trusted. Going past the limit is an [`Error::BodyExceedsLimit`].
+    ///
+    /// ```
+    /// use serde::Deserialize;
+    ///
+    /// #[derive(Deserialize)]
+    /// struct Event {
+    ///     id: u64,
+    /// }
+    ///
+    /// let res = ureq::get("http://httpbin.org/stream/3")
+    ///     .call()?;
+    ///
+    /// let reader = res.into_body().into_json_reader(10 * 1024 * 1024);
+    ///
+    /// for event in serde_json::Deserializer::from_reader(reader).into_iter::<Event>() {
+    ///     println!("Event: {}", event?.id);
+    /// }
+    /// # Ok::<_, ureq::Error>(())
+    /// ```
+    #[cfg(feature = "json")]
+    pub fn into_json_reader(self, limit: u64) -> BodyReader<'static> {
+        self.into_with_config().limit(limit).reader()
+    }
+
     /// Read the body data with configuration.
     ///
     /// This borrows the body which gives easier use with [`http::Response::body_mut()`].
@@ -299,6 +538,18 @@ impl Body {
         let handler = self.source.into();
         BodyWithConfig::new(handler, self.info.clone())
     }
+
+    /// Turn this body into a reader of the parts of a `multipart/*` response,
+    /// such as `multipart/mixed` or `multipart/byteranges`.
+    ///
+    /// The parts are streamed, not buffered. Fails with [`Error::Multipart`] if the
+    /// `content-type` header has no `boundary` parameter. See [`MultipartReader`].
+    pub fn into_multipart(self) -> Result<MultipartReader, Error> {
+        let Some(boundary) = self.info.boundary.clone() else {
+            return Err(Error::Multipart("no boundary in content-type"));
+        };
+        Ok(MultipartReader::new(self.into_reader(), &boundary))
+    }
 }
 
 /// Configuration of how to read the body.
@@ -312,16 +563,23 @@ pub struct BodyWithConfig<'a> {
     handler: BodySourceRef<'a>,
     info: Arc<ResponseInfo>,
     limit: u64,
+    decompressed_limit: u64,
     lossy_utf8: bool,
+    charset: Option<String>,
+    deadline: Option<std::time::Instant>,
 }
 
 impl<'a> BodyWithConfig<'a> {
     fn new(handler: BodySourceRef<'a>, info: Arc<ResponseInfo>) -> Self {
+        let limit = info.max_body_size.unwrap_or(u64::MAX);
         BodyWithConfig {
             handler,
             info,
-            limit: u64::MAX,
+            limit,
+            decompressed_limit: u64::MAX,
             lossy_utf8: false,
+            charset: None,
+            deadline: None,
         }
     }
 
@@ -330,12 +588,65 @@ impl<'a> BodyWithConfig<'a> {
     /// Controls how many bytes we should read before throwing an error. This is used
     /// to ensure RAM isn't exhausted by a server sending a very large response body.
     ///
-    /// The default limit is `u64::MAX` (unlimited).
+    /// The default limit is [`Config::max_response_body_size()`](crate::config::Config::max_response_body_size),
+    /// which if unset is `u64::MAX` (unlimited).
     pub fn limit(mut self, value: u64) -> Self {
         self.limit = value;
         self
     }
 
+    /// Limit the response body after decompression.
+    ///
+    /// [`limit()`](BodyWithConfig::limit) caps the bytes received, which doesn't
+    /// protect against a small compressed body that expands to gigabytes.
+    /// This caps the output of gzip or brotli decoding. Going past the limit is
+    /// an [`Error::DecompressionLimit`]. Has no effect on bodies that aren't compressed.
+    ///
+    /// The default is no limit.
+    ///
+    /// ```
+    /// let body = ureq::get("http://httpbin.org/get")
+    ///     .call()?
+    ///     .body_mut()
+    ///     .with_config()
+    ///     .decompressed_limit(10 * 1024 * 1024)
+    ///     .read_to_vec()?;
+    /// # Ok::<_, ureq::Error>(())
+    /// ```
+    #[cfg(any(feature = "gzip", feature = "brotli"))]
+    pub fn decompressed_limit(mut self, value: u64) -> Self {
+        self.decompressed_limit = value;
+        self
+    }
+
+    /// Fail reading the body after an absolute point in time.
+    ///
+    /// Unlike the timeouts in [`Config`](crate::config::Config), which are relative to
+    /// when the call started, this bounds the reading regardless of when it starts.
+    /// Reading past the deadline fails with [`Error::Timeout`] for
+    /// [`Timeout::RecvBody`](crate::Timeout::RecvBody). The timeouts still apply,
+    /// whichever comes first.
+    ///
+    /// Has no effect on bodies not received from a server.
+    ///
+    /// ```
+    /// use std::time::{Duration, Instant};
+    ///
+    /// let deadline = Instant::now() + Duration::from_secs(10);
+    ///
+    /// let body = ureq::get("http://httpbin.org/get")
+    ///     .call()?
+    ///     .body_mut()
+    ///     .with_config()
+    ///     .deadline(deadline)
+    ///     .read_to_vec()?;
+    /// # Ok::<_, ureq::Error>(())
+    /// ```
+    pub fn deadline(mut self, deadline: std::time::Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
     /// Replace invalid utf-8 chars.
     ///
     /// `true` means that broken utf-8 characters are replaced by a question mark `?`
@@ -348,12 +659,50 @@ impl<'a> BodyWithConfig<'a> {
         self
     }
 
-    fn do_build(self) -> BodyReader<'a> {
+    /// Decode the body from this charset, regardless of the `Content-Type` header.
+    ///
+    /// For servers that omit the charset, or state the wrong one. The label is looked
+    /// up using [`Encoding::for_label`](encoding_rs::Encoding::for_label), e.g.
+    /// `"shift_jis"` or `"iso-8859-1"`. The body is decoded to utf-8 also when the
+    /// mime type isn't `text/*`.
+    ///
+    /// An unknown label is ignored, and the charset from the header (or utf-8) is used.
+    ///
+    /// ```
+    /// let text = ureq::get("http://httpbin.org/get")
+    ///     .call()?
+    ///     .body_mut()
+    ///     .with_config()
+    ///     .charset("iso-8859-1")
+    ///     .read_to_string()?;
+    /// # Ok::<_, ureq::Error>(())
+    /// ```
+    #[cfg(feature = "charset")]
+    pub fn charset(mut self, label: &str) -> Self {
+        let encoding = encoding_rs::Encoding::for_label(label.as_bytes());
+        if encoding.is_none() {
+            debug!("Ignoring unknown charset: {}", label);
+        }
+        self.charset = encoding.map(|e| e.name().to_string());
+        self
+    }
+
+    fn do_build(mut self) -> BodyReader<'a> {
+        if let Some(deadline) = self.deadline {
+            match &mut self.handler {
+                BodySourceRef::HandlerShared(v) => v.set_deadline(deadline),
+                BodySourceRef::HandlerOwned(v) => v.set_deadline(deadline),
+                _ => {}
+            }
+        }
+
         BodyReader::new(
             LimitReader::new(self.handler, self.limit),
             &self.info,
             self.info.body_mode,
+            self.decompressed_limit,
             self.lossy_utf8,
+            self.charset.as_deref(),
         )
     }
 
@@ -390,6 +739,38 @@ impl<'a> BodyWithConfig<'a> {
         self.do_build()
     }
 
+    /// Creates a reader that also writes every byte it yields to `sink`.
+    ///
+    /// Useful for a cache, where the body is streamed to the caller while a
+    /// copy is persisted. The copy is the same as what the reader yields, i.e.
+    /// after decompression and charset conversion. The sink is flushed when the
+    /// reader reaches the end of the body. A failing write to the sink fails the read.
+    ///
+    /// ```
+    /// use std::fs::File;
+    /// use std::io::Read;
+    ///
+    /// let path = std::env::temp_dir().join("ureq-tee-example");
+    /// let file = File::create(&path)?;
+    ///
+    /// let mut res = ureq::get("http://httpbin.org/bytes/100")
+    ///     .call()?;
+    ///
+    /// let mut bytes = Vec::new();
+    /// res.body_mut()
+    ///     .with_config()
+    ///     .tee(file)
+    ///     .read_to_end(&mut bytes)?;
+    ///
+    /// assert_eq!(bytes, std::fs::read(&path)?);
+    /// # Ok::<_, ureq::Error>(())
+    /// ```
+    pub fn tee<W: io::Write + Send + Sync + 'static>(self, sink: W) -> BodyReader<'a> {
+        let mut reader = self.do_build();
+        reader.tee = Some(Box::new(sink));
+        reader
+    }
+
     /// Read into string.
     pub fn read_to_string(self) -> Result<String, Error> {
         use std::io::Read;
@@ -433,17 +814,81 @@ impl ResponseInfo {
             .map(ContentEncoding::from)
             .unwrap_or(ContentEncoding::None);
 
-        let (mime_type, charset) = headers
-            .get("content-type")
-            .and_then(|v| v.to_str().ok())
-            .map(split_content_type)
-            .unwrap_or((None, None));
+        let content_type = headers.get("content-type").and_then(|v| v.to_str().ok());
+
+        let (mime_type, charset) = content_type.map(split_content_type).unwrap_or((None, None));
+
+        let boundary = content_type.and_then(multipart_boundary);
 
         ResponseInfo {
             content_encoding,
             mime_type,
             charset,
+            boundary,
             body_mode,
+            max_body_size: None,
+        }
+    }
+
+    /// Set the default limit from [`Config::max_response_body_size()`](crate::config::Config::max_response_body_size).
+    pub fn with_max_body_size(mut self, v: Option<u64>) -> Self {
+        self.max_body_size = v;
+        self
+    }
+
+    /// Limit for the reads that have a default limit, like `read_to_string()`.
+    fn default_limit(&self) -> u64 {
+        self.max_body_size.unwrap_or(MAX_BODY_SIZE)
+    }
+
+    /// Info for reading the body bytes without any decoding.
+    fn raw(&self) -> ResponseInfo {
+        ResponseInfo {
+            content_encoding: ContentEncoding::None,
+            mime_type: None,
+            charset: None,
+            ..self.clone()
+        }
+    }
+
+    /// Whether the body bytes are used as is, without any decoding.
+    fn is_plain_utf8(&self) -> bool {
+        let is_utf8 = self
+            .charset
+            .as_deref()
+            .map(|c| c.eq_ignore_ascii_case("utf-8") || c.eq_ignore_ascii_case("utf8"))
+            .unwrap_or(true);
+
+        matches!(self.content_encoding, ContentEncoding::None) && is_utf8
+    }
+
+    /// Whether reading the body gives other bytes than received.
+    fn is_decoded(&self) -> bool {
+        let content_decoded = match self.content_encoding {
+            ContentEncoding::Gzip => cfg!(feature = "gzip"),
+            ContentEncoding::Brotli => cfg!(feature = "brotli"),
+            ContentEncoding::None | ContentEncoding::Unknown => false,
+        };
+
+        content_decoded || (self.is_text() && self.is_charset_decoded())
+    }
+
+    fn is_charset_decoded(&self) -> bool {
+        #[cfg(feature = "charset")]
+        {
+            use encoding_rs::UTF_8;
+
+            // Same as charset_decoder().
+            self.charset
+                .as_deref()
+                .and_then(|c| encoding_rs::Encoding::for_label(c.as_bytes()))
+                .map(|e| e != UTF_8)
+                .unwrap_or(false)
+        }
+
+        #[cfg(not(feature = "charset"))]
+        {
+            false
         }
     }
 
@@ -476,6 +921,22 @@ fn split_content_type(content_type: &str) -> (Option<String>, Option<String>) {
     (Some(mime_type.to_string()), charset)
 }
 
+fn multipart_boundary(content_type: &str) -> Option<String> {
+    // Content-Type: multipart/mixed; boundary="abc"
+    if !content_type.trim_start().starts_with("multipart/") {
+        return None;
+    }
+
+    content_type.split(';').skip(1).find_map(|param| {
+        let (name, value) = param.trim().split_once('=')?;
+        if !name.eq_ignore_ascii_case("boundary") {
+            return None;
+        }
+        let value = value.trim_matches('"');
+        (!value.is_empty()).then(|| value.to_string())
+    })
+}
+
 /// A reader of the response data.
 ///
 /// 1. If `Transfer-Encoding: chunked`, the returned reader will unchunk it
@@ -521,6 +982,8 @@ pub struct BodyReader<'a> {
     // body mode can indiciate the content-length. Gzip, charset etc
     // would mean input is not same as output.
     outgoing_body_mode: BodyMode,
+    // Receives a copy of all bytes read, see BodyWithConfig::tee().
+    tee: Option<Box<dyn io::Write + Send + Sync>>,
 }
 
 impl<'a> BodyReader<'a> {
@@ -528,7 +991,10 @@ impl<'a> BodyReader<'a> {
         reader: LimitReader<BodySourceRef<'a>>,
         info: &ResponseInfo,
         incoming_body_mode: BodyMode,
+        #[cfg_attr(not(any(feature = "gzip", feature = "brotli")), allow(unused))]
+        decompressed_limit: u64,
         lossy_utf8: bool,
+        charset: Option<&str>,
     ) -> BodyReader<'a> {
         // This is outgoing body_mode in case we are using the BodyReader as a send body
         // in a proxy situation.
@@ -540,7 +1006,9 @@ impl<'a> BodyReader<'a> {
             ContentEncoding::Gzip => {
                 debug!("Decoding gzip");
                 outgoing_body_mode = BodyMode::Chunked;
-                ContentDecoder::Gzip(Box::new(gzip::GzipDecoder::new(reader)))
+                let decoder = gzip::GzipDecoder::new(reader);
+                let limited = DecompressionLimitReader::new(decoder, decompressed_limit);
+                ContentDecoder::Gzip(Box::new(limited))
             }
             #[cfg(not(feature = "gzip"))]
             ContentEncoding::Gzip => ContentDecoder::PassThrough(reader),
@@ -548,24 +1016,29 @@ impl<'a> BodyReader<'a> {
             ContentEncoding::Brotli => {
                 debug!("Decoding brotli");
                 outgoing_body_mode = BodyMode::Chunked;
-                ContentDecoder::Brotli(Box::new(brotli::BrotliDecoder::new(reader)))
+                let decoder = brotli::BrotliDecoder::new(reader);
+                let limited = DecompressionLimitReader::new(decoder, decompressed_limit);
+                ContentDecoder::Brotli(Box::new(limited))
             }
             #[cfg(not(feature = "brotli"))]
             ContentEncoding::Brotli => ContentDecoder::PassThrough(reader),
         };
 
-        let reader = if info.is_text() {
+        // A forced charset decodes also bodies that are not text/*.
+        let is_text = charset.is_some() || info.is_text();
+
+        let reader = if is_text {
             charset_decoder(
                 reader,
                 info.mime_type.as_deref(),
-                info.charset.as_deref(),
+                charset.or(info.charset.as_deref()),
                 &mut outgoing_body_mode,
             )
         } else {
             CharsetDecoder::PassThrough(reader)
         };
 
-        let reader = if info.is_text() && lossy_utf8 {
+        let reader = if is_text && lossy_utf8 {
             MaybeLossyDecoder::Lossy(LossyUtf8Reader::new(reader))
         } else {
             MaybeLossyDecoder::PassThrough(reader)
@@ -574,6 +1047,7 @@ impl<'a> BodyReader<'a> {
         BodyReader {
             outgoing_body_mode,
             reader,
+            tee: None,
         }
     }
 
@@ -629,7 +1103,17 @@ impl<R: io::Read> io::Read for MaybeLossyDecoder<R> {
 
 impl<'a> io::Read for BodyReader<'a> {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        self.reader.read(buf)
+        let n = self.reader.read(buf)?;
+
+        if let Some(tee) = &mut self.tee {
+            if n > 0 {
+                tee.write_all(&buf[..n])?;
+            } else {
+                tee.flush()?;
+            }
+        }
+
+        Ok(n)
     }
 }
 
@@ -651,9 +1135,9 @@ impl<R: io::Read> io::Read for CharsetDecoder<R> {
 
 enum ContentDecoder<R: io::Read> {
     #[cfg(feature = "gzip")]
-    Gzip(Box<gzip::GzipDecoder<R>>),
+    Gzip(Box<DecompressionLimitReader<gzip::GzipDecoder<R>>>),
     #[cfg(feature = "brotli")]
-    Brotli(Box<brotli::BrotliDecoder<R>>),
+    Brotli(Box<DecompressionLimitReader<brotli::BrotliDecoder<R>>>),
     PassThrough(R),
 }
 
@@ -725,12 +1209,87 @@ impl<'a> io::Read for BodySourceRef<'a> {
 
 #[cfg(all(test, feature = "_test"))]
 mod test {
+    use std::borrow::Cow;
+    use std::io::Read;
     use std::iter;
 
     use crate::test::init_test_log;
     use crate::transport::set_handler;
     use crate::Error;
 
+    #[test]
+    fn content_length() {
+        init_test_log();
+        let res = crate::get("https://my.test/bytes/100").call().unwrap();
+        assert_eq!(res.body().content_length(), Some(100));
+
+        set_handler(
+            "/content-length-gzip",
+            200,
+            &[("content-encoding", "gzip"), ("content-length", "10")],
+            &[0; 10],
+        );
+        let res = crate::get("https://my.test/content-length-gzip")
+            .call()
+            .unwrap();
+        assert_eq!(res.body().content_length(), None);
+
+        set_handler(
+            "/content-length-chunked",
+            200,
+            &[("transfer-encoding", "chunked")],
+            b"2\r\nok\r\n0\r\n\r\n",
+        );
+        let res = crate::get("https://my.test/content-length-chunked")
+            .call()
+            .unwrap();
+        assert_eq!(res.body().content_length(), None);
+    }
+
+    #[test]
+    fn read_to_cow_str_borrowed() {
+        init_test_log();
+        let agent = crate::Agent::new_with_defaults();
+        let mut res = agent.get("https://my.test/robots.txt").call().unwrap();
+
+        let s = res.body_mut().read_to_cow_str().unwrap();
+        assert!(matches!(s, Cow::Borrowed(_)));
+        assert_eq!(s, "User-agent: *\nDisallow: /deny\n");
+
+        // Reading on gives the end of the body.
+        assert_eq!(res.body_mut().read_to_string().unwrap(), "");
+        assert_eq!(agent.pool_count(), 1);
+    }
+
+    #[test]
+    fn read_to_cow_str_borrowed_then_drop() {
+        init_test_log();
+        let agent = crate::Agent::new_with_defaults();
+        let mut res = agent.get("https://my.test/robots.txt").call().unwrap();
+
+        let s = res.body_mut().read_to_cow_str().unwrap();
+        assert!(matches!(s, Cow::Borrowed(_)));
+
+        // Dropping the body still returns the connection to the pool.
+        drop(res);
+        assert_eq!(agent.pool_count(), 1);
+    }
+
+    #[test]
+    fn read_to_cow_str_owned() {
+        init_test_log();
+        set_handler(
+            "/get",
+            200,
+            &[("transfer-encoding", "chunked")],
+            b"5\r\nhello\r\n0\r\n\r\n",
+        );
+        let mut res = crate::get("https://my.test/get").call().unwrap();
+        let s = res.body_mut().read_to_cow_str().unwrap();
+        assert!(matches!(s, Cow::Owned(_)));
+        assert_eq!(s, "hello");
+    }
+
     #[test]
     fn content_type_without_charset() {
         init_test_log();
@@ -795,4 +1354,188 @@ mod test {
         let err = crate::get("https://my.test/get").call().unwrap_err();
         assert!(matches!(err, Error::LargeResponseHeader(_, _)));
     }
+
+    #[test]
+    fn max_response_body_size() {
+        init_test_log();
+        let config = crate::config::Config::builder()
+            .max_response_body_size(Some(50))
+            .build();
+
+        // Applies to readers.
+        let agent: crate::Agent = config.clone().into();
+        let mut res = agent.get("https://my.test/bytes/100").call().unwrap();
+        let err = res
+            .body_mut()
+            .as_reader()
+            .read_to_end(&mut vec![])
+            .unwrap_err();
+        assert!(matches!(Error::from(err), Error::BodyExceedsLimit(50)));
+
+        // Applies to read_to_vec() instead of 10MB.
+        let agent: crate::Agent = config.clone().into();
+        let mut res = agent.get("https://my.test/bytes/100").call().unwrap();
+        let err = res.body_mut().read_to_vec().unwrap_err();
+        assert!(matches!(err, Error::BodyExceedsLimit(50)));
+
+        // An explicit limit overrides.
+        let agent: crate::Agent = config.into();
+        let mut res = agent.get("https://my.test/bytes/100").call().unwrap();
+        let bytes = res
+            .body_mut()
+            .with_config()
+            .limit(200)
+            .read_to_vec()
+            .unwrap();
+        assert_eq!(bytes.len(), 100);
+    }
+
+    #[test]
+    fn tee() {
+        use std::io::Write;
+        use std::sync::{Arc, Mutex};
+
+        #[derive(Clone, Default)]
+        struct Shared(Arc<Mutex<Vec<u8>>>);
+        impl Write for Shared {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().write(buf)
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        init_test_log();
+        let copy = Shared::default();
+        let mut res = crate::get("https://my.test/robots.txt").call().unwrap();
+        let mut body = String::new();
+        res.body_mut()
+            .with_config()
+            .tee(copy.clone())
+            .read_to_string(&mut body)
+            .unwrap();
+
+        assert_eq!(body, "User-agent: *\nDisallow: /deny\n");
+        assert_eq!(*copy.0.lock().unwrap(), body.as_bytes());
+    }
+
+    #[test]
+    #[cfg(feature = "charset")]
+    fn charset_override() {
+        init_test_log();
+        // 日本 in Shift_JIS, labeled as utf-8.
+        set_handler(
+            "/shift-jis",
+            200,
+            &[
+                ("content-type", "text/plain; charset=utf-8"),
+                ("content-length", "4"),
+            ],
+            &[0x93, 0xfa, 0x96, 0x7b],
+        );
+        let mut res = crate::get("https://my.test/shift-jis").call().unwrap();
+        let s = res
+            .body_mut()
+            .with_config()
+            .charset("shift_jis")
+            .read_to_string()
+            .unwrap();
+        assert_eq!(s, "日本");
+
+        // Unknown label falls back to the header.
+        let mut res = crate::get("https://my.test/robots.txt").call().unwrap();
+        let s = res
+            .body_mut()
+            .with_config()
+            .charset("not-a-charset")
+            .read_to_string()
+            .unwrap();
+        assert_eq!(s, "User-agent: *\nDisallow: /deny\n");
+    }
+
+    #[test]
+    fn raw_reader() {
+        init_test_log();
+        set_handler(
+            "/raw-gzip",
+            200,
+            &[
+                ("content-encoding", "gzip"),
+                ("content-type", "text/plain; charset=iso-8859-1"),
+                ("content-length", "8"),
+            ],
+            b"not\xe5gzip",
+        );
+        let res = crate::get("https://my.test/raw-gzip").call().unwrap();
+        let mut bytes = vec![];
+        res.into_body()
+            .into_raw_reader()
+            .read_to_end(&mut bytes)
+            .unwrap();
+        assert_eq!(bytes, b"not\xe5gzip");
+    }
+
+    #[test]
+    fn was_decompressed() {
+        init_test_log();
+        let res = crate::get("https://my.test/bytes/100").call().unwrap();
+        assert_eq!(res.body().was_decompressed(), None);
+
+        set_handler(
+            "/unknown-encoding",
+            200,
+            &[("content-encoding", "zstd"), ("content-length", "2")],
+            b"ok",
+        );
+        let res = crate::get("https://my.test/unknown-encoding")
+            .call()
+            .unwrap();
+        assert_eq!(res.body().was_decompressed(), None);
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn read_json_lines() {
+        init_test_log();
+        let mut res = crate::get("https://my.test/stream/3").call().unwrap();
+        let ids: Vec<u64> = res
+            .body_mut()
+            .read_json_lines::<serde_json::Value>()
+            .map(|v| v.unwrap()["id"].as_u64().unwrap())
+            .collect();
+        assert_eq!(ids, [0, 1, 2]);
+
+        set_handler(
+            "/bad-json-lines",
+            200,
+            &[("content-length", "12")],
+            b"{\"id\": 0}\nx\n",
+        );
+        let mut res = crate::get("https://my.test/bad-json-lines").call().unwrap();
+        let mut iter = res.body_mut().read_json_lines::<serde_json::Value>();
+        assert!(iter.next().unwrap().is_ok());
+        assert!(matches!(iter.next(), Some(Err(Error::Json(_)))));
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn into_json_reader() {
+        use serde_json::{Deserializer, Value};
+
+        init_test_log();
+        let res = crate::get("https://my.test/stream/3").call().unwrap();
+        let reader = res.into_body().into_json_reader(1000);
+        let ids: Vec<u64> = Deserializer::from_reader(reader)
+            .into_iter::<Value>()
+            .map(|v| v.unwrap()["id"].as_u64().unwrap())
+            .collect();
+        assert_eq!(ids, [0, 1, 2]);
+
+        let res = crate::get("https://my.test/stream/3").call().unwrap();
+        let reader = res.into_body().into_json_reader(15);
+        let result: Result<Vec<Value>, _> = Deserializer::from_reader(reader).into_iter().collect();
+        assert!(result.unwrap_err().is_io());
+    }
 }
@@ -0,0 +1,336 @@
+use std::fmt;
+use std::io::{self, Read};
+
+use http::{HeaderMap, HeaderName, HeaderValue};
+
+use crate::http;
+use crate::Error;
+
+use super::BodyReader;
+
+/// Max size of the headers of a single part.
+const MAX_PART_HEADER_SIZE: usize = 64 * 1024;
+
+/// Reader of a `multipart/*` response body.
+///
+/// Created by [`Body::into_multipart()`](crate::Body::into_multipart). The parts are
+/// read one at a time while streaming the body, which means the previous
+/// [`MultipartPart`] must be dropped before getting the next.
+///
+/// ```
+/// use std::io::Read;
+///
+/// let res = ureq::get("http://httpbin.org/multipart").call()?;
+///
+/// let mut multipart = res.into_body().into_multipart()?;
+///
+/// while let Some(mut part) = multipart.next_part()? {
+///     let content_type = part.headers().get("content-type").cloned();
+///
+///     let mut data = vec![];
+///     part.read_to_end(&mut data)?;
+///
+///     println!("{:?}: {} bytes", content_type, data.len());
+/// }
+/// # Ok::<_, ureq::Error>(())
+/// ```
+pub struct MultipartReader {
+    reader: BodyReader<'static>,
+    /// `\r\n--<boundary>`
+    delimiter: Vec<u8>,
+    buf: Vec<u8>,
+    done: bool,
+}
+
+/// A single part of a multipart body.
+///
+/// Reading the part gives the part body.
+pub struct MultipartPart<'a> {
+    headers: HeaderMap,
+    multipart: &'a mut MultipartReader,
+}
+
+impl MultipartReader {
+    pub(crate) fn new(reader: BodyReader<'static>, boundary: &str) -> Self {
+        let mut delimiter = b"\r\n--".to_vec();
+        delimiter.extend_from_slice(boundary.as_bytes());
+
+        MultipartReader {
+            reader,
+            delimiter,
+            // The first delimiter doesn't have a preceding CRLF, unless
+            // there is a preamble. Pretend we got one.
+            buf: b"\r\n".to_vec(),
+            done: false,
+        }
+    }
+
+    /// Get the next part.
+    ///
+    /// Any unread data of the previous part is skipped. Returns `None` once
+    /// the closing delimiter is reached.
+    pub fn next_part(&mut self) -> Result<Option<MultipartPart<'_>>, Error> {
+        if self.done {
+            return Ok(None);
+        }
+
+        // Skip preamble or the rest of the previous part.
+        let mut scratch = [0_u8; 1024];
+        while self.read_part(&mut scratch)? > 0 {}
+
+        // The buffer now starts with the delimiter. We need two more bytes
+        // to tell a closing delimiter from a part.
+        self.fill_to(self.delimiter.len() + 2)?;
+        self.buf.drain(..self.delimiter.len());
+
+        if self.buf.starts_with(b"--") {
+            self.done = true;
+            // Read the epilogue to end, for the connection to be reused.
+            io::copy(&mut self.reader, &mut io::sink())?;
+            return Ok(None);
+        }
+
+        let headers = self.read_headers()?;
+
+        Ok(Some(MultipartPart {
+            headers,
+            multipart: self,
+        }))
+    }
+
+    fn read_headers(&mut self) -> Result<HeaderMap, Error> {
+        // Find the end of the header section. The CRLF ending the delimiter line
+        // is counted, which means a part without headers ends at position 0.
+        let end = loop {
+            if let Some(i) = find(&self.buf, b"\r\n\r\n") {
+                break i;
+            }
+            if self.buf.len() > MAX_PART_HEADER_SIZE {
+                return Err(Error::Multipart("part headers too big"));
+            }
+            if !self.fill()? {
+                return Err(Error::Multipart("unexpected end of body"));
+            }
+        };
+
+        // Skip transport padding after the delimiter.
+        let start = self.buf.iter().position(|c| *c != b' ' && *c != b'\t');
+        if !self.buf[start.unwrap_or(0)..].starts_with(b"\r\n") {
+            return Err(Error::Multipart("bad delimiter line"));
+        }
+
+        let mut headers = HeaderMap::new();
+
+        for line in self.buf[..end].split(|c| *c == b'\n').skip(1) {
+            let line = line.strip_suffix(b"\r").unwrap_or(line);
+            let Some(colon) = line.iter().position(|c| *c == b':') else {
+                return Err(Error::Multipart("bad part header"));
+            };
+            let name = HeaderName::from_bytes(&line[..colon])
+                .map_err(|_| Error::Multipart("bad part header name"))?;
+            let value = HeaderValue::from_bytes(trim(&line[colon + 1..]))
+                .map_err(|_| Error::Multipart("bad part header value"))?;
+            headers.append(name, value);
+        }
+
+        self.buf.drain(..end + 4);
+
+        Ok(headers)
+    }
+
+    /// Read data up until the next delimiter.
+    fn read_part(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let (avail, found) = match find(&self.buf, &self.delimiter) {
+                Some(i) => (i, true),
+                // Hold back enough to find a delimiter split over two reads.
+                None => (
+                    self.buf.len().saturating_sub(self.delimiter.len() - 1),
+                    false,
+                ),
+            };
+
+            // At the delimiter, this gives 0 for the end of the part.
+            if avail > 0 || found {
+                let n = avail.min(out.len());
+                out[..n].copy_from_slice(&self.buf[..n]);
+                self.buf.drain(..n);
+                return Ok(n);
+            }
+
+            if !self.fill()? {
+                return Err(Error::Multipart("unexpected end of body").into_io());
+            }
+        }
+    }
+
+    fn fill_to(&mut self, len: usize) -> io::Result<()> {
+        while self.buf.len() < len {
+            if !self.fill()? {
+                return Err(Error::Multipart("unexpected end of body").into_io());
+            }
+        }
+        Ok(())
+    }
+
+    fn fill(&mut self) -> io::Result<bool> {
+        let mut tmp = [0_u8; 8192];
+        let n = self.reader.read(&mut tmp)?;
+        self.buf.extend_from_slice(&tmp[..n]);
+        Ok(n > 0)
+    }
+}
+
+impl<'a> MultipartPart<'a> {
+    /// The headers of this part.
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+}
+
+impl<'a> Read for MultipartPart<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.multipart.read_part(buf)
+    }
+}
+
+fn trim(mut v: &[u8]) -> &[u8] {
+    while let [b' ' | b'\t', rest @ ..] = v {
+        v = rest;
+    }
+    while let [rest @ .., b' ' | b'\t'] = v {
+        v = rest;
+    }
+    v
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+impl fmt::Debug for MultipartReader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MultipartReader").finish()
+    }
+}
+
+impl<'a> fmt::Debug for MultipartPart<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MultipartPart")
+            .field("headers", &self.headers)
+            .finish()
+    }
+}
+
+#[cfg(all(test, feature = "_test"))]
+mod test {
+    use std::io::Read;
+
+    use crate::test::init_test_log;
+    use crate::transport::set_handler;
+    use crate::{Body, Error};
+
+    fn read_part(part: &mut super::MultipartPart) -> String {
+        let mut s = String::new();
+        part.read_to_string(&mut s).unwrap();
+        s
+    }
+
+    #[test]
+    fn two_parts() {
+        init_test_log();
+        let agent: crate::Agent = crate::config::Config::default().into();
+        let res = agent.get("http://httpbin.org/multipart").call().unwrap();
+        let mut multipart = res.into_body().into_multipart().unwrap();
+
+        let mut part = multipart.next_part().unwrap().unwrap();
+        assert_eq!(part.headers().get("content-type").unwrap(), "text/plain");
+        assert_eq!(read_part(&mut part), "first part");
+
+        let mut part = multipart.next_part().unwrap().unwrap();
+        assert_eq!(
+            part.headers().get("content-type").unwrap(),
+            "application/json"
+        );
+        assert_eq!(read_part(&mut part), r#"{"second":true}"#);
+
+        assert!(multipart.next_part().unwrap().is_none());
+        assert!(multipart.next_part().unwrap().is_none());
+
+        // The body was read to end, so the connection is reused.
+        assert_eq!(agent.pool_count(), 1);
+    }
+
+    #[test]
+    fn skip_unread_part() {
+        init_test_log();
+        let res = crate::get("http://httpbin.org/multipart").call().unwrap();
+        let mut multipart = res.into_body().into_multipart().unwrap();
+
+        multipart.next_part().unwrap().unwrap();
+        let mut part = multipart.next_part().unwrap().unwrap();
+        assert_eq!(read_part(&mut part), r#"{"second":true}"#);
+    }
+
+    #[test]
+    fn preamble_and_split_reads() {
+        init_test_log();
+        set_handler(
+            "/multipart-preamble",
+            200,
+            &[("content-type", "multipart/byteranges; boundary=b")],
+            b"preamble\r\n--b  \r\n\r\nno headers\r\n--b\r\nContent-Range: bytes 0-1/2\r\n\r\nab\r\n--b--",
+        );
+        let res = crate::get("https://my.test/multipart-preamble")
+            .call()
+            .unwrap();
+
+        // Read the body one byte at a time to split the delimiters.
+        struct OneByte(crate::BodyReader<'static>);
+        impl Read for OneByte {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                let max = buf.len().min(1);
+                self.0.read(&mut buf[..max])
+            }
+        }
+        let reader = OneByte(res.into_body().into_reader());
+        let mut multipart =
+            super::MultipartReader::new(Body::builder().reader(reader).into_reader(), "b");
+
+        let mut part = multipart.next_part().unwrap().unwrap();
+        assert!(part.headers().is_empty());
+        assert_eq!(read_part(&mut part), "no headers");
+
+        let mut part = multipart.next_part().unwrap().unwrap();
+        assert_eq!(part.headers().get("content-range").unwrap(), "bytes 0-1/2");
+        assert_eq!(read_part(&mut part), "ab");
+
+        assert!(multipart.next_part().unwrap().is_none());
+    }
+
+    #[test]
+    fn no_boundary() {
+        init_test_log();
+        let res = crate::get("http://httpbin.org/get").call().unwrap();
+        let err = res.into_body().into_multipart().unwrap_err();
+        assert!(matches!(err, Error::Multipart(_)));
+    }
+
+    #[test]
+    fn truncated() {
+        init_test_log();
+        set_handler(
+            "/multipart-truncated",
+            200,
+            &[("content-type", "multipart/mixed; boundary=b")],
+            b"--b\r\n\r\npart without end",
+        );
+        let res = crate::get("https://my.test/multipart-truncated")
+            .call()
+            .unwrap();
+        let mut multipart = res.into_body().into_multipart().unwrap();
+        let mut part = multipart.next_part().unwrap().unwrap();
+        let err = Error::from(part.read_to_end(&mut vec![]).unwrap_err());
+        assert!(matches!(err, Error::Multipart(_)));
+    }
+}
@@ -1,19 +1,27 @@
 //! Agent configuration
 
+use std::collections::hash_map::RandomState;
 use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+#[cfg(feature = "socket2")]
+use std::net::IpAddr;
 use std::net::SocketAddr;
 use std::sync::Arc;
 use std::time::Duration;
 
-use http::Uri;
+use http::uri::Authority;
+use http::{HeaderMap, StatusCode, Uri};
 
 use crate::http;
 use crate::middleware::{Middleware, MiddlewareChain};
-use crate::{Agent, AsSendBody, Proxy, RequestBuilder};
+use crate::{AbortHandle, Agent, AsSendBody, LifecycleEvent, Proxy, RequestBuilder};
 
 #[cfg(feature = "_tls")]
 use crate::tls::TlsConfig;
 
+#[cfg(feature = "cookies")]
+use crate::SharedCookieJar;
+
 pub use ureq_proto::client::flow::RedirectAuthHeaders;
 
 mod private {
@@ -136,25 +144,53 @@ use typestate::RequestScope;
 #[derive(Clone)]
 pub struct Config {
     http_status_as_error: bool,
+    error_on_server_error_only: bool,
     https_only: bool,
+    error_context: bool,
     ip_family: IpFamily,
     #[cfg(feature = "_tls")]
     tls_config: TlsConfig,
+    #[cfg(feature = "_tls")]
+    panic_on_missing_tls: bool,
     proxy: Option<Proxy>,
+    #[cfg(feature = "cookies")]
+    cookie_jar: Option<Arc<SharedCookieJar>>,
+    address_sort: Option<AddressSort>,
+    resolve_overrides: Arc<Vec<(String, u16, SocketAddr)>>,
     no_delay: bool,
+    #[cfg(feature = "socket2")]
+    tcp_keepalive: Option<Duration>,
+    #[cfg(feature = "socket2")]
+    local_address: Option<IpAddr>,
+    #[cfg(all(feature = "socket2", target_os = "linux"))]
+    bind_device: Option<String>,
     max_redirects: u32,
     max_redirects_will_error: bool,
+    detect_redirect_loops: bool,
     redirect_auth_headers: RedirectAuthHeaders,
+    on_redirect: Option<OnRedirect>,
+    observer: Option<Observer>,
+    default_headers: HeaderMap,
     user_agent: AutoHeaderValue,
     accept: AutoHeaderValue,
     accept_encoding: AutoHeaderValue,
     timeouts: Timeouts,
     max_response_header_size: usize,
+    max_response_headers: usize,
+    max_request_header_size: usize,
+    max_response_body_size: Option<u64>,
+    no_content_body_as_error: bool,
     input_buffer_size: usize,
     output_buffer_size: usize,
+    output_chunk_size: Option<usize>,
+    connection_pooling: bool,
     max_idle_connections: usize,
     max_idle_connections_per_host: usize,
+    max_concurrent_connections: usize,
     max_idle_age: Duration,
+    pool_checkout_probe: ProbeKind,
+    reuse_connection_if: Option<ReuseConnectionIf>,
+    abort_handle: Option<AbortHandle>,
 
     // Chain built for middleware.
     pub(crate) middleware: MiddlewareChain,
@@ -203,6 +239,15 @@ impl Config {
         self.http_status_as_error
     }
 
+    /// Whether only 5xx HTTP status codes are treated as errors.
+    ///
+    /// Only has an effect when [`http_status_as_error()`](Self::http_status_as_error) is true.
+    ///
+    /// Defaults to `false`.
+    pub fn error_on_server_error_only(&self) -> bool {
+        self.error_on_server_error_only
+    }
+
     /// Whether to limit requests (including redirects) to https only
     ///
     /// Defaults to `false`.
@@ -210,6 +255,13 @@ impl Config {
         self.https_only
     }
 
+    /// Whether errors carry the method and uri of the request.
+    ///
+    /// Defaults to `false`.
+    pub fn error_context(&self) -> bool {
+        self.error_context
+    }
+
     /// Configuration of IPv4/IPv6.
     ///
     /// This affects the resolver.
@@ -227,6 +279,17 @@ impl Config {
         &self.tls_config
     }
 
+    /// Whether to panic when the uri scheme is https, but the configured TLS
+    /// provider is not enabled by feature flags.
+    ///
+    /// When false, the request fails with [`Error::NoTlsProvider`](crate::Error::NoTlsProvider).
+    ///
+    /// Defaults to `true`.
+    #[cfg(feature = "_tls")]
+    pub fn panic_on_missing_tls(&self) -> bool {
+        self.panic_on_missing_tls
+    }
+
     /// Proxy configuration.
     ///
     /// Picked up from environment when using [`Config::default()`] or
@@ -234,6 +297,31 @@ impl Config {
         self.proxy.as_ref()
     }
 
+    /// Cookie jar to use instead of the agent's own.
+    ///
+    /// Defaults to `None`.
+    #[cfg(feature = "cookies")]
+    pub fn cookie_jar(&self) -> Option<&Arc<SharedCookieJar>> {
+        self.cookie_jar.as_ref()
+    }
+
+    /// Hook to reorder the resolved socket addresses.
+    ///
+    /// Defaults to `None`.
+    pub fn address_sort(&self) -> Option<&AddressSort> {
+        self.address_sort.as_ref()
+    }
+
+    /// Fixed address for a host and port, bypassing the resolver.
+    ///
+    /// Defaults to no overrides.
+    pub fn resolve_override(&self, host: &str, port: u16) -> Option<SocketAddr> {
+        self.resolve_overrides
+            .iter()
+            .find(|(h, p, _)| h.eq_ignore_ascii_case(host) && *p == port)
+            .map(|(_, _, addr)| *addr)
+    }
+
     /// Disable Nagle's algorithm
     ///
     /// Set TCP_NODELAY. It's up to the transport whether this flag is honored.
@@ -243,6 +331,37 @@ impl Config {
         self.no_delay
     }
 
+    /// TCP keepalive for the connection
+    ///
+    /// When set, enables SO_KEEPALIVE with the duration as the idle time before
+    /// the first keepalive probe. It's up to the transport whether this is honored.
+    ///
+    /// Defaults to `None`.
+    #[cfg(feature = "socket2")]
+    pub fn tcp_keepalive(&self) -> Option<Duration> {
+        self.tcp_keepalive
+    }
+
+    /// Local address to bind outgoing connections to.
+    ///
+    /// It's up to the transport whether this is honored.
+    ///
+    /// Defaults to `None`.
+    #[cfg(feature = "socket2")]
+    pub fn local_address(&self) -> Option<IpAddr> {
+        self.local_address
+    }
+
+    /// Network device to bind outgoing connections to (SO_BINDTODEVICE).
+    ///
+    /// It's up to the transport whether this is honored.
+    ///
+    /// Defaults to `None`.
+    #[cfg(all(feature = "socket2", target_os = "linux"))]
+    pub fn bind_device(&self) -> Option<&str> {
+        self.bind_device.as_deref()
+    }
+
     /// The max number of redirects to follow before giving up.
     ///
     /// Whe max redirects are reached, the behavior is controlled by the
@@ -267,6 +386,13 @@ impl Config {
         self.max_redirects_will_error
     }
 
+    /// If we should error when a redirect goes to an already visited uri.
+    ///
+    /// Defaults to false
+    pub fn detect_redirect_loops(&self) -> bool {
+        self.detect_redirect_loops
+    }
+
     /// How to handle `Authorization` headers when following redirects
     ///
     /// * `Never` (the default) means the authorization header is never attached to a redirected call.
@@ -277,6 +403,27 @@ impl Config {
         self.redirect_auth_headers
     }
 
+    /// Hook to inspect, stop or rewrite redirects.
+    ///
+    /// Defaults to `None`.
+    pub fn on_redirect(&self) -> Option<&OnRedirect> {
+        self.on_redirect.as_ref()
+    }
+
+    /// Hook called at each stage of a call.
+    ///
+    /// Defaults to `None`.
+    pub fn observer(&self) -> Option<&Observer> {
+        self.observer.as_ref()
+    }
+
+    /// Headers added to every request.
+    ///
+    /// Defaults to no headers.
+    pub fn default_headers(&self) -> &HeaderMap {
+        &self.default_headers
+    }
+
     /// Value to use for the `User-Agent` header.
     ///
     /// This can be overridden by setting a `user-agent` header on the request
@@ -293,8 +440,12 @@ impl Config {
     /// Value to use for the `Accept` header.
     ///
     /// This agent configured value can be overriden per request by setting the header.
-    //
-    /// Setting a value of `""` on the request or agent level will also not send a header.
+    ///
+    /// The header is one of three states:
+    ///
+    /// * [`AutoHeaderValue::Default`] sends `Accept: */*`.
+    /// * [`AutoHeaderValue::Provided`] (any non-empty string) sends that value.
+    /// * [`AutoHeaderValue::None`] (or a value of `""`) omits the header entirely.
     ///
     /// Defaults to `Default`, which results in `*/*`
     pub fn accept(&self) -> &AutoHeaderValue {
@@ -332,6 +483,49 @@ impl Config {
         self.max_response_header_size
     }
 
+    /// Max size of the HTTP request header.
+    ///
+    /// From the request line, including all headers up until the body.
+    ///
+    /// Defaults to 64kb.
+    pub fn max_request_header_size(&self) -> usize {
+        self.max_request_header_size
+    }
+
+    /// Max number of headers in the HTTP response.
+    ///
+    /// Going over fails with [`Error::TooManyHeaders`](crate::Error::TooManyHeaders).
+    /// This is at most 128, the hard limit of the response parser.
+    ///
+    /// Defaults to 128.
+    pub fn max_response_headers(&self) -> usize {
+        self.max_response_headers
+    }
+
+    /// Max size of the HTTP response body.
+    ///
+    /// Applied as the default limit to all reads of the body, also
+    /// [`Body::as_reader()`](crate::Body::as_reader). An explicit
+    /// [`BodyWithConfig::limit()`](crate::BodyWithConfig::limit) overrides it.
+    ///
+    /// Defaults to `None`, which means `read_to_string()`, `read_to_vec()` and
+    /// `read_json()` are limited to 10MB and the readers are unlimited.
+    pub fn max_response_body_size(&self) -> Option<u64> {
+        self.max_response_body_size
+    }
+
+    /// Whether a `204` or `304` response with a body is an error.
+    ///
+    /// These statuses never have a body, but broken servers sometimes send one
+    /// anyway. ureq always treats the response as bodyless, and if there is any
+    /// sign of a body, the connection is closed instead of being pooled. Set to
+    /// `true` to instead fail with [`Error::UnexpectedResponseBody`](crate::Error::UnexpectedResponseBody).
+    ///
+    /// Defaults to `false`.
+    pub fn no_content_body_as_error(&self) -> bool {
+        self.no_content_body_as_error
+    }
+
     /// Default size of the input buffer
     ///
     /// The default connectors use this setting.
@@ -350,6 +544,23 @@ impl Config {
         self.output_buffer_size
     }
 
+    /// Max size of each chunk when sending a chunked request body.
+    ///
+    /// Defaults to `None`, which means as large as fits in the output buffer.
+    pub fn output_chunk_size(&self) -> Option<usize> {
+        self.output_chunk_size
+    }
+
+    /// Whether connections are kept alive and reused between requests.
+    ///
+    /// When false, every request opens a new connection, sends `Connection: close`
+    /// and closes the connection afterwards.
+    ///
+    /// Defaults to `true`
+    pub fn connection_pooling(&self) -> bool {
+        self.connection_pooling
+    }
+
     /// Max number of idle pooled connections overall.
     ///
     /// This setting has no effect when used per-request.
@@ -368,6 +579,19 @@ impl Config {
         self.max_idle_connections_per_host
     }
 
+    /// Max number of connections in use at the same time.
+    ///
+    /// A request that would exceed the limit waits (up to the connect timeout) for
+    /// another request to return its connection to the pool, or close it. Idle
+    /// connections in the pool are not counted. 0 means no limit.
+    ///
+    /// This setting has no effect when used per-request.
+    ///
+    /// Defaults to 0
+    pub fn max_concurrent_connections(&self) -> usize {
+        self.max_concurrent_connections
+    }
+
     /// Max duration to keep an idle connection in the pool
     ///
     /// This can also be configured per-request to be shorter than the pool.
@@ -380,6 +604,27 @@ impl Config {
     pub fn max_idle_age(&self) -> Duration {
         self.max_idle_age
     }
+
+    /// How to check a pooled connection before using it.
+    ///
+    /// Defaults to `ProbeKind::NonBlockingRead`.
+    pub fn pool_checkout_probe(&self) -> ProbeKind {
+        self.pool_checkout_probe
+    }
+
+    /// Predicate deciding whether a finished connection goes back to the pool.
+    ///
+    /// Defaults to `None`.
+    pub fn reuse_connection_if(&self) -> Option<&ReuseConnectionIf> {
+        self.reuse_connection_if.as_ref()
+    }
+
+    /// Handle to abort requests from another thread.
+    ///
+    /// Defaults to `None`.
+    pub fn abort_handle(&self) -> Option<&AbortHandle> {
+        self.abort_handle.as_ref()
+    }
 }
 
 /// Builder of [`Config`]
@@ -399,6 +644,32 @@ impl<Scope: private::ConfigScope> ConfigBuilder<Scope> {
         self
     }
 
+    /// Whether only 5xx HTTP status codes are treated as
+    /// [`Err(Error::StatusCode))`](crate::Error::StatusCode).
+    ///
+    /// For APIs where 4xx is expected, such as a 404 for a missing resource, while
+    /// 5xx should still be an error. Only has an effect when
+    /// [`http_status_as_error()`](Self::http_status_as_error) is true.
+    ///
+    /// ```
+    /// use ureq::Agent;
+    ///
+    /// let agent: Agent = Agent::config_builder()
+    ///     .error_on_server_error_only(true)
+    ///     .build()
+    ///     .into();
+    ///
+    /// let res = agent.get("http://httpbin.org/status/404").call()?;
+    /// assert_eq!(res.status(), 404);
+    /// # Ok::<_, ureq::Error>(())
+    /// ```
+    ///
+    /// Defaults to `false`.
+    pub fn error_on_server_error_only(mut self, v: bool) -> Self {
+        self.config().error_on_server_error_only = v;
+        self
+    }
+
     /// Whether to limit requests (including redirects) to https only
     ///
     /// Defaults to `false`.
@@ -407,6 +678,38 @@ impl<Scope: private::ConfigScope> ConfigBuilder<Scope> {
         self
     }
 
+    /// Whether errors carry the method and uri of the request.
+    ///
+    /// When enabled, errors from a call are wrapped in [`Error::WithContext`], with the
+    /// method and uri of the request as sent (before any redirects). This makes the errors
+    /// more useful in logs, but means matching on the error must first look through
+    /// the wrapper, for instance with [`Error::without_context()`].
+    ///
+    /// ```no_run
+    /// use ureq::Agent;
+    /// use ureq::config::Config;
+    ///
+    /// let agent: Agent = Config::builder()
+    ///     .error_context(true)
+    ///     .build()
+    ///     .into();
+    ///
+    /// if let Err(e) = agent.get("http://httpbin.org/get").call() {
+    ///     // For example "GET http://httpbin.org/get: timeout: global"
+    ///     eprintln!("{}", e);
+    ///
+    ///     let (method, uri) = e.request_context().unwrap();
+    ///     assert_eq!(method, "GET");
+    ///     assert_eq!(uri, "http://httpbin.org/get");
+    /// }
+    /// ```
+    ///
+    /// Defaults to `false`.
+    pub fn error_context(mut self, v: bool) -> Self {
+        self.config().error_context = v;
+        self
+    }
+
     /// Configuration of IPv4/IPv6.
     ///
     /// This affects the resolver.
@@ -420,21 +723,149 @@ impl<Scope: private::ConfigScope> ConfigBuilder<Scope> {
     /// Config for TLS.
     ///
     /// This config is generic for all TLS connectors.
+    ///
+    /// Can also be set per request, for instance to use a different client
+    /// certificate. Pooled connections are only reused for requests with the
+    /// same client certificate.
+    ///
+    /// ```
+    /// use ureq::tls::{ClientCert, TlsConfig};
+    ///
+    /// # fn client_cert() -> ClientCert { unimplemented!() }
+    /// # fn run() -> Result<(), ureq::Error> {
+    /// let tls_config = TlsConfig::builder()
+    ///     .client_cert(Some(client_cert()))
+    ///     .build();
+    ///
+    /// let res = ureq::get("https://mtls.example.test")
+    ///     .config()
+    ///     .tls_config(tls_config)
+    ///     .build()
+    ///     .call()?;
+    /// # Ok(()) }
+    /// ```
     #[cfg(feature = "_tls")]
     pub fn tls_config(mut self, v: TlsConfig) -> Self {
         self.config().tls_config = v;
         self
     }
 
+    /// Whether to panic when the uri scheme is https, but the configured TLS
+    /// provider is not enabled by feature flags.
+    ///
+    /// A missing feature flag is a build mistake, which is why the default is to panic.
+    /// Set this to false to instead fail the request with
+    /// [`Error::NoTlsProvider`](crate::Error::NoTlsProvider).
+    ///
+    /// Defaults to `true`.
+    #[cfg(feature = "_tls")]
+    pub fn panic_on_missing_tls(mut self, v: bool) -> Self {
+        self.config().panic_on_missing_tls = v;
+        self
+    }
+
     /// Proxy configuration.
     ///
     /// Picked up from environment when using [`Config::default()`] or
     /// [`Agent::new_with_defaults()`][crate::Agent::new_with_defaults].
+    ///
+    /// This can also be set per request, to use a different proxy than the agent,
+    /// or `None` to connect directly even if the agent has a proxy.
+    ///
+    /// ```
+    /// use ureq::{Agent, Proxy};
+    ///
+    /// let agent: Agent = Agent::config_builder()
+    ///     .proxy(Some(Proxy::new("http://proxy.example.test:8080")?))
+    ///     .build()
+    ///     .into();
+    ///
+    /// // This request bypasses the agent proxy.
+    /// let request = agent.get("http://internal.example.test")
+    ///     .config()
+    ///     .proxy(None)
+    ///     .build();
+    /// # Ok::<_, ureq::Error>(())
+    /// ```
     pub fn proxy(mut self, v: Option<Proxy>) -> Self {
         self.config().proxy = v;
         self
     }
 
+    /// Cookie jar to use instead of the agent's own.
+    ///
+    /// Cookies are sent from, and stored in, this jar. Set per request, one agent can
+    /// serve many sessions with isolated cookies, while sharing the connection pool.
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use ureq::SharedCookieJar;
+    ///
+    /// let session = Arc::new(SharedCookieJar::new());
+    ///
+    /// let res = ureq::get("http://httpbin.org/get")
+    ///     .config()
+    ///     .cookie_jar(Some(session.clone()))
+    ///     .build()
+    ///     .call()?;
+    ///
+    /// let count = session.lock().iter().count();
+    /// # Ok::<_, ureq::Error>(())
+    /// ```
+    ///
+    /// Defaults to `None`, which uses the agent's jar.
+    #[cfg(feature = "cookies")]
+    pub fn cookie_jar(mut self, v: Option<Arc<SharedCookieJar>>) -> Self {
+        self.config().cookie_jar = v;
+        self
+    }
+
+    /// Hook to reorder the resolved socket addresses.
+    ///
+    /// The function is called with the addresses from the resolver before the
+    /// connector tries them in order. This can be used to spread load over
+    /// a host's many A/AAAA records (see [`shuffle`]), or to prefer addresses
+    /// by some other measure, such as latency.
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use ureq::config::{shuffle, Config};
+    ///
+    /// let config = Config::builder()
+    ///     .address_sort(Arc::new(shuffle))
+    ///     .build();
+    /// ```
+    ///
+    /// Defaults to `None`.
+    pub fn address_sort(mut self, v: AddressSort) -> Self {
+        self.config().address_sort = Some(v);
+        self
+    }
+
+    /// Connect to a fixed address for a host and port, bypassing the resolver.
+    ///
+    /// Like curl's `--resolve`. The request is otherwise unchanged, i.e. the `Host`
+    /// header and the TLS server name are still `host`. Useful for testing against
+    /// a staging server, or pinning a specific CDN edge. Can be called several times
+    /// for different hosts and ports.
+    ///
+    /// ```
+    /// use ureq::config::Config;
+    ///
+    /// let config = Config::builder()
+    ///     .resolve_override("example.com", 443, "10.0.0.5:443".parse().unwrap())
+    ///     .build();
+    /// ```
+    ///
+    /// When using a CONNECT proxy, the override applies to the proxy host, since that
+    /// is what is resolved. Defaults to no overrides.
+    pub fn resolve_override(mut self, host: &str, port: u16, addr: SocketAddr) -> Self {
+        let overrides = Arc::make_mut(&mut self.config().resolve_overrides);
+        overrides.retain(|(h, p, _)| !(h.eq_ignore_ascii_case(host) && *p == port));
+        overrides.push((host.to_string(), port, addr));
+        self
+    }
+
     /// Disable Nagle's algorithm
     ///
     /// Set TCP_NODELAY. It's up to the transport whether this flag is honored.
@@ -445,6 +876,50 @@ impl<Scope: private::ConfigScope> ConfigBuilder<Scope> {
         self
     }
 
+    /// TCP keepalive for the connection
+    ///
+    /// When set, enables SO_KEEPALIVE with the duration as the idle time before
+    /// the first keepalive probe. This helps keeping long-idle pooled connections
+    /// alive through load balancers and NATs that silently drop them.
+    ///
+    /// It's up to the transport whether this is honored.
+    ///
+    /// Defaults to `None`.
+    #[cfg(feature = "socket2")]
+    pub fn tcp_keepalive(mut self, v: Option<Duration>) -> Self {
+        self.config().tcp_keepalive = v;
+        self
+    }
+
+    /// Local address to bind outgoing connections to.
+    ///
+    /// Selects the source IP, and thereby the outbound interface, on multi-homed
+    /// hosts. Resolved addresses of a different IP family than the local address
+    /// are skipped.
+    ///
+    /// It's up to the transport whether this is honored.
+    ///
+    /// Defaults to `None`.
+    #[cfg(feature = "socket2")]
+    pub fn local_address(mut self, v: Option<IpAddr>) -> Self {
+        self.config().local_address = v;
+        self
+    }
+
+    /// Network device to bind outgoing connections to.
+    ///
+    /// Sets SO_BINDTODEVICE, for instance `"eth1"` or the name of a VRF. This
+    /// typically requires the `CAP_NET_RAW` capability.
+    ///
+    /// It's up to the transport whether this is honored.
+    ///
+    /// Defaults to `None`.
+    #[cfg(all(feature = "socket2", target_os = "linux"))]
+    pub fn bind_device(mut self, v: Option<String>) -> Self {
+        self.config().bind_device = v;
+        self
+    }
+
     /// The max number of redirects to follow before giving up.
     ///
     /// Whe max redirects are reached, the behavior is controlled by the
@@ -471,6 +946,21 @@ impl<Scope: private::ConfigScope> ConfigBuilder<Scope> {
         self
     }
 
+    /// If we should error when a redirect goes to an already visited uri.
+    ///
+    /// Without this, a redirect loop such as `/a → /b → /a` is followed until
+    /// `max_redirects` is reached. With this set to `true`, the loop results in an
+    /// [`Error::RedirectLoop`](crate::Error::RedirectLoop) as soon as it is detected.
+    ///
+    /// Note that some servers legitimately redirect back to a visited uri, for
+    /// instance after setting a cookie.
+    ///
+    /// Defaults to false
+    pub fn detect_redirect_loops(mut self, v: bool) -> Self {
+        self.config().detect_redirect_loops = v;
+        self
+    }
+
     /// How to handle `Authorization` headers when following redirects
     ///
     /// * `Never` (the default) means the authorization header is never attached to a redirected call.
@@ -482,6 +972,89 @@ impl<Scope: private::ConfigScope> ConfigBuilder<Scope> {
         self
     }
 
+    /// Hook to inspect, stop or rewrite redirects.
+    ///
+    /// The function is called with the uri redirected from, the status and the
+    /// uri redirected to, before following each redirect. Returning
+    /// [`RedirectAction::Stop`] gives back the redirect response as is, with an
+    /// empty body.
+    ///
+    /// This can for instance be used to not follow redirects to private networks
+    /// when fetching user provided urls.
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use ureq::config::{Config, RedirectAction};
+    ///
+    /// let config = Config::builder()
+    ///     .on_redirect(Arc::new(|_from, _status, to| {
+    ///         if to.host() == Some("localhost") {
+    ///             RedirectAction::Stop
+    ///         } else {
+    ///             RedirectAction::Follow
+    ///         }
+    ///     }))
+    ///     .build();
+    /// ```
+    ///
+    /// Defaults to `None`.
+    pub fn on_redirect(mut self, v: OnRedirect) -> Self {
+        self.config().on_redirect = Some(v);
+        self
+    }
+
+    /// Hook called at each stage of a call.
+    ///
+    /// The hook is called from the thread making the request, as the call passes
+    /// each [`LifecycleStage`](crate::LifecycleStage), with the time elapsed since
+    /// the call started. This can be used for tracing spans. The hook should be quick,
+    /// since it holds up the request.
+    ///
+    /// ```
+    /// use ureq::LifecycleStage;
+    /// use ureq::config::Config;
+    ///
+    /// let config = Config::builder()
+    ///     .observer(|event| {
+    ///         if event.stage == LifecycleStage::ResponseReceived {
+    ///             println!("First byte after {:?}", event.elapsed);
+    ///         }
+    ///     })
+    ///     .build();
+    /// ```
+    ///
+    /// Defaults to `None`.
+    pub fn observer(mut self, v: impl Fn(LifecycleEvent) + Send + Sync + 'static) -> Self {
+        self.config().observer = Some(Arc::new(v));
+        self
+    }
+
+    /// Headers added to every request.
+    ///
+    /// A header set on the request takes precedence, and the default is not
+    /// sent at all for that header name. A default `user-agent` or `accept`
+    /// header takes precedence over [`user_agent()`](Self::user_agent) and
+    /// [`accept()`](Self::accept).
+    ///
+    /// ```
+    /// use ureq::http::{HeaderMap, HeaderValue};
+    /// use ureq::Agent;
+    ///
+    /// let mut headers = HeaderMap::new();
+    /// headers.insert("x-api-key", HeaderValue::from_static("secret"));
+    ///
+    /// let agent: Agent = Agent::config_builder()
+    ///     .default_headers(headers)
+    ///     .build()
+    ///     .into();
+    /// ```
+    ///
+    /// Defaults to no headers.
+    pub fn default_headers(mut self, v: HeaderMap) -> Self {
+        self.config().default_headers = v;
+        self
+    }
+
     /// Value to use for the `User-Agent` header.
     ///
     /// This can be overridden by setting a `user-agent` header on the request
@@ -499,8 +1072,12 @@ impl<Scope: private::ConfigScope> ConfigBuilder<Scope> {
     /// Value to use for the `Accept` header.
     ///
     /// This agent configured value can be overriden per request by setting the header.
-    //
-    /// Setting a value of `""` on the request or agent level will also not send a header.
+    ///
+    /// The header is one of three states:
+    ///
+    /// * [`AutoHeaderValue::Default`] sends `Accept: */*`.
+    /// * [`AutoHeaderValue::Provided`] (any non-empty string) sends that value.
+    /// * [`AutoHeaderValue::None`] (or a value of `""`) omits the header entirely.
     ///
     /// Defaults to `Default`, which results in `*/*`
     pub fn accept(mut self, v: impl Into<AutoHeaderValue>) -> Self {
@@ -536,6 +1113,57 @@ impl<Scope: private::ConfigScope> ConfigBuilder<Scope> {
         self
     }
 
+    /// Max size of the HTTP request header.
+    ///
+    /// From the request line, including all headers up until the body. A larger
+    /// request, for instance from a very long uri or many headers, fails with
+    /// [`Error::LargeRequestHeader`](crate::Error::LargeRequestHeader) before anything
+    /// is sent. Servers commonly reject such requests with `431`.
+    ///
+    /// Defaults to 64kb.
+    pub fn max_request_header_size(mut self, v: usize) -> Self {
+        self.config().max_request_header_size = v;
+        self
+    }
+
+    /// Max number of headers in the HTTP response.
+    ///
+    /// Going over fails with [`Error::TooManyHeaders`](crate::Error::TooManyHeaders).
+    /// The response parser has a hard limit of 128 headers, higher values are
+    /// clamped to that.
+    ///
+    /// Defaults to 128.
+    pub fn max_response_headers(mut self, v: usize) -> Self {
+        self.config().max_response_headers = v.min(MAX_RESPONSE_HEADERS);
+        self
+    }
+
+    /// Max size of the HTTP response body.
+    ///
+    /// Applied as the default limit to all reads of the body, also
+    /// [`Body::as_reader()`](crate::Body::as_reader). An explicit
+    /// [`BodyWithConfig::limit()`](crate::BodyWithConfig::limit) overrides it.
+    ///
+    /// Defaults to `None`, which means `read_to_string()`, `read_to_vec()` and
+    /// `read_json()` are limited to 10MB and the readers are unlimited.
+    pub fn max_response_body_size(mut self, v: Option<u64>) -> Self {
+        self.config().max_response_body_size = v;
+        self
+    }
+
+    /// Whether a `204` or `304` response with a body is an error.
+    ///
+    /// These statuses never have a body, but broken servers sometimes send one
+    /// anyway. ureq always treats the response as bodyless, and if there is any
+    /// sign of a body, the connection is closed instead of being pooled. Set to
+    /// `true` to instead fail with [`Error::UnexpectedResponseBody`](crate::Error::UnexpectedResponseBody).
+    ///
+    /// Defaults to `false`.
+    pub fn no_content_body_as_error(mut self, v: bool) -> Self {
+        self.config().no_content_body_as_error = v;
+        self
+    }
+
     /// Default size of the input buffer
     ///
     /// The default connectors use this setting.
@@ -556,6 +1184,31 @@ impl<Scope: private::ConfigScope> ConfigBuilder<Scope> {
         self
     }
 
+    /// Max size of each chunk when sending a chunked request body.
+    ///
+    /// Chunks are never larger than what fits in the output buffer, see
+    /// [`output_buffer_size()`](Self::output_buffer_size). Smaller chunks can be needed
+    /// for some servers or proxies, at the cost of more overhead. A value of 0 is
+    /// treated as 1.
+    ///
+    /// Defaults to no limit besides the output buffer.
+    pub fn output_chunk_size(mut self, v: usize) -> Self {
+        self.config().output_chunk_size = Some(v);
+        self
+    }
+
+    /// Whether connections are kept alive and reused between requests.
+    ///
+    /// When false, every request opens a new connection, sends `Connection: close`
+    /// and closes the connection afterwards. This is for servers that mishandle
+    /// keep-alive. A `Connection` header set on the request is left as is.
+    ///
+    /// Defaults to `true`
+    pub fn connection_pooling(mut self, v: bool) -> Self {
+        self.config().connection_pooling = v;
+        self
+    }
+
     /// Max number of idle pooled connections overall.
     ///
     /// This setting has no effect when used per-request.
@@ -576,6 +1229,20 @@ impl<Scope: private::ConfigScope> ConfigBuilder<Scope> {
         self
     }
 
+    /// Max number of connections in use at the same time.
+    ///
+    /// A request that would exceed the limit waits (up to the connect timeout) for
+    /// another request to return its connection to the pool, or close it. Idle
+    /// connections in the pool are not counted. 0 means no limit.
+    ///
+    /// This setting has no effect when used per-request.
+    ///
+    /// Defaults to 0
+    pub fn max_concurrent_connections(mut self, v: usize) -> Self {
+        self.config().max_concurrent_connections = v;
+        self
+    }
+
     /// Max duration to keep an idle connection in the pool
     ///
     /// This can also be configured per-request to be shorter than the pool.
@@ -590,6 +1257,71 @@ impl<Scope: private::ConfigScope> ConfigBuilder<Scope> {
         self
     }
 
+    /// How to check a pooled connection before using it.
+    ///
+    /// A connection can sit idle in the pool for a while, during which the server
+    /// might have closed it. The probe is a tradeoff between latency and reliability.
+    /// See [`ProbeKind`] for the options. A connection that fails the probe is
+    /// discarded, and another pooled connection is tried, or a new one is made.
+    ///
+    /// Defaults to `ProbeKind::NonBlockingRead`.
+    pub fn pool_checkout_probe(mut self, v: ProbeKind) -> Self {
+        self.config().pool_checkout_probe = v;
+        self
+    }
+
+    /// Predicate deciding whether a finished connection goes back to the pool.
+    ///
+    /// The predicate is consulted once a request is done with a connection that
+    /// otherwise could be reused. Returning `false` closes the connection. This can
+    /// be used to cap the number of requests per connection, for instance to force
+    /// a fresh TLS handshake every now and then.
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use ureq::config::Config;
+    ///
+    /// let config = Config::builder()
+    ///     // At most 100 requests per connection.
+    ///     .reuse_connection_if(Arc::new(|c| c.requests() < 100))
+    ///     .build();
+    /// ```
+    ///
+    /// Defaults to `None`.
+    pub fn reuse_connection_if(mut self, v: ReuseConnectionIf) -> Self {
+        self.config().reuse_connection_if = Some(v);
+        self
+    }
+
+    /// Handle to abort requests from another thread.
+    ///
+    /// Requests using this config fail with [`Error::Aborted`](crate::Error::Aborted)
+    /// once [`AbortHandle::abort()`] is called. Set on the agent to abort all its
+    /// requests, or per request to cancel a single download.
+    ///
+    /// ```
+    /// use ureq::AbortHandle;
+    ///
+    /// let handle = AbortHandle::new();
+    ///
+    /// // Typically called from another thread.
+    /// handle.abort();
+    ///
+    /// let result = ureq::get("http://httpbin.org/bytes/100")
+    ///     .config()
+    ///     .abort_handle(handle.clone())
+    ///     .build()
+    ///     .call();
+    ///
+    /// assert!(matches!(result, Err(ureq::Error::Aborted)));
+    /// ```
+    ///
+    /// Defaults to `None`.
+    pub fn abort_handle(mut self, v: AbortHandle) -> Self {
+        self.config().abort_handle = Some(v);
+        self
+    }
+
     /// Add middleware to use for each request in this agent.
     ///
     /// Defaults to no middleware.
@@ -603,6 +1335,10 @@ impl<Scope: private::ConfigScope> ConfigBuilder<Scope> {
     /// This is end-to-end, from DNS lookup to finishing reading the response body.
     /// Thus it covers all other timeouts.
     ///
+    /// When following redirects, the timeout is not reset per hop, it bounds the
+    /// total time of all calls. Use [`ConfigBuilder::timeout_per_call()`] for a
+    /// timeout that restarts with each redirect.
+    ///
     /// Defaults to `None`.
     pub fn timeout_global(mut self, v: Option<Duration>) -> Self {
         self.config().timeouts.global = v;
@@ -634,7 +1370,8 @@ impl<Scope: private::ConfigScope> ConfigBuilder<Scope> {
 
     /// Max duration for establishing the connection
     ///
-    /// For a TLS connection this includes opening the socket and doing the TLS handshake.
+    /// This is opening the socket, and for proxies the proxy handshake. The TLS
+    /// handshake has its own timeout, see [`ConfigBuilder::timeout_tls_handshake()`].
     ///
     /// Defaults to `None`.
     pub fn timeout_connect(mut self, v: Option<Duration>) -> Self {
@@ -642,6 +1379,17 @@ impl<Scope: private::ConfigScope> ConfigBuilder<Scope> {
         self
     }
 
+    /// Max duration for the TLS handshake of a new connection
+    ///
+    /// Starts once the connection is established. Connections reused from the pool
+    /// don't do a handshake.
+    ///
+    /// Defaults to `None`.
+    pub fn timeout_tls_handshake(mut self, v: Option<Duration>) -> Self {
+        self.config().timeouts.tls_handshake = v;
+        self
+    }
+
     /// Max duration for sending the request, but not the request body.
     ///
     /// Defaults to `None`.
@@ -653,7 +1401,11 @@ impl<Scope: private::ConfigScope> ConfigBuilder<Scope> {
     /// Max duration for awaiting a 100-continue response.
     ///
     /// Only used if there is a request body and we sent the `Expect: 100-continue`
-    /// header to indicate we want the server to respond with 100.
+    /// header to indicate we want the server to respond with 100. See
+    /// [`RequestBuilder::expect_continue()`](crate::RequestBuilder::expect_continue).
+    ///
+    /// When this timeout is reached, it is not an error. Instead we stop waiting and
+    /// send the request body anyway, since many servers never respond with 100.
     ///
     /// This defaults to 1 second.
     pub fn timeout_await_100(mut self, v: Option<Duration>) -> Self {
@@ -684,6 +1436,20 @@ impl<Scope: private::ConfigScope> ConfigBuilder<Scope> {
         self.config().timeouts.recv_body = v;
         self
     }
+
+    /// Max duration to wait for more of the response body.
+    ///
+    /// Unlike [`timeout_recv_body`](Self::timeout_recv_body) and
+    /// [`timeout_global`](Self::timeout_global), which cap the total time, this restarts
+    /// every time some of the body arrives. A long running but steadily flowing
+    /// response (such as server-sent events) keeps going, while a stalled one fails
+    /// with [`Timeout::RecvBody`](crate::Timeout::RecvBody).
+    ///
+    /// Defaults to `None`.
+    pub fn timeout_recv_body_idle(mut self, v: Option<Duration>) -> Self {
+        self.config().timeouts.recv_body_idle = v;
+        self
+    }
 }
 
 /// Possible config values for headers.
@@ -762,7 +1528,7 @@ impl<S: AsSendBody> ConfigBuilder<HttpCrateScope<S>> {
 /// This can be configured both on Agent level as well as per request.
 #[derive(Clone, Copy)]
 pub struct Timeouts {
-    /// Timeout for the entire call
+    /// Timeout for the entire call, including all redirects
     pub global: Option<Duration>,
 
     /// Timeout for call-by-call when following redirects
@@ -774,6 +1540,9 @@ pub struct Timeouts {
     /// Max duration for establishing the connection
     pub connect: Option<Duration>,
 
+    /// Max duration for the TLS handshake of a new connection
+    pub tls_handshake: Option<Duration>,
+
     /// Max duration for sending the request, but not the request body.
     pub send_request: Option<Duration>,
 
@@ -788,6 +1557,9 @@ pub struct Timeouts {
 
     /// Max duration for receving the response body.
     pub recv_body: Option<Duration>,
+
+    /// Max duration to wait for more of the response body.
+    pub recv_body_idle: Option<Duration>,
 }
 
 #[derive(Debug, Clone)]
@@ -796,29 +1568,60 @@ pub(crate) struct RequestLevelConfig(pub Config);
 pub(crate) static DEFAULT_USER_AGENT: &str =
     concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
 
+/// Hard limit of headers in the response parser.
+pub(crate) const MAX_RESPONSE_HEADERS: usize = 128;
+
 impl Default for Config {
     fn default() -> Self {
         Self {
             http_status_as_error: true,
+            error_on_server_error_only: false,
             https_only: false,
+            error_context: false,
             ip_family: IpFamily::Any,
             #[cfg(feature = "_tls")]
             tls_config: TlsConfig::default(),
+            #[cfg(feature = "_tls")]
+            panic_on_missing_tls: true,
             proxy: Proxy::try_from_env(),
+            #[cfg(feature = "cookies")]
+            cookie_jar: None,
+            address_sort: None,
+            resolve_overrides: Arc::new(vec![]),
             no_delay: true,
+            #[cfg(feature = "socket2")]
+            tcp_keepalive: None,
+            #[cfg(feature = "socket2")]
+            local_address: None,
+            #[cfg(all(feature = "socket2", target_os = "linux"))]
+            bind_device: None,
             max_redirects: 10,
             max_redirects_will_error: true,
+            detect_redirect_loops: false,
             redirect_auth_headers: RedirectAuthHeaders::Never,
+            on_redirect: None,
+            observer: None,
+            default_headers: HeaderMap::new(),
             user_agent: AutoHeaderValue::default(),
             accept: AutoHeaderValue::default(),
             accept_encoding: AutoHeaderValue::default(),
             timeouts: Timeouts::default(),
             max_response_header_size: 64 * 1024,
+            max_response_headers: MAX_RESPONSE_HEADERS,
+            max_request_header_size: 64 * 1024,
+            max_response_body_size: None,
+            no_content_body_as_error: false,
             input_buffer_size: 128 * 1024,
             output_buffer_size: 128 * 1024,
+            output_chunk_size: None,
+            connection_pooling: true,
             max_idle_connections: 10,
             max_idle_connections_per_host: 3,
+            max_concurrent_connections: 0,
             max_idle_age: Duration::from_secs(15),
+            pool_checkout_probe: ProbeKind::NonBlockingRead,
+            reuse_connection_if: None,
+            abort_handle: None,
             middleware: MiddlewareChain::default(),
             force_send_body: false,
         }
@@ -832,11 +1635,13 @@ impl Default for Timeouts {
             per_call: None,
             resolve: None,
             connect: None,
+            tls_handshake: None,
             send_request: None,
             await_100: Some(Duration::from_secs(1)),
             send_body: None,
             recv_response: None,
             recv_body: None,
+            recv_body_idle: None,
         }
     }
 }
@@ -873,33 +1678,177 @@ impl IpFamily {
     }
 }
 
+/// How to check a pooled connection before using it.
+///
+/// Used with [`ConfigBuilder::pool_checkout_probe()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProbeKind {
+    /// No check. The pooled connection is used as is.
+    ///
+    /// Saves a syscall per request, but a request on a connection closed by the
+    /// server fails.
+    None,
+    /// A non-blocking read on the socket, which detects a connection closed by
+    /// the server, or unexpected data sent by the server.
+    NonBlockingRead,
+    /// As `NonBlockingRead`, but first also check the socket for pending errors,
+    /// such as a failed TCP keep-alive.
+    ///
+    /// Transports that can't do this check fall back to `NonBlockingRead`.
+    TcpKeepAliveCheck,
+}
+
+/// Function to reorder resolved socket addresses.
+///
+/// See [`ConfigBuilder::address_sort()`].
+pub type AddressSort = Arc<dyn Fn(&mut Vec<SocketAddr>) + Send + Sync>;
+
+/// Hook called at each stage of a call.
+///
+/// See [`ConfigBuilder::observer()`].
+pub type Observer = Arc<dyn Fn(LifecycleEvent) + Send + Sync>;
+
+/// Hook deciding what to do with a redirect.
+///
+/// See [`ConfigBuilder::on_redirect()`].
+pub type OnRedirect = Arc<dyn Fn(&Uri, StatusCode, &Uri) -> RedirectAction + Send + Sync>;
+
+/// What to do with a redirect.
+///
+/// See [`ConfigBuilder::on_redirect()`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RedirectAction {
+    /// Follow the redirect.
+    Follow,
+
+    /// Don't follow the redirect, and return the redirect response.
+    Stop,
+
+    /// Follow the redirect, but to this uri instead.
+    FollowTo(Uri),
+}
+
+/// Predicate deciding whether to pool a connection.
+///
+/// See [`ConfigBuilder::reuse_connection_if()`].
+pub type ReuseConnectionIf = Arc<dyn Fn(&ConnectionInfo) -> bool + Send + Sync>;
+
+/// Information about a connection about to be returned to the pool.
+///
+/// See [`ConfigBuilder::reuse_connection_if()`].
+#[derive(Debug, Clone)]
+pub struct ConnectionInfo {
+    pub(crate) authority: Authority,
+    pub(crate) age: Duration,
+    pub(crate) requests: usize,
+}
+
+impl ConnectionInfo {
+    /// The authority, i.e. host and port, the connection is for.
+    pub fn authority(&self) -> &Authority {
+        &self.authority
+    }
+
+    /// How long since the connection was opened.
+    pub fn age(&self) -> Duration {
+        self.age
+    }
+
+    /// The number of requests served by the connection, including the one just finished.
+    pub fn requests(&self) -> usize {
+        self.requests
+    }
+}
+
+/// Randomly shuffle socket addresses.
+///
+/// Intended to be used with [`ConfigBuilder::address_sort()`] to spread load
+/// across all addresses a host name resolves to.
+// &mut Vec to match the AddressSort signature.
+#[allow(clippy::ptr_arg)]
+pub fn shuffle(addrs: &mut Vec<SocketAddr>) {
+    // RandomState is seeded differently for each instance, which is
+    // random enough for load distribution without pulling in a rand crate.
+    let state = RandomState::new();
+
+    // Fisher-Yates
+    for i in (1..addrs.len()).rev() {
+        let mut hasher = state.build_hasher();
+        hasher.write_usize(i);
+        let j = (hasher.finish() % (i as u64 + 1)) as usize;
+        addrs.swap(i, j);
+    }
+}
+
 impl fmt::Debug for Config {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let mut dbg = f.debug_struct("Config");
 
         dbg.field("http_status_as_error", &self.http_status_as_error)
+            .field(
+                "error_on_server_error_only",
+                &self.error_on_server_error_only,
+            )
             .field("https_only", &self.https_only)
+            .field("error_context", &self.error_context)
             .field("ip_family", &self.ip_family)
             .field("proxy", &self.proxy)
+            .field("address_sort", &self.address_sort.is_some())
+            .field("resolve_overrides", &self.resolve_overrides)
             .field("no_delay", &self.no_delay)
             .field("max_redirects", &self.max_redirects)
+            .field("detect_redirect_loops", &self.detect_redirect_loops)
             .field("redirect_auth_headers", &self.redirect_auth_headers)
+            .field("on_redirect", &self.on_redirect.is_some())
+            .field("observer", &self.observer.is_some())
+            // Values left out, since they might be secrets.
+            .field(
+                "default_headers",
+                &self.default_headers.keys().collect::<Vec<_>>(),
+            )
             .field("user_agent", &self.user_agent)
             .field("timeouts", &self.timeouts)
             .field("max_response_header_size", &self.max_response_header_size)
+            .field("max_request_header_size", &self.max_request_header_size)
+            .field("max_response_headers", &self.max_response_headers)
+            .field("max_response_body_size", &self.max_response_body_size)
+            .field("no_content_body_as_error", &self.no_content_body_as_error)
             .field("input_buffer_size", &self.input_buffer_size)
             .field("output_buffer_size", &self.output_buffer_size)
+            .field("output_chunk_size", &self.output_chunk_size)
+            .field("connection_pooling", &self.connection_pooling)
             .field("max_idle_connections", &self.max_idle_connections)
             .field(
                 "max_idle_connections_per_host",
                 &self.max_idle_connections_per_host,
             )
+            .field(
+                "max_concurrent_connections",
+                &self.max_concurrent_connections,
+            )
             .field("max_idle_age", &self.max_idle_age)
+            .field("pool_checkout_probe", &self.pool_checkout_probe)
+            .field("reuse_connection_if", &self.reuse_connection_if.is_some())
+            .field("abort_handle", &self.abort_handle)
             .field("middleware", &self.middleware);
 
+        #[cfg(feature = "socket2")]
+        {
+            dbg.field("tcp_keepalive", &self.tcp_keepalive);
+            dbg.field("local_address", &self.local_address);
+            #[cfg(target_os = "linux")]
+            dbg.field("bind_device", &self.bind_device);
+        }
+
+        #[cfg(feature = "cookies")]
+        {
+            dbg.field("cookie_jar", &self.cookie_jar.is_some());
+        }
+
         #[cfg(feature = "_tls")]
         {
             dbg.field("tls_config", &self.tls_config);
+            dbg.field("panic_on_missing_tls", &self.panic_on_missing_tls);
         }
 
         dbg.finish()
@@ -913,11 +1862,13 @@ impl fmt::Debug for Timeouts {
             .field("per_call", &self.per_call)
             .field("resolve", &self.resolve)
             .field("connect", &self.connect)
+            .field("tls_handshake", &self.tls_handshake)
             .field("send_request", &self.send_request)
             .field("await_100", &self.await_100)
             .field("send_body", &self.send_body)
             .field("recv_response", &self.recv_response)
             .field("recv_body", &self.recv_body)
+            .field("recv_body_idle", &self.recv_body_idle)
             .finish()
     }
 }
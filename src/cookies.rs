@@ -12,8 +12,13 @@ use crate::Error;
 #[cfg(feature = "json")]
 use std::io;
 
+/// A cookie jar that can be used instead of the agent's own.
+///
+/// Set using [`ConfigBuilder::cookie_jar()`](crate::config::ConfigBuilder::cookie_jar)
+/// to isolate the cookies of different sessions, while still sharing the agent and its
+/// connection pool.
 #[derive(Debug)]
-pub(crate) struct SharedCookieJar {
+pub struct SharedCookieJar {
     inner: Mutex<CookieStore>,
 }
 
@@ -130,6 +135,38 @@ impl<'a> CookieJar<'a> {
         self.0.clear()
     }
 
+    /// Removes all cookies for `domain`, including those of its subdomains.
+    ///
+    /// Removing for `example.com` also removes the cookies of `www.example.com`,
+    /// but not the other way around.
+    ///
+    /// ```
+    /// let agent = ureq::agent();
+    ///
+    /// // Log out by forgetting the session cookies.
+    /// agent.cookie_jar_lock().remove_domain("example.com");
+    /// ```
+    pub fn remove_domain(&mut self, domain: &str) {
+        let domain = domain.trim_start_matches('.');
+
+        let to_remove: Vec<_> = self
+            .0
+            .iter_any()
+            .filter_map(|c| {
+                let d = c.domain.as_cow()?;
+                let is_match = d.eq_ignore_ascii_case(domain)
+                    || d.len() > domain.len()
+                        && d.ends_with(domain)
+                        && d[..d.len() - domain.len()].ends_with('.');
+                is_match.then(|| (d.into_owned(), c.path.to_string(), c.name().to_string()))
+            })
+            .collect();
+
+        for (domain, path, name) in to_remove {
+            self.0.remove(&domain, &path, &name);
+        }
+    }
+
     /// An iterator visiting all the __unexpired__ cookies in the jar
     pub fn iter(&self) -> impl Iterator<Item = Cookie<'_>> {
         self.0
@@ -139,17 +176,36 @@ impl<'a> CookieJar<'a> {
 
     /// Serialize any __unexpired__ and __persistent__ cookies in the jar to JSON format and
     /// write them to `writer`
+    ///
+    /// The name, value, domain, path, expiry and secure/httponly flags are kept. Session
+    /// cookies (without expiry) are not persistent and are not saved.
+    ///
+    /// ```no_run
+    /// # #[cfg(feature = "json")]
+    /// # {
+    /// let agent = ureq::agent();
+    /// // ... use the agent to log in ...
+    ///
+    /// let file = std::fs::File::create("cookies.json")?;
+    /// agent.cookie_jar_lock().save_json(file)?;
+    ///
+    /// // ... in the next run of the program ...
+    /// let file = std::fs::File::open("cookies.json")?;
+    /// agent.cookie_jar_lock().load_json(file)?;
+    /// # }
+    /// # Ok::<_, ureq::Error>(())
+    /// ```
     #[cfg(feature = "json")]
-    pub fn save_json<W: io::Write>(&self, writer: &mut W) -> Result<(), Error> {
-        Ok(cookie_store::serde::json::save(&self.0, writer)?)
+    pub fn save_json<W: io::Write>(&self, mut writer: W) -> Result<(), Error> {
+        Ok(cookie_store::serde::json::save(&self.0, &mut writer)?)
     }
 
     /// Load JSON-formatted cookies from `reader`, skipping any __expired__ cookies
     ///
     /// Replaces all the contents of the current cookie jar.
     #[cfg(feature = "json")]
-    pub fn load_json<R: io::BufRead>(&mut self, reader: R) -> Result<(), Error> {
-        let store = cookie_store::serde::json::load(reader)?;
+    pub fn load_json<R: io::Read>(&mut self, reader: R) -> Result<(), Error> {
+        let store = cookie_store::serde::json::load(io::BufReader::new(reader))?;
         *self.0 = store;
         Ok(())
     }
@@ -169,13 +225,18 @@ impl<'a> CookieJar<'a> {
 }
 
 impl SharedCookieJar {
-    pub(crate) fn new() -> Self {
+    /// Creates an empty cookie jar.
+    pub fn new() -> Self {
         SharedCookieJar {
             inner: Mutex::new(CookieStore::new()),
         }
     }
 
-    pub(crate) fn lock(&self) -> CookieJar<'_> {
+    /// Lock the jar to access the cookies.
+    ///
+    /// The lock must be released before making a request using this jar, or end up
+    /// with a deadlock.
+    pub fn lock(&self) -> CookieJar<'_> {
         let lock = self.inner.lock().unwrap();
         CookieJar(lock)
     }
@@ -210,6 +271,12 @@ impl SharedCookieJar {
     }
 }
 
+impl Default for SharedCookieJar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 fn is_cookie_rfc_compliant(cookie: &cookie_store::Cookie) -> bool {
     // https://tools.ietf.org/html/rfc6265#page-9
     // set-cookie-header = "Set-Cookie:" SP set-cookie-string
@@ -319,4 +386,73 @@ mod test {
         let cookie = Cookie::parse("name=value", &uri()).unwrap();
         assert!(is_cookie_rfc_compliant(cookie.as_cookie_store()));
     }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn save_load_json() {
+        let jar = SharedCookieJar::new();
+        let mut lock = jar.lock();
+
+        let persistent = Cookie::parse("a=1; Max-Age=3600; Secure; HttpOnly", &uri()).unwrap();
+        let session = Cookie::parse("b=2", &uri()).unwrap();
+        lock.insert(persistent, &uri()).unwrap();
+        lock.insert(session, &uri()).unwrap();
+
+        let mut json = vec![];
+        lock.save_json(&mut json).unwrap();
+
+        let jar2 = SharedCookieJar::new();
+        let mut lock2 = jar2.lock();
+        lock2.load_json(&json[..]).unwrap();
+
+        let names: Vec<_> = lock2.iter().map(|c| c.name().to_string()).collect();
+        assert_eq!(names, ["a"]);
+
+        let cookie = lock2.get("example.test", "/", "a").unwrap();
+        assert_eq!(cookie.value(), "1");
+        assert_eq!(cookie.as_cookie_store().secure(), Some(true));
+        assert_eq!(cookie.as_cookie_store().http_only(), Some(true));
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn load_json_skips_expired() {
+        let jar = SharedCookieJar::new();
+        let mut lock = jar.lock();
+
+        let json = concat!(
+            r#"[{"raw_cookie":"a=1; Expires=Thu, 01 Jan 1970 00:00:01 GMT","#,
+            r#""path":["/",false],"domain":{"HostOnly":"example.test"},"#,
+            r#""expires":{"AtUtc":"1970-01-01T00:00:01Z"}}]"#
+        );
+        lock.load_json(json.as_bytes()).unwrap();
+
+        assert_eq!(lock.iter().count(), 0);
+    }
+
+    #[test]
+    fn remove_domain() {
+        let jar = SharedCookieJar::new();
+        let mut lock = jar.lock();
+
+        for uri in [
+            "https://example.test",
+            "https://www.example.test",
+            "https://other.test",
+        ] {
+            let uri = Uri::try_from(uri).unwrap();
+            let cookie = Cookie::parse("a=1", &uri).unwrap();
+            lock.insert(cookie, &uri).unwrap();
+        }
+        assert_eq!(lock.iter().count(), 3);
+
+        lock.remove_domain("www.example.test");
+        assert!(lock.get("www.example.test", "/", "a").is_none());
+        assert!(lock.get("example.test", "/", "a").is_some());
+
+        lock.remove_domain("example.test");
+        let left: Vec<_> = lock.iter().map(|c| c.to_string()).collect();
+        assert_eq!(left, ["a=1"]);
+        assert!(lock.get("other.test", "/", "a").is_some());
+    }
 }
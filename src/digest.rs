@@ -0,0 +1,312 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use base64::prelude::BASE64_STANDARD;
+use base64::Engine;
+use http::{HeaderName, HeaderValue};
+use md5::Md5;
+use sha2::{Digest, Sha256};
+
+use crate::http;
+
+/// Digest of the request body to send as a header.
+///
+/// See [`RequestBuilder::with_body_digest()`](crate::RequestBuilder::with_body_digest).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum BodyDigest {
+    /// `Content-MD5: <base64 md5>`
+    Md5,
+    /// `Digest: sha-256=<base64 sha256>`
+    Sha256,
+}
+
+impl BodyDigest {
+    /// The header to set for the body data.
+    pub(crate) fn header(&self, data: &[u8]) -> (HeaderName, HeaderValue) {
+        match self {
+            BodyDigest::Md5 => {
+                let v = BASE64_STANDARD.encode(md5(data));
+                // unwrap is ok because base64 is a valid header value.
+                let value = HeaderValue::from_str(&v).unwrap();
+                (HeaderName::from_static("content-md5"), value)
+            }
+            BodyDigest::Sha256 => {
+                let v = format!("sha-256={}", BASE64_STANDARD.encode(sha256(data)));
+                let value = HeaderValue::from_str(&v).unwrap();
+                (HeaderName::from_static("digest"), value)
+            }
+        }
+    }
+}
+
+/// A `WWW-Authenticate: Digest` challenge, see RFC 7616.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct DigestChallenge {
+    realm: String,
+    nonce: String,
+    opaque: Option<String>,
+    algorithm: &'static str,
+    sha256: bool,
+    sess: bool,
+    qop: Option<&'static str>,
+}
+
+impl DigestChallenge {
+    /// The first digest challenge in the `WWW-Authenticate` headers that we can answer.
+    pub(crate) fn from_headers(headers: &http::HeaderMap) -> Option<Self> {
+        headers
+            .get_all(http::header::WWW_AUTHENTICATE)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .find_map(Self::parse)
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        let value = value.trim_start();
+        let scheme = value.get(..7)?;
+        if !scheme.eq_ignore_ascii_case("digest ") {
+            return None;
+        }
+
+        let params = parse_params(&value[7..]);
+        let param = |name: &str| {
+            params
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case(name))
+                .map(|(_, v)| v.as_str())
+        };
+
+        let (algorithm, sha256, sess) = match param("algorithm").unwrap_or("MD5") {
+            a if a.eq_ignore_ascii_case("MD5") => ("MD5", false, false),
+            a if a.eq_ignore_ascii_case("MD5-sess") => ("MD5-sess", false, true),
+            a if a.eq_ignore_ascii_case("SHA-256") => ("SHA-256", true, false),
+            a if a.eq_ignore_ascii_case("SHA-256-sess") => ("SHA-256-sess", true, true),
+            _ => return None,
+        };
+
+        let qop = match param("qop") {
+            Some(v) => {
+                let offered: Vec<_> = v.split(',').map(|q| q.trim()).collect();
+                if offered.contains(&"auth") {
+                    Some("auth")
+                } else if offered.contains(&"auth-int") {
+                    Some("auth-int")
+                } else {
+                    return None;
+                }
+            }
+            None => None,
+        };
+
+        Some(DigestChallenge {
+            realm: param("realm")?.to_string(),
+            nonce: param("nonce")?.to_string(),
+            opaque: param("opaque").map(|v| v.to_string()),
+            algorithm,
+            sha256,
+            sess,
+            qop,
+        })
+    }
+
+    fn hash(&self, data: &str) -> String {
+        if self.sha256 {
+            hex(&sha256(data.as_bytes()))
+        } else {
+            hex(&md5(data.as_bytes()))
+        }
+    }
+
+    /// The `Authorization` header value answering this challenge.
+    pub(crate) fn authorization(
+        &self,
+        username: &str,
+        password: &str,
+        method: &str,
+        uri: &str,
+        body: &[u8],
+        cnonce: &str,
+    ) -> String {
+        // Each challenge is answered once, which makes the nonce count always 1.
+        let nc = "00000001";
+
+        let mut ha1 = self.hash(&format!("{}:{}:{}", username, self.realm, password));
+        if self.sess {
+            ha1 = self.hash(&format!("{}:{}:{}", ha1, self.nonce, cnonce));
+        }
+
+        let ha2 = if self.qop == Some("auth-int") {
+            let body_hash = if self.sha256 {
+                hex(&sha256(body))
+            } else {
+                hex(&md5(body))
+            };
+            self.hash(&format!("{}:{}:{}", method, uri, body_hash))
+        } else {
+            self.hash(&format!("{}:{}", method, uri))
+        };
+
+        let response = match self.qop {
+            Some(qop) => self.hash(&format!(
+                "{}:{}:{}:{}:{}:{}",
+                ha1, self.nonce, nc, cnonce, qop, ha2
+            )),
+            None => self.hash(&format!("{}:{}:{}", ha1, self.nonce, ha2)),
+        };
+
+        let mut value = format!(
+            "Digest username={}, realm={}, nonce={}, uri={}, algorithm={}, response=\"{}\"",
+            quote(username),
+            quote(&self.realm),
+            quote(&self.nonce),
+            quote(uri),
+            self.algorithm,
+            response
+        );
+
+        if let Some(qop) = self.qop {
+            value.push_str(&format!(", qop={}, nc={}, cnonce=\"{}\"", qop, nc, cnonce));
+        }
+
+        if let Some(opaque) = &self.opaque {
+            value.push_str(&format!(", opaque={}", quote(opaque)));
+        }
+
+        value
+    }
+}
+
+/// Client nonce for digest authentication.
+pub(crate) fn cnonce() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+
+    let seed = format!("{}:{}:{}", time, count, std::process::id());
+    hex(&sha256(seed.as_bytes())[..8])
+}
+
+/// Parse the comma separated `key=value` or `key="quoted value"` parameters of a challenge.
+fn parse_params(mut rest: &str) -> Vec<(String, String)> {
+    let mut params = vec![];
+
+    loop {
+        rest = rest.trim_start_matches(|c: char| c == ',' || c.is_whitespace());
+
+        let Some(eq) = rest.find('=') else {
+            break;
+        };
+
+        let key = rest[..eq].trim().to_string();
+        rest = rest[eq + 1..].trim_start();
+
+        let value = if let Some(quoted) = rest.strip_prefix('"') {
+            let mut value = String::new();
+            let mut escaped = false;
+            let mut end = quoted.len();
+
+            for (i, c) in quoted.char_indices() {
+                if escaped {
+                    value.push(c);
+                    escaped = false;
+                } else if c == '\\' {
+                    escaped = true;
+                } else if c == '"' {
+                    end = i + 1;
+                    break;
+                } else {
+                    value.push(c);
+                }
+            }
+
+            rest = &quoted[end..];
+            value
+        } else {
+            let end = rest.find(',').unwrap_or(rest.len());
+            let value = rest[..end].trim().to_string();
+            rest = &rest[end..];
+            value
+        };
+
+        params.push((key, value));
+    }
+
+    params
+}
+
+fn quote(v: &str) -> String {
+    format!("\"{}\"", v.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn hex(v: &[u8]) -> String {
+    v.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn md5(data: &[u8]) -> [u8; 16] {
+    Md5::digest(data).into()
+}
+
+fn sha256(data: &[u8]) -> [u8; 32] {
+    Sha256::digest(data).into()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn digest_auth_rfc2617() {
+        let mut headers = http::HeaderMap::new();
+        headers.append(
+            http::header::WWW_AUTHENTICATE,
+            HeaderValue::from_static("Basic realm=\"other\""),
+        );
+        headers.append(
+            http::header::WWW_AUTHENTICATE,
+            HeaderValue::from_static(
+                "Digest realm=\"testrealm@host.com\", qop=\"auth,auth-int\", \
+                nonce=\"dcd98b7102dd2f0e8b11d0f600bfb0c093\", \
+                opaque=\"5ccc069c403ebaf9f0171e9517f40e41\"",
+            ),
+        );
+
+        let challenge = DigestChallenge::from_headers(&headers).unwrap();
+        let value = challenge.authorization(
+            "Mufasa",
+            "Circle Of Life",
+            "GET",
+            "/dir/index.html",
+            &[],
+            "0a4f113b",
+        );
+
+        assert_eq!(
+            value,
+            "Digest username=\"Mufasa\", realm=\"testrealm@host.com\", \
+            nonce=\"dcd98b7102dd2f0e8b11d0f600bfb0c093\", uri=\"/dir/index.html\", \
+            algorithm=MD5, response=\"6629fae49393a05397450978507c4ef1\", \
+            qop=auth, nc=00000001, cnonce=\"0a4f113b\", \
+            opaque=\"5ccc069c403ebaf9f0171e9517f40e41\""
+        );
+    }
+
+    #[test]
+    fn digest_challenge_unsupported() {
+        let parse = DigestChallenge::parse;
+        assert!(parse("Basic realm=\"x\"").is_none());
+        assert!(parse("Digest realm=\"x\", nonce=\"y\", algorithm=SHA-512").is_none());
+        assert!(parse("Digest realm=\"x\", nonce=\"y\", qop=\"other\"").is_none());
+        assert!(parse("Digest realm=\"x\"").is_none());
+
+        let c = parse("digest REALM=\"a \\\"b\\\"\", nonce=n, algorithm=sha-256-sess").unwrap();
+        assert_eq!(c.realm, "a \"b\"");
+        assert_eq!(c.nonce, "n");
+        assert_eq!(c.algorithm, "SHA-256-sess");
+        assert_eq!(c.qop, None);
+    }
+}
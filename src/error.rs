@@ -1,6 +1,7 @@
 use std::{fmt, io};
 
 use crate::http;
+use crate::util::DebugUri;
 use crate::Timeout;
 
 /// Errors from ureq.
@@ -64,10 +65,26 @@ pub enum Error {
     /// The number of redirects is limited to 10 by default.
     TooManyRedirects,
 
+    /// A redirect went to an already visited uri.
+    ///
+    /// Only raised when
+    /// [`detect_redirect_loops()`](crate::config::ConfigBuilder::detect_redirect_loops)
+    /// is true.
+    RedirectLoop(http::Uri),
+
     /// Some error with TLS.
     #[cfg(feature = "_tls")]
     Tls(&'static str),
 
+    /// The uri scheme is https, but the configured TLS provider is not enabled
+    /// by feature flags.
+    ///
+    /// Only returned when
+    /// [`panic_on_missing_tls()`](crate::config::ConfigBuilder::panic_on_missing_tls)
+    /// is false. The default is to panic.
+    #[cfg(feature = "_tls")]
+    NoTlsProvider(crate::tls::TlsProvider),
+
     /// Error in reading PEM certificates/private keys.
     ///
     /// *Note:* The wrapped error struct is not considered part of ureq API.
@@ -124,6 +141,18 @@ pub enum Error {
     #[cfg(feature = "charset")]
     UnknownCharset(String),
 
+    /// A character in the request body can't be represented in the charset.
+    ///
+    /// See [`SendBody::encode_charset()`](crate::SendBody::encode_charset).
+    #[cfg(feature = "charset")]
+    UnmappableChar(char, &'static str),
+
+    /// The `content-type` charset differs from the charset the request body is encoded to.
+    ///
+    /// See [`SendBody::encode_charset()`](crate::SendBody::encode_charset).
+    #[cfg(feature = "charset")]
+    CharsetMismatch(String, &'static str),
+
     /// The setting [`https_only`](crate::config::ConfigBuilder::https_only) is true and
     /// the URI is not https.
     RequireHttpsOnly(String),
@@ -131,25 +160,116 @@ pub enum Error {
     /// The response header, from status up until body, is too big.
     LargeResponseHeader(usize, usize),
 
+    /// The request header, from request line up until body, is bigger than
+    /// [`max_request_header_size()`](crate::config::ConfigBuilder::max_request_header_size).
+    LargeRequestHeader(usize, usize),
+
+    /// The response has more headers than
+    /// [`max_response_headers()`](crate::config::ConfigBuilder::max_response_headers).
+    TooManyHeaders(usize),
+
+    /// A `204` or `304` response had a body.
+    ///
+    /// Only raised when [`no_content_body_as_error()`](crate::config::ConfigBuilder::no_content_body_as_error)
+    /// is true.
+    UnexpectedResponseBody(u16),
+
     /// Body decompression failed (gzip or brotli).
     #[cfg(any(feature = "gzip", feature = "brotli"))]
     Decompress(&'static str, io::Error),
 
+    /// The decompressed response body is larger than the limit.
+    ///
+    /// See [`BodyWithConfig::decompressed_limit()`](crate::BodyWithConfig::decompressed_limit).
+    #[cfg(any(feature = "gzip", feature = "brotli"))]
+    DecompressionLimit(u64),
+
     /// Serde JSON error.
     #[cfg(feature = "json")]
     Json(serde_json::Error),
 
+    /// A `multipart/*` response body could not be read.
+    ///
+    /// See [`Body::into_multipart()`](crate::Body::into_multipart).
+    Multipart(&'static str),
+
     /// Attempt to connect to a CONNECT proxy failed.
     ConnectProxyFailed(String),
 
+    /// The request was aborted using an [`AbortHandle`](crate::AbortHandle).
+    Aborted,
+
     /// hoot made no progress and there is no more input to read.
     ///
     /// We should never see this value.
     #[doc(hidden)]
     BodyStalled,
+
+    /// An error together with the method and uri of the request it is for.
+    ///
+    /// Only returned when [`error_context()`](crate::config::ConfigBuilder::error_context)
+    /// is enabled. See [`Error::request_context()`] and [`Error::without_context()`].
+    WithContext(Box<(http::Method, http::Uri, Error)>),
+
+    /// Any other error, such as from a custom [`Connector`](crate::unversioned::transport::Connector),
+    /// [`Resolver`](crate::unversioned::resolver::Resolver) or
+    /// [`Middleware`](crate::middleware::Middleware).
+    ///
+    /// The wrapped error is the [`source()`](std::error::Error::source) of this error,
+    /// which means it can be recovered by downcasting. It is not repeated when
+    /// displaying this error, which only says "other error".
+    ///
+    /// ```
+    /// use std::error::Error as _;
+    /// use std::fmt;
+    ///
+    /// #[derive(Debug)]
+    /// struct MyError;
+    ///
+    /// impl fmt::Display for MyError {
+    ///     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    ///         write!(f, "my error")
+    ///     }
+    /// }
+    ///
+    /// impl std::error::Error for MyError {}
+    ///
+    /// let err = ureq::Error::Other(Box::new(MyError));
+    /// assert_eq!(err.to_string(), "other error");
+    ///
+    /// let source = err.source().and_then(|e| e.downcast_ref::<MyError>());
+    /// assert!(source.is_some());
+    /// ```
+    Other(Box<dyn std::error::Error + Send + Sync>),
 }
 
-impl std::error::Error for Error {}
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Http(v) => Some(v),
+            Error::Protocol(v) => Some(v),
+            Error::Io(v) => Some(v),
+            #[cfg(feature = "rustls")]
+            Error::Rustls(v) => Some(v),
+            #[cfg(feature = "native-tls")]
+            Error::NativeTls(v) => Some(v),
+            #[cfg(feature = "native-tls")]
+            Error::Der(v) => Some(v),
+            #[cfg(feature = "cookies")]
+            Error::Cookie(v) => Some(v),
+            #[cfg(feature = "cookies")]
+            Error::CookieJar(v) => Some(&**v),
+            #[cfg(any(feature = "gzip", feature = "brotli"))]
+            Error::Decompress(_, v) => Some(v),
+            #[cfg(feature = "json")]
+            Error::Json(v) => Some(v),
+            Error::Other(v) => Some(&**v),
+            // The Display of WithContext includes the wrapped error.
+            Error::WithContext(v) => v.2.source(),
+            _ => None,
+        }
+    }
+}
 
 impl Error {
     /// Convert the error into a [`std::io::Error`].
@@ -164,6 +284,67 @@ impl Error {
         }
     }
 
+    /// The method and uri of the request, if the error is [`Error::WithContext`].
+    ///
+    /// See [`ConfigBuilder::error_context()`](crate::config::ConfigBuilder::error_context).
+    pub fn request_context(&self) -> Option<(&http::Method, &http::Uri)> {
+        match self {
+            Error::WithContext(v) => Some((&v.0, &v.1)),
+            _ => None,
+        }
+    }
+
+    /// The error without the request context of [`Error::WithContext`].
+    ///
+    /// ```
+    /// use ureq::Error;
+    ///
+    /// let err = Error::WithContext(Box::new((
+    ///     "GET".parse().unwrap(),
+    ///     "https://example.com/".parse().unwrap(),
+    ///     Error::HostNotFound,
+    /// )));
+    ///
+    /// assert!(matches!(err.without_context(), Error::HostNotFound));
+    /// ```
+    pub fn without_context(&self) -> &Error {
+        match self {
+            Error::WithContext(v) => v.2.without_context(),
+            _ => self,
+        }
+    }
+
+    /// Whether the error is [`Error::Timeout`].
+    pub fn is_timeout(&self) -> bool {
+        matches!(self.without_context(), Error::Timeout(_))
+    }
+
+    /// Whether the error is [`Error::Io`].
+    pub fn is_io(&self) -> bool {
+        matches!(self.without_context(), Error::Io(_))
+    }
+
+    /// The status code, if the error is [`Error::StatusCode`].
+    pub fn is_status(&self) -> Option<u16> {
+        match self.without_context() {
+            Error::StatusCode(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    /// Whether the error is [`Error::ConnectionFailed`].
+    pub fn is_connection_failed(&self) -> bool {
+        matches!(self.without_context(), Error::ConnectionFailed)
+    }
+
+    /// The kind of the wrapped io error, if the error is [`Error::Io`].
+    pub fn io_kind(&self) -> Option<io::ErrorKind> {
+        match self.without_context() {
+            Error::Io(e) => Some(e.kind()),
+            _ => None,
+        }
+    }
+
     pub(crate) fn disconnected() -> Error {
         io::Error::new(io::ErrorKind::UnexpectedEof, "Peer disconnected").into()
     }
@@ -201,9 +382,17 @@ impl fmt::Display for Error {
                 write!(f, "the response body is larger than request limit: {}", v)
             }
             Error::TooManyRedirects => write!(f, "too many redirects"),
+            Error::RedirectLoop(v) => write!(f, "redirect loop: {}", v),
             #[cfg(feature = "_tls")]
             Error::Tls(v) => write!(f, "{}", v),
             #[cfg(feature = "_tls")]
+            Error::NoTlsProvider(v) => write!(
+                f,
+                "uri scheme is https, provider is {:?} but feature is not enabled: {}",
+                v,
+                v.feature_name()
+            ),
+            #[cfg(feature = "_tls")]
             Error::Pem(v) => write!(f, "PEM: {:?}", v),
             #[cfg(feature = "rustls")]
             Error::Rustls(v) => write!(f, "rustls: {}", v),
@@ -219,16 +408,41 @@ impl fmt::Display for Error {
             Error::CookieJar(v) => write!(f, "cookie: {}", v),
             #[cfg(feature = "charset")]
             Error::UnknownCharset(v) => write!(f, "unknown character set: {}", v),
+            #[cfg(feature = "charset")]
+            Error::UnmappableChar(c, v) => write!(f, "character {:?} can't be encoded in {}", c, v),
+            #[cfg(feature = "charset")]
+            Error::CharsetMismatch(x, y) => {
+                write!(
+                    f,
+                    "content-type charset {} differs from body charset {}",
+                    x, y
+                )
+            }
             Error::RequireHttpsOnly(v) => write!(f, "configured for https only: {}", v),
             Error::LargeResponseHeader(x, y) => {
                 write!(f, "response header is too big: {} > {}", x, y)
             }
+            Error::LargeRequestHeader(x, y) => {
+                write!(f, "request header is too big: {} > {}", x, y)
+            }
+            Error::TooManyHeaders(v) => write!(f, "too many response headers, limit: {}", v),
+            Error::UnexpectedResponseBody(v) => {
+                write!(f, "unexpected body in response with status: {}", v)
+            }
             #[cfg(any(feature = "gzip", feature = "brotli"))]
             Error::Decompress(x, y) => write!(f, "{} decompression failed: {}", x, y),
+            #[cfg(any(feature = "gzip", feature = "brotli"))]
+            Error::DecompressionLimit(v) => {
+                write!(f, "the decompressed body is larger than the limit: {}", v)
+            }
             #[cfg(feature = "json")]
             Error::Json(v) => write!(f, "json: {}", v),
+            Error::Multipart(v) => write!(f, "multipart: {}", v),
             Error::ConnectProxyFailed(v) => write!(f, "CONNECT proxy failed: {}", v),
+            Error::Aborted => write!(f, "aborted"),
             Error::BodyStalled => write!(f, "body data reading stalled"),
+            Error::Other(_) => write!(f, "other error"),
+            Error::WithContext(v) => write!(f, "{} {:?}: {}", v.0, DebugUri(&v.1), v.2),
         }
     }
 }
@@ -317,6 +531,66 @@ mod test {
         assert!(matches!(err, Error::StatusCode(500)));
     }
 
+    #[test]
+    fn source_chain() {
+        use std::error::Error as _;
+
+        #[derive(Debug)]
+        struct MyError;
+
+        impl fmt::Display for MyError {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "my error")
+            }
+        }
+
+        impl std::error::Error for MyError {}
+
+        let err = Error::Other(Box::new(MyError));
+        // The source isn't repeated when printing the chain.
+        assert_eq!(err.to_string(), "other error");
+        let source = err.source().and_then(|e| e.downcast_ref::<MyError>());
+        assert!(source.is_some());
+
+        let err = Error::from(io::Error::new(io::ErrorKind::Other, MyError));
+        let io = err.source().and_then(|e| e.downcast_ref::<io::Error>());
+        let inner = io
+            .and_then(|e| e.get_ref())
+            .and_then(|e| e.downcast_ref::<MyError>());
+        assert!(inner.is_some());
+
+        assert!(Error::StatusCode(500).source().is_none());
+    }
+
+    #[test]
+    fn predicates() {
+        let err = Error::StatusCode(503);
+        assert_eq!(err.is_status(), Some(503));
+        assert!(!err.is_io());
+        assert_eq!(err.io_kind(), None);
+
+        let err = Error::from(io::Error::new(io::ErrorKind::ConnectionReset, "reset"));
+        assert!(err.is_io());
+        assert_eq!(err.io_kind(), Some(io::ErrorKind::ConnectionReset));
+        assert_eq!(err.is_status(), None);
+
+        assert!(Error::Timeout(Timeout::Global).is_timeout());
+        assert!(!Error::ConnectionFailed.is_timeout());
+        assert!(Error::ConnectionFailed.is_connection_failed());
+
+        let err = Error::WithContext(Box::new((
+            http::Method::GET,
+            http::Uri::from_static("https://example.test/"),
+            Error::Timeout(Timeout::Global),
+        )));
+        assert!(err.is_timeout());
+        assert!(matches!(err.without_context(), Error::Timeout(_)));
+        assert_eq!(
+            err.to_string(),
+            "GET https://example.test/: timeout: global"
+        );
+    }
+
     #[test]
     fn ensure_error_size() {
         // This is platform dependent, so we can't be too strict or precise.
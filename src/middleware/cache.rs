@@ -0,0 +1,543 @@
+use std::fmt;
+use std::io::{Cursor, Read};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use http::header::{CACHE_CONTROL, CONNECTION, CONTENT_LENGTH, ETAG, IF_MODIFIED_SINCE};
+use http::header::{IF_NONE_MATCH, LAST_MODIFIED, VARY};
+use http::{Extensions, HeaderMap, HeaderName, HeaderValue, Method, StatusCode, Uri, Version};
+use ureq_proto::BodyMode;
+
+use crate::body::ResponseInfo;
+use crate::config::RequestLevelConfig;
+use crate::http;
+use crate::util::HeaderMapExt;
+use crate::{Body, Error, SendBody};
+
+use super::{Middleware, MiddlewareNext};
+
+/// Middleware caching responses in memory.
+///
+/// Caches `200 OK` responses to `GET` and `HEAD` requests, as a private cache following
+/// the response `Cache-Control` header:
+///
+/// * `max-age` is how long a response is served from the cache without asking the server.
+/// * `no-store` means the response is not cached.
+/// * `no-cache` means the response is cached, but always revalidated.
+///
+/// A response that is no longer fresh, but has an `ETag` or `Last-Modified` header, is
+/// revalidated by sending the request with `If-None-Match` or `If-Modified-Since`. When the
+/// server answers `304 Not Modified`, the cached response is returned.
+///
+/// Entries are keyed on method, uri and the request headers named in the `Vary` header
+/// of the response. The cache holds at most `capacity` responses, evicting the least
+/// recently used. Only bodies up to [`CacheMiddleware::max_body_size()`] are cached,
+/// since they are kept in memory.
+///
+/// Requests with `Cache-Control: no-store`, or that already have conditional headers,
+/// bypass the cache. `Cache-Control: no-cache` on the request forces a revalidation.
+///
+/// ```
+/// use ureq::Agent;
+/// use ureq::config::Config;
+/// use ureq::middleware::CacheMiddleware;
+///
+/// let agent: Agent = Config::builder()
+///     .middleware(CacheMiddleware::new(100))
+///     .build()
+///     .into();
+///
+/// // Served from the cache for repeated requests, if the response allows it.
+/// agent.get("http://httpbin.org/get").call()?;
+/// # Ok::<_, ureq::Error>(())
+/// ```
+pub struct CacheMiddleware {
+    capacity: usize,
+    max_body_size: u64,
+    /// Least recently used first.
+    entries: Mutex<Vec<CacheEntry>>,
+}
+
+impl CacheMiddleware {
+    /// Create a cache holding at most `capacity` responses.
+    pub fn new(capacity: usize) -> Self {
+        CacheMiddleware {
+            capacity,
+            max_body_size: 1024 * 1024,
+            entries: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Max size of a response body to cache.
+    ///
+    /// Responses with larger bodies are passed through without caching. The size is
+    /// of the body as sent by the server, i.e. before decompression.
+    ///
+    /// Defaults to 1MB.
+    pub fn max_body_size(mut self, v: u64) -> Self {
+        self.max_body_size = v;
+        self
+    }
+
+    fn store(&self, entry: CacheEntry) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|e| !e.same_key(&entry));
+        while entries.len() >= self.capacity {
+            entries.remove(0);
+        }
+        entries.push(entry);
+    }
+}
+
+impl Middleware for CacheMiddleware {
+    fn handle(
+        &self,
+        mut request: http::Request<SendBody>,
+        next: MiddlewareNext,
+    ) -> Result<http::Response<Body>, Error> {
+        let method = request.method().clone();
+
+        let bypass = (method != Method::GET && method != Method::HEAD)
+            || CacheControl::from_headers(request.headers()).no_store
+            || request.headers().contains_key(IF_NONE_MATCH)
+            || request.headers().contains_key(IF_MODIFIED_SINCE);
+
+        if bypass {
+            return next.handle(request);
+        }
+
+        let max_body_size = match request.extensions().get::<RequestLevelConfig>() {
+            Some(c) => c.0.max_response_body_size(),
+            None => next.agent.config().max_response_body_size(),
+        };
+
+        let force_revalidate = CacheControl::from_headers(request.headers()).no_cache;
+
+        // A stale entry, taken out of the cache while revalidating.
+        let mut stale = None;
+
+        {
+            let mut entries = self.entries.lock().unwrap();
+
+            if let Some(index) = entries.iter().position(|e| e.matches(&request)) {
+                let entry = entries.remove(index);
+
+                if !force_revalidate && entry.is_fresh() {
+                    let response = entry.response(max_body_size);
+                    entries.push(entry);
+                    return Ok(response);
+                }
+
+                if entry.has_validators() {
+                    stale = Some(entry);
+                }
+            }
+        }
+
+        if let Some(entry) = &stale {
+            let headers = request.headers_mut();
+            if let Some(etag) = entry.headers.get(ETAG) {
+                headers.insert(IF_NONE_MATCH, etag.clone());
+            }
+            if let Some(last_modified) = entry.headers.get(LAST_MODIFIED) {
+                headers.insert(IF_MODIFIED_SINCE, last_modified.clone());
+            }
+        }
+
+        let uri = request.uri().clone();
+        let request_headers = request.headers().clone();
+
+        let response = match next.handle(request) {
+            Ok(v) => v,
+            Err(e) => {
+                // Not revalidated, but the entry is as good as before.
+                if let Some(entry) = stale {
+                    self.store(entry);
+                }
+                return Err(e);
+            }
+        };
+
+        if let Some(mut entry) = stale {
+            if response.status() == StatusCode::NOT_MODIFIED {
+                entry.refresh(response.headers());
+                let response = entry.response(max_body_size);
+                self.store(entry);
+                return Ok(response);
+            }
+        }
+
+        let control = CacheControl::from_headers(response.headers());
+
+        let cacheable = response.status() == StatusCode::OK
+            && !control.no_store
+            && (control.max_age.is_some()
+                || response.headers().contains_key(ETAG)
+                || response.headers().contains_key(LAST_MODIFIED));
+
+        if !cacheable {
+            return Ok(response);
+        }
+
+        let Some(vary) = vary_values(response.headers(), &request_headers) else {
+            return Ok(response);
+        };
+
+        let (parts, body) = response.into_parts();
+
+        let mut raw = body.into_raw_reader();
+        let mut data = vec![];
+        (&mut raw)
+            .take(self.max_body_size + 1)
+            .read_to_end(&mut data)?;
+
+        if data.len() as u64 > self.max_body_size {
+            // Too big to cache, continue reading from where we stopped.
+            let body_mode = match parts.headers.content_length() {
+                Some(len) => BodyMode::LengthDelimited(len),
+                None => BodyMode::Chunked,
+            };
+            let info =
+                ResponseInfo::new(&parts.headers, body_mode).with_max_body_size(max_body_size);
+            let body = Body::from_raw(info, Cursor::new(data).chain(raw));
+            return Ok(http::Response::from_parts(parts, body));
+        }
+
+        let entry = CacheEntry {
+            method,
+            uri,
+            vary,
+            status: parts.status,
+            version: parts.version,
+            headers: parts.headers,
+            extensions: parts.extensions,
+            body: data,
+            stored: Instant::now(),
+            max_age: control.max_age(),
+        };
+
+        let response = entry.response(max_body_size);
+        self.store(entry);
+
+        Ok(response)
+    }
+}
+
+struct CacheEntry {
+    method: Method,
+    uri: Uri,
+    /// Request headers named in the `Vary` response header, with their values.
+    vary: Vec<(HeaderName, Option<HeaderValue>)>,
+    status: StatusCode,
+    version: Version,
+    headers: HeaderMap,
+    extensions: Extensions,
+    /// Body as sent by the server.
+    body: Vec<u8>,
+    stored: Instant,
+    max_age: Duration,
+}
+
+impl CacheEntry {
+    fn matches(&self, request: &http::Request<SendBody>) -> bool {
+        self.method == request.method()
+            && self.uri == *request.uri()
+            && self
+                .vary
+                .iter()
+                .all(|(name, value)| request.headers().get(name) == value.as_ref())
+    }
+
+    fn same_key(&self, other: &CacheEntry) -> bool {
+        self.method == other.method && self.uri == other.uri && self.vary == other.vary
+    }
+
+    fn is_fresh(&self) -> bool {
+        self.stored.elapsed() < self.max_age
+    }
+
+    fn has_validators(&self) -> bool {
+        self.headers.contains_key(ETAG) || self.headers.contains_key(LAST_MODIFIED)
+    }
+
+    /// Update the entry from the headers of a `304 Not Modified`.
+    ///
+    /// Headers about the connection the `304` came on are not part of the stored response.
+    fn refresh(&mut self, headers: &HeaderMap) {
+        // Connection can name further headers that only apply to this connection.
+        let connection: Vec<_> = headers
+            .get_all(CONNECTION)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .map(|v| v.trim())
+            .collect();
+
+        for name in headers.keys() {
+            if *name == CONTENT_LENGTH
+                || is_hop_by_hop(name)
+                || connection
+                    .iter()
+                    .any(|c| c.eq_ignore_ascii_case(name.as_str()))
+            {
+                continue;
+            }
+            self.headers.remove(name);
+            for value in headers.get_all(name) {
+                self.headers.append(name.clone(), value.clone());
+            }
+        }
+
+        self.stored = Instant::now();
+        self.max_age = CacheControl::from_headers(&self.headers).max_age();
+    }
+
+    fn response(&self, max_body_size: Option<u64>) -> http::Response<Body> {
+        let body_mode = if self.method == Method::HEAD {
+            BodyMode::NoBody
+        } else {
+            BodyMode::LengthDelimited(self.body.len() as u64)
+        };
+
+        let info = ResponseInfo::new(&self.headers, body_mode).with_max_body_size(max_body_size);
+        let body = Body::from_raw(info, Cursor::new(self.body.clone()));
+
+        let mut response = http::Response::new(body);
+        *response.status_mut() = self.status;
+        *response.version_mut() = self.version;
+        *response.headers_mut() = self.headers.clone();
+        *response.extensions_mut() = self.extensions.clone();
+        response
+    }
+}
+
+/// Headers that apply to a single connection (RFC 9110 section 7.6.1).
+fn is_hop_by_hop(name: &HeaderName) -> bool {
+    matches!(
+        name.as_str(),
+        "connection"
+            | "keep-alive"
+            | "proxy-authenticate"
+            | "proxy-authorization"
+            | "proxy-connection"
+            | "te"
+            | "trailer"
+            | "transfer-encoding"
+            | "upgrade"
+    )
+}
+
+/// The request headers named in the `Vary` response header, or `None` for `Vary: *`.
+fn vary_values(
+    response_headers: &HeaderMap,
+    request_headers: &HeaderMap,
+) -> Option<Vec<(HeaderName, Option<HeaderValue>)>> {
+    let names = response_headers
+        .get_all(VARY)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(|n| n.trim())
+        .filter(|n| !n.is_empty());
+
+    let mut vary = vec![];
+
+    for name in names {
+        if name == "*" {
+            return None;
+        }
+        let name = HeaderName::from_bytes(name.as_bytes()).ok()?;
+        let value = request_headers.get(&name).cloned();
+        vary.push((name, value));
+    }
+
+    Some(vary)
+}
+
+#[derive(Debug, Default)]
+struct CacheControl {
+    no_store: bool,
+    no_cache: bool,
+    max_age: Option<u64>,
+}
+
+impl CacheControl {
+    fn from_headers(headers: &HeaderMap) -> Self {
+        let mut control = CacheControl::default();
+
+        let directives = headers
+            .get_all(CACHE_CONTROL)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','));
+
+        for directive in directives {
+            let (name, arg) = match directive.split_once('=') {
+                Some((name, arg)) => (name.trim(), Some(arg.trim().trim_matches('"'))),
+                None => (directive.trim(), None),
+            };
+
+            if name.eq_ignore_ascii_case("no-store") {
+                control.no_store = true;
+            } else if name.eq_ignore_ascii_case("no-cache") {
+                control.no_cache = true;
+            } else if name.eq_ignore_ascii_case("max-age") {
+                control.max_age = arg.and_then(|a| a.parse().ok());
+            }
+        }
+
+        control
+    }
+
+    /// How long a response is fresh.
+    fn max_age(&self) -> Duration {
+        if self.no_cache {
+            return Duration::ZERO;
+        }
+        Duration::from_secs(self.max_age.unwrap_or(0))
+    }
+}
+
+impl fmt::Debug for CacheMiddleware {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CacheMiddleware")
+            .field("capacity", &self.capacity)
+            .field("max_body_size", &self.max_body_size)
+            .field("len", &self.entries.lock().unwrap().len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn cache_control() {
+        let mut headers = HeaderMap::new();
+        headers.append(
+            CACHE_CONTROL,
+            HeaderValue::from_static("public, max-age=60"),
+        );
+        let c = CacheControl::from_headers(&headers);
+        assert!(!c.no_store);
+        assert_eq!(c.max_age(), Duration::from_secs(60));
+
+        headers.append(CACHE_CONTROL, HeaderValue::from_static("No-Cache"));
+        let c = CacheControl::from_headers(&headers);
+        assert!(c.no_cache);
+        assert_eq!(c.max_age(), Duration::ZERO);
+    }
+
+    #[test]
+    fn vary_star_not_cacheable() {
+        let mut request_headers = HeaderMap::new();
+        request_headers.insert("accept", HeaderValue::from_static("*/*"));
+
+        let mut headers = HeaderMap::new();
+        headers.insert(VARY, HeaderValue::from_static("Accept, Accept-Language"));
+        let vary = vary_values(&headers, &request_headers).unwrap();
+        assert_eq!(vary.len(), 2);
+        assert_eq!(vary[0].1, Some(HeaderValue::from_static("*/*")));
+        assert_eq!(vary[1].1, None);
+
+        headers.insert(VARY, HeaderValue::from_static("*"));
+        assert!(vary_values(&headers, &request_headers).is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "_test")]
+    fn cache_max_age() {
+        use crate::config::Config;
+        use crate::Agent;
+
+        let agent: Agent = Config::builder()
+            .middleware(CacheMiddleware::new(10))
+            .build()
+            .into();
+
+        let get = |no_store: bool| {
+            let mut req = agent.get("https://my.test/cache/max-age");
+            if no_store {
+                req = req.header("cache-control", "no-store");
+            }
+            req.call().unwrap().body_mut().read_to_string().unwrap()
+        };
+
+        let first = get(false);
+        assert_eq!(get(false), first);
+        assert_ne!(get(true), first);
+    }
+
+    #[test]
+    #[cfg(feature = "_test")]
+    fn cache_etag_revalidate() {
+        use crate::config::Config;
+        use crate::Agent;
+
+        let agent: Agent = Config::builder()
+            .middleware(CacheMiddleware::new(10))
+            .build()
+            .into();
+
+        let mut res = agent.get("https://my.test/cache/etag").call().unwrap();
+        assert_eq!(res.status(), 200);
+        assert_eq!(res.body_mut().read_to_string().unwrap(), "hello");
+
+        // Answered with 304, which is turned into the cached response.
+        let mut res = agent.get("https://my.test/cache/etag").call().unwrap();
+        assert_eq!(res.status(), 200);
+        assert_eq!(res.body_mut().read_to_string().unwrap(), "hello");
+        assert!(res.headers().get("keep-alive").is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "_test")]
+    fn cache_revalidate_error_keeps_entry() {
+        use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        use crate::config::Config;
+        use crate::middleware::MiddlewareNext;
+        use crate::Agent;
+
+        let fail = Arc::new(AtomicBool::new(false));
+        let fail2 = fail.clone();
+        let revalidations = Arc::new(AtomicUsize::new(0));
+        let revalidations2 = revalidations.clone();
+
+        // Sits between the cache and the server.
+        let network = move |req: http::Request<SendBody>, next: MiddlewareNext| {
+            if req.headers().contains_key(IF_NONE_MATCH) {
+                revalidations2.fetch_add(1, Ordering::SeqCst);
+                if fail2.swap(false, Ordering::SeqCst) {
+                    return Err(Error::ConnectionFailed);
+                }
+            }
+            next.handle(req)
+        };
+
+        let agent: Agent = Config::builder()
+            .middleware(CacheMiddleware::new(10))
+            .middleware(network)
+            .build()
+            .into();
+
+        let get = || agent.get("https://my.test/cache/etag").call();
+
+        assert_eq!(get().unwrap().body_mut().read_to_string().unwrap(), "hello");
+
+        fail.store(true, Ordering::SeqCst);
+        assert!(matches!(get(), Err(Error::ConnectionFailed)));
+
+        // The entry is still there to revalidate.
+        let mut res = get().unwrap();
+        assert_eq!(res.status(), 200);
+        assert_eq!(res.body_mut().read_to_string().unwrap(), "hello");
+        assert_eq!(revalidations.load(Ordering::SeqCst), 2);
+    }
+}
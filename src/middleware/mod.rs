@@ -0,0 +1,827 @@
+//! Chained interception to modify the request or response.
+
+use std::fmt;
+use std::io::{self, Read};
+use std::sync::Arc;
+use std::time::Instant;
+
+use http::header::{AUTHORIZATION, COOKIE, PROXY_AUTHORIZATION, SET_COOKIE};
+use http::{HeaderMap, HeaderName, HeaderValue};
+use ureq_proto::BodyMode;
+
+use crate::config::{Config, RequestLevelConfig};
+#[cfg(feature = "digest")]
+use crate::digest::{cnonce, DigestChallenge};
+use crate::http;
+#[cfg(feature = "charset")]
+use crate::run::set_content_type_charset;
+use crate::run::{check_status, run};
+use crate::send_body::BodyInner;
+use crate::util::DebugUri;
+use crate::{Agent, Body, Error, SendBody};
+
+mod cache;
+pub use cache::CacheMiddleware;
+
+/// Chained processing of request (and response).
+///
+/// # Middleware as `fn`
+///
+/// The middleware trait is implemented for all functions that have the signature
+///
+/// `Fn(Request, MiddlewareNext) -> Result<Response, Error>`
+///
+/// That means the easiest way to implement middleware is by providing a `fn`, like so
+///
+/// ```
+/// use ureq::{Body, SendBody};
+/// use ureq::middleware::MiddlewareNext;
+/// use ureq::http::{Request, Response};
+///
+/// fn my_middleware(req: Request<SendBody>, next: MiddlewareNext)
+///     -> Result<Response<Body>, ureq::Error> {
+///
+///     // do middleware things to request
+///
+///     // continue the middleware chain
+///     let res = next.handle(req)?;
+///
+///     // do middleware things to response
+///
+///     Ok(res)
+/// }
+/// ```
+///
+/// # Adding headers
+///
+/// A common use case is to add headers to the outgoing request. Here an example of how.
+///
+/// ```no_run
+/// use ureq::{Body, SendBody, Agent, config::Config};
+/// use ureq::middleware::MiddlewareNext;
+/// use ureq::http::{Request, Response, header::HeaderValue};
+///
+/// # #[cfg(feature = "json")]
+/// # {
+/// fn my_middleware(mut req: Request<SendBody>, next: MiddlewareNext)
+///     -> Result<Response<Body>, ureq::Error> {
+///
+///     req.headers_mut().insert("X-My-Header", HeaderValue::from_static("value_42"));
+///
+///     // set my bespoke header and continue the chain
+///     next.handle(req)
+/// }
+///
+/// let mut config = Config::builder()
+///     .middleware(my_middleware)
+///     .build();
+///
+/// let agent: Agent = config.into();
+///
+/// let result: serde_json::Value =
+///     agent.get("http://httpbin.org/headers").call()?.body_mut().read_json()?;
+///
+/// assert_eq!(&result["headers"]["X-My-Header"], "value_42");
+/// # } Ok::<_, ureq::Error>(())
+/// ```
+///
+/// # State
+///
+/// To maintain state between middleware invocations, we need to do something more elaborate than
+/// the simple `fn` and implement the `Middleware` trait directly.
+///
+/// ## Example with mutex lock
+///
+/// In the `examples` directory there is an additional example `count-bytes.rs` which uses
+/// a mutex lock like shown below.
+///
+/// ```
+/// use std::sync::{Arc, Mutex};
+///
+/// use ureq::{Body, SendBody};
+/// use ureq::middleware::{Middleware, MiddlewareNext};
+/// use ureq::http::{Request, Response};
+///
+/// struct MyState {
+///     // whatever is needed
+/// }
+///
+/// struct MyMiddleware(Arc<Mutex<MyState>>);
+///
+/// impl Middleware for MyMiddleware {
+///     fn handle(&self, request: Request<SendBody>, next: MiddlewareNext)
+///         -> Result<Response<Body>, ureq::Error> {
+///
+///         // These extra brackets ensures we release the Mutex lock before continuing the
+///         // chain. There could also be scenarios where we want to maintain the lock through
+///         // the invocation, which would block other requests from proceeding concurrently
+///         // through the middleware.
+///         {
+///             let mut state = self.0.lock().unwrap();
+///             // do stuff with state
+///         }
+///
+///         // continue middleware chain
+///         next.handle(request)
+///     }
+/// }
+/// ```
+///
+/// ## Example with atomic
+///
+/// This example shows how we can increase a counter for each request going
+/// through the agent.
+///
+/// ```
+/// use ureq::{Body, SendBody, Agent, config::Config};
+/// use ureq::middleware::{Middleware, MiddlewareNext};
+/// use ureq::http::{Request, Response};
+/// use std::sync::atomic::{AtomicU64, Ordering};
+/// use std::sync::Arc;
+///
+/// // Middleware that stores a counter state. This example uses an AtomicU64
+/// // since the middleware is potentially shared by multiple threads running
+/// // requests at the same time.
+/// struct MyCounter(Arc<AtomicU64>);
+///
+/// impl Middleware for MyCounter {
+///     fn handle(&self, req: Request<SendBody>, next: MiddlewareNext)
+///         -> Result<Response<Body>, ureq::Error> {
+///
+///         // increase the counter for each invocation
+///         self.0.fetch_add(1, Ordering::SeqCst);
+///
+///         // continue the middleware chain
+///         next.handle(req)
+///     }
+/// }
+///
+/// let shared_counter = Arc::new(AtomicU64::new(0));
+///
+/// let mut config = Config::builder()
+///     .middleware(MyCounter(shared_counter.clone()))
+///     .build();
+///
+/// let agent: Agent = config.into();
+///
+/// agent.get("http://httpbin.org/get").call()?;
+/// agent.get("http://httpbin.org/get").call()?;
+///
+/// // Check we did indeed increase the counter twice.
+/// assert_eq!(shared_counter.load(Ordering::SeqCst), 2);
+///
+/// # Ok::<_, ureq::Error>(())
+/// ```
+pub trait Middleware: Send + Sync + 'static {
+    /// Handle of the middleware logic.
+    fn handle(
+        &self,
+        request: http::Request<SendBody>,
+        next: MiddlewareNext,
+    ) -> Result<http::Response<Body>, Error>;
+}
+
+#[derive(Clone, Default)]
+pub(crate) struct MiddlewareChain {
+    chain: Arc<Vec<Box<dyn Middleware>>>,
+}
+
+impl MiddlewareChain {
+    pub(crate) fn add(&mut self, mw: impl Middleware) {
+        let Some(chain) = Arc::get_mut(&mut self.chain) else {
+            panic!("Can't add to a MiddlewareChain that is already cloned")
+        };
+
+        chain.push(Box::new(mw));
+    }
+}
+
+/// Continuation of a [`Middleware`] chain.
+///
+/// Cloning the continuation lets a middleware send more than one request through
+/// the rest of the chain, for instance to retry a request with a refreshed token,
+/// see [`TokenRefreshMiddleware`]. Each clone runs the request from the next middleware
+/// onwards, the middleware itself is not invoked again.
+#[derive(Clone)]
+pub struct MiddlewareNext<'a> {
+    agent: &'a Agent,
+    index: usize,
+}
+
+impl<'a> MiddlewareNext<'a> {
+    pub(crate) fn new(agent: &'a Agent) -> Self {
+        MiddlewareNext { agent, index: 0 }
+    }
+
+    /// Continue the middleware chain.
+    ///
+    /// The middleware must call this in order to run the request. Not calling
+    /// it is a valid choice for not wanting the request to execute.
+    pub fn handle(
+        mut self,
+        request: http::Request<SendBody>,
+    ) -> Result<http::Response<Body>, Error> {
+        if let Some(mw) = self.agent.config().middleware.chain.get(self.index) {
+            // This middleware exists, run it.
+            self.index += 1;
+            mw.handle(request, self)
+        } else {
+            // When chain is over, call the actual do_run on agent.
+            let (parts, body) = request.into_parts();
+            let request = http::Request::from_parts(parts, ());
+            run(self.agent, request, body)
+        }
+    }
+}
+
+impl<F> Middleware for F
+where
+    F: Fn(http::Request<SendBody>, MiddlewareNext) -> Result<http::Response<Body>, Error>
+        + Send
+        + Sync
+        + 'static,
+{
+    fn handle(
+        &self,
+        request: http::Request<SendBody>,
+        next: MiddlewareNext,
+    ) -> Result<http::Response<Body>, Error> {
+        (self)(request, next)
+    }
+}
+
+/// Middleware logging requests and responses.
+///
+/// Logs the method, uri, headers, response status and elapsed time at `debug` level
+/// using the [`log`](https://crates.io/crates/log) crate. The values of headers in the
+/// redaction list are replaced by `<redacted>`. By default that is `authorization`,
+/// `proxy-authorization`, `cookie` and `set-cookie`.
+///
+/// ```
+/// use ureq::Agent;
+/// use ureq::config::Config;
+/// use ureq::middleware::LoggingMiddleware;
+///
+/// let logging = LoggingMiddleware::new().redact("x-api-key");
+///
+/// let agent: Agent = Config::builder()
+///     .middleware(logging)
+///     .build()
+///     .into();
+///
+/// agent.get("http://httpbin.org/get").call()?;
+/// # Ok::<_, ureq::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct LoggingMiddleware {
+    redact: Vec<HeaderName>,
+}
+
+impl LoggingMiddleware {
+    /// Create a new logging middleware with the default redaction list.
+    pub fn new() -> Self {
+        LoggingMiddleware {
+            redact: vec![AUTHORIZATION, PROXY_AUTHORIZATION, COOKIE, SET_COOKIE],
+        }
+    }
+
+    /// Add a header to the redaction list.
+    ///
+    /// Panics if the name is not a valid header name.
+    pub fn redact(mut self, name: &str) -> Self {
+        let name = HeaderName::from_bytes(name.as_bytes()).expect("valid header name");
+        self.redact.push(name);
+        self
+    }
+}
+
+impl Default for LoggingMiddleware {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Middleware for LoggingMiddleware {
+    fn handle(
+        &self,
+        request: http::Request<SendBody>,
+        next: MiddlewareNext,
+    ) -> Result<http::Response<Body>, Error> {
+        let method = request.method().clone();
+        let uri = request.uri().clone();
+
+        debug!(
+            "{} {:?} {:?}",
+            method,
+            DebugUri(&uri),
+            RedactedHeaders(request.headers(), &self.redact)
+        );
+
+        let start = Instant::now();
+        let result = next.handle(request);
+        let elapsed = start.elapsed();
+
+        match &result {
+            Ok(response) => debug!(
+                "{} {:?} -> {} in {:?} {:?}",
+                method,
+                DebugUri(&uri),
+                response.status(),
+                elapsed,
+                RedactedHeaders(response.headers(), &self.redact)
+            ),
+            Err(e) => debug!(
+                "{} {:?} failed in {:?}: {}",
+                method,
+                DebugUri(&uri),
+                elapsed,
+                e
+            ),
+        }
+
+        result
+    }
+}
+
+/// Middleware for HTTP Digest authentication.
+///
+/// Sends the request as is, and if the server answers `401 Unauthorized` with a
+/// `WWW-Authenticate: Digest` challenge, sends it again with an `Authorization`
+/// header computed from the username and password. Supports the `MD5` and `SHA-256`
+/// algorithms (and their `-sess` variants), with `qop` of `auth` or `auth-int`.
+///
+/// Since the request might be sent twice, the request body is read into memory first.
+///
+/// Requires the **digest** feature.
+///
+/// ```
+/// use ureq::Agent;
+/// use ureq::config::Config;
+/// use ureq::middleware::DigestAuthMiddleware;
+///
+/// let agent: Agent = Config::builder()
+///     .middleware(DigestAuthMiddleware::new("user", "passwd"))
+///     .build()
+///     .into();
+///
+/// agent.get("http://httpbin.org/digest-auth/auth/user/passwd").call()?;
+/// # Ok::<_, ureq::Error>(())
+/// ```
+#[cfg(feature = "digest")]
+#[derive(Clone)]
+pub struct DigestAuthMiddleware {
+    username: String,
+    password: String,
+}
+
+#[cfg(feature = "digest")]
+impl DigestAuthMiddleware {
+    /// Create a new digest auth middleware for the given credentials.
+    pub fn new(username: &str, password: &str) -> Self {
+        DigestAuthMiddleware {
+            username: username.to_string(),
+            password: password.to_string(),
+        }
+    }
+}
+
+#[cfg(feature = "digest")]
+impl Middleware for DigestAuthMiddleware {
+    fn handle(
+        &self,
+        request: http::Request<SendBody>,
+        next: MiddlewareNext,
+    ) -> Result<http::Response<Body>, Error> {
+        let replay = Replayable::new(request, next.agent)?;
+
+        let response = next.clone().handle(replay.first_attempt(next.agent))?;
+
+        let challenge = if response.status() == http::StatusCode::UNAUTHORIZED {
+            DigestChallenge::from_headers(response.headers())
+        } else {
+            None
+        };
+
+        let Some(challenge) = challenge else {
+            return replay.finish(response);
+        };
+
+        let parts = &replay.parts;
+        let uri = parts
+            .uri
+            .path_and_query()
+            .map(|p| p.as_str())
+            .unwrap_or("/");
+
+        let value = challenge.authorization(
+            &self.username,
+            &self.password,
+            parts.method.as_str(),
+            uri,
+            replay.data.as_deref().unwrap_or_default(),
+            &cnonce(),
+        );
+
+        let mut retry = replay.request();
+        retry.headers_mut().insert(
+            AUTHORIZATION,
+            HeaderValue::from_str(&value).map_err(http::Error::from)?,
+        );
+
+        discard(response);
+
+        next.handle(retry)
+    }
+}
+
+/// Middleware for bearer tokens that expire.
+///
+/// Sets `Authorization: Bearer <token>` on each request, with the token from the `token`
+/// closure. If the server answers `401 Unauthorized`, the `refresh` closure is called
+/// to get a new token, and the request is sent again with it.
+///
+/// The request is retried at most once. If the server answers 401 also with the refreshed
+/// token, that response is returned (or [`Error::StatusCode`], depending on
+/// [`ConfigBuilder::http_status_as_error()`][crate::config::ConfigBuilder::http_status_as_error]).
+/// This avoids looping on credentials that can't be refreshed. An error from either
+/// closure fails the request.
+///
+/// Since the request might be sent twice, the request body is read into memory first.
+///
+/// ```
+/// use std::sync::{Arc, Mutex};
+/// use ureq::Agent;
+/// use ureq::config::Config;
+/// use ureq::middleware::TokenRefreshMiddleware;
+///
+/// let token = Arc::new(Mutex::new("initial".to_string()));
+/// let token2 = token.clone();
+///
+/// let middleware = TokenRefreshMiddleware::new(
+///     move || Ok(token.lock().unwrap().clone()),
+///     move || {
+///         // Fetch a new token from the authorization server.
+///         let new_token = "refreshed".to_string();
+///         *token2.lock().unwrap() = new_token.clone();
+///         Ok(new_token)
+///     },
+/// );
+///
+/// let agent: Agent = Config::builder()
+///     .middleware(middleware)
+///     .build()
+///     .into();
+/// ```
+pub struct TokenRefreshMiddleware {
+    token: Box<TokenFn>,
+    refresh: Box<TokenFn>,
+}
+
+type TokenFn = dyn Fn() -> Result<String, Error> + Send + Sync;
+
+impl TokenRefreshMiddleware {
+    /// Create a new middleware from closures for the current and a refreshed token.
+    pub fn new(
+        token: impl Fn() -> Result<String, Error> + Send + Sync + 'static,
+        refresh: impl Fn() -> Result<String, Error> + Send + Sync + 'static,
+    ) -> Self {
+        TokenRefreshMiddleware {
+            token: Box::new(token),
+            refresh: Box::new(refresh),
+        }
+    }
+}
+
+fn set_bearer(request: &mut http::Request<SendBody>, token: &str) -> Result<(), Error> {
+    let value = HeaderValue::from_str(&format!("Bearer {}", token)).map_err(http::Error::from)?;
+    request.headers_mut().insert(AUTHORIZATION, value);
+    Ok(())
+}
+
+impl Middleware for TokenRefreshMiddleware {
+    fn handle(
+        &self,
+        request: http::Request<SendBody>,
+        next: MiddlewareNext,
+    ) -> Result<http::Response<Body>, Error> {
+        let replay = Replayable::new(request, next.agent)?;
+
+        let mut first = replay.first_attempt(next.agent);
+        set_bearer(&mut first, &(self.token)()?)?;
+
+        let response = next.clone().handle(first)?;
+
+        if response.status() != http::StatusCode::UNAUTHORIZED {
+            return replay.finish(response);
+        }
+
+        let mut retry = replay.request();
+        set_bearer(&mut retry, &(self.refresh)()?)?;
+
+        discard(response);
+
+        next.handle(retry)
+    }
+}
+
+impl fmt::Debug for TokenRefreshMiddleware {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TokenRefreshMiddleware")
+            .finish_non_exhaustive()
+    }
+}
+
+/// A request with the body read to memory, to send it more than once.
+struct Replayable {
+    parts: http::request::Parts,
+    data: Option<Vec<u8>>,
+    /// The config of the original request, for the status handling.
+    config: Config,
+}
+
+impl Replayable {
+    fn new(request: http::Request<SendBody>, agent: &Agent) -> Result<Self, Error> {
+        #[cfg_attr(not(feature = "charset"), allow(unused_mut))]
+        let (mut parts, body) = request.into_parts();
+
+        // Reading the body to memory drops the charset, set it on the header up front.
+        #[cfg(feature = "charset")]
+        if let Some(charset) = body.charset() {
+            set_content_type_charset(&mut parts.headers, charset)?;
+        }
+
+        let data = if body.body_mode() == BodyMode::NoBody {
+            None
+        } else {
+            let mut data = vec![];
+            body.into_reader().read_to_end(&mut data)?;
+            Some(data)
+        };
+
+        let config = match parts.extensions.get::<RequestLevelConfig>() {
+            Some(c) => c.0.clone(),
+            None => agent.config().clone(),
+        };
+
+        Ok(Replayable {
+            parts,
+            data,
+            config,
+        })
+    }
+
+    /// A copy of the original request.
+    fn request(&self) -> http::Request<SendBody<'_>> {
+        let body = match &self.data {
+            Some(data) => BodyInner::ByteSlice(data),
+            None => BodyInner::None,
+        };
+
+        let parts = &self.parts;
+        let mut request = http::Request::new(SendBody::from(body));
+        *request.method_mut() = parts.method.clone();
+        *request.uri_mut() = parts.uri.clone();
+        *request.version_mut() = parts.version;
+        *request.headers_mut() = parts.headers.clone();
+        *request.extensions_mut() = parts.extensions.clone();
+        request
+    }
+
+    /// A copy of the original request that doesn't turn a 401 into an error.
+    fn first_attempt(&self, agent: &Agent) -> http::Request<SendBody<'_>> {
+        agent
+            .configure_request(self.request())
+            .http_status_as_error(false)
+            .build()
+    }
+
+    /// Apply the original status handling to a response of the first attempt.
+    fn finish(&self, response: http::Response<Body>) -> Result<http::Response<Body>, Error> {
+        check_status(&self.config, response.status())?;
+        Ok(response)
+    }
+}
+
+/// Most of a response body we read just to reuse the connection.
+const MAX_DISCARD: u64 = 64 * 1024;
+
+/// Read the rest of a response that is replaced by a retry, which returns its
+/// connection to the pool. Larger bodies are not worth it, the connection is
+/// closed instead.
+fn discard(response: http::Response<Body>) {
+    let reader = response.into_body().into_raw_reader();
+    let _ = io::copy(&mut reader.take(MAX_DISCARD), &mut io::sink());
+}
+
+#[cfg(feature = "digest")]
+impl fmt::Debug for DigestAuthMiddleware {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DigestAuthMiddleware")
+            .field("username", &self.username)
+            .finish_non_exhaustive()
+    }
+}
+
+struct RedactedHeaders<'a>(&'a HeaderMap, &'a [HeaderName]);
+
+impl<'a> fmt::Debug for RedactedHeaders<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_map();
+
+        for (name, value) in self.0 {
+            if self.1.contains(name) {
+                debug.entry(name, &"<redacted>");
+            } else {
+                debug.entry(name, value);
+            }
+        }
+
+        debug.finish()
+    }
+}
+
+impl fmt::Debug for MiddlewareChain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MiddlewareChain")
+            .field("len", &self.chain.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn redacted_headers() {
+        let mw = LoggingMiddleware::new().redact("x-api-key");
+
+        let mut headers = HeaderMap::new();
+        headers.insert("authorization", "Bearer secret".parse().unwrap());
+        headers.insert("x-api-key", "secret".parse().unwrap());
+        headers.insert("accept", "*/*".parse().unwrap());
+
+        let s = format!("{:?}", RedactedHeaders(&headers, &mw.redact));
+        assert_eq!(
+            s,
+            r#"{"authorization": "<redacted>", "x-api-key": "<redacted>", "accept": "*/*"}"#
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "digest", feature = "_test"))]
+    fn digest_auth() {
+        let agent: Agent = Config::builder()
+            .middleware(DigestAuthMiddleware::new("user", "passwd"))
+            .build()
+            .into();
+
+        let mut res = agent.get("https://my.test/digest-auth?x=1").call().unwrap();
+        let auth = res.body_mut().read_to_string().unwrap();
+
+        assert!(auth.starts_with(r#"Digest username="user", realm="test", nonce="abc""#));
+        assert!(auth.contains(r#"uri="/digest-auth?x=1""#));
+        assert!(auth.contains("qop=auth, nc=00000001"));
+    }
+
+    #[test]
+    #[cfg(all(feature = "digest", feature = "_test"))]
+    fn digest_auth_status_as_error() {
+        let agent: Agent = Config::builder()
+            .middleware(DigestAuthMiddleware::new("user", "passwd"))
+            .build()
+            .into();
+
+        let err = agent.get("https://my.test/status/401").call().unwrap_err();
+        assert!(matches!(err, Error::StatusCode(401)));
+
+        let res = agent
+            .get("https://my.test/status/401")
+            .config()
+            .http_status_as_error(false)
+            .build()
+            .call()
+            .unwrap();
+        assert_eq!(res.status(), 401);
+    }
+
+    #[test]
+    #[cfg(all(feature = "digest", feature = "_test"))]
+    fn digest_auth_reuses_connection() {
+        use crate::resolver::DefaultResolver;
+        use crate::transport::{one_shot_server, TcpConnector};
+        use crate::ResponseExt;
+
+        let (addr, server) = one_shot_server(vec![
+            "HTTP/1.1 401 Unauthorized\r\n\
+            WWW-Authenticate: Digest realm=\"test\", nonce=\"abc\", qop=\"auth\"\r\n\
+            Content-Length: 6\r\n\
+            \r\n\
+            denied",
+            "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok",
+        ]);
+
+        let config = Config::builder()
+            .resolve_override("localhost", addr.port(), addr)
+            .middleware(DigestAuthMiddleware::new("user", "passwd"))
+            // The retry must not wait for a second connection the server won't accept.
+            .timeout_global(Some(std::time::Duration::from_secs(5)))
+            .build();
+        let agent = Agent::with_parts(config, TcpConnector::default(), DefaultResolver::default());
+
+        let mut res = agent
+            .get(format!("http://localhost:{}/", addr.port()))
+            .call()
+            .unwrap();
+        assert!(res.connection_reused());
+        assert_eq!(res.body_mut().read_to_string().unwrap(), "ok");
+
+        let requests = server.join().unwrap();
+        assert!(requests[1].contains("\r\nauthorization: Digest username=\"user\""));
+    }
+
+    #[test]
+    #[cfg(feature = "_test")]
+    fn token_refresh() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let refreshes = Arc::new(AtomicUsize::new(0));
+        let refreshes2 = refreshes.clone();
+
+        let agent: Agent = Config::builder()
+            .middleware(TokenRefreshMiddleware::new(
+                || Ok("expired".to_string()),
+                move || {
+                    refreshes2.fetch_add(1, Ordering::SeqCst);
+                    Ok("valid".to_string())
+                },
+            ))
+            .build()
+            .into();
+
+        let mut res = agent
+            .post("https://my.test/bearer-auth")
+            .send("hello")
+            .unwrap();
+        assert_eq!(res.body_mut().read_to_string().unwrap(), "ok");
+        assert_eq!(refreshes.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    #[cfg(feature = "_test")]
+    fn token_refresh_reuses_connection() {
+        use crate::resolver::DefaultResolver;
+        use crate::transport::{one_shot_server, TcpConnector};
+        use crate::ResponseExt;
+
+        let (addr, server) = one_shot_server(vec![
+            "HTTP/1.1 401 Unauthorized\r\nContent-Length: 7\r\n\r\nexpired",
+            "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok",
+        ]);
+
+        let config = Config::builder()
+            .resolve_override("localhost", addr.port(), addr)
+            .middleware(TokenRefreshMiddleware::new(
+                || Ok("expired".to_string()),
+                || Ok("valid".to_string()),
+            ))
+            // The retry must not wait for a second connection the server won't accept.
+            .timeout_global(Some(std::time::Duration::from_secs(5)))
+            .build();
+        let agent = Agent::with_parts(config, TcpConnector::default(), DefaultResolver::default());
+
+        let mut res = agent
+            .get(format!("http://localhost:{}/", addr.port()))
+            .call()
+            .unwrap();
+        assert!(res.connection_reused());
+        assert_eq!(res.body_mut().read_to_string().unwrap(), "ok");
+
+        let requests = server.join().unwrap();
+        assert!(requests[1].contains("\r\nauthorization: Bearer valid\r\n"));
+    }
+
+    #[test]
+    #[cfg(feature = "_test")]
+    fn token_refresh_retries_once() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let refreshes = Arc::new(AtomicUsize::new(0));
+        let refreshes2 = refreshes.clone();
+
+        let agent: Agent = Config::builder()
+            .middleware(TokenRefreshMiddleware::new(
+                || Ok("expired".to_string()),
+                move || {
+                    refreshes2.fetch_add(1, Ordering::SeqCst);
+                    Ok("still-expired".to_string())
+                },
+            ))
+            .build()
+            .into();
+
+        let err = agent.get("https://my.test/bearer-auth").call().unwrap_err();
+        assert!(matches!(err, Error::StatusCode(401)));
+        assert_eq!(refreshes.load(Ordering::SeqCst), 1);
+    }
+}
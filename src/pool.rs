@@ -1,28 +1,35 @@
 use std::collections::VecDeque;
 use std::fmt;
-use std::sync::{Arc, Mutex, Weak};
+use std::sync::{Arc, Condvar, Mutex, Weak};
 
 use http::uri::{Authority, Scheme};
 use http::Uri;
 
-use crate::config::Config;
+use crate::config::{Config, ConnectionInfo, ProbeKind, ReuseConnectionIf};
 use crate::http;
 use crate::proxy::Proxy;
+use crate::response::ByteCounts;
+#[cfg(feature = "_tls")]
+use crate::tls::ClientCert;
 use crate::transport::time::{Duration, Instant};
 use crate::transport::{Buffers, ConnectionDetails, Connector, NextTimeout, Transport};
 use crate::util::DebugAuthority;
-use crate::Error;
+use crate::{AbortHandle, Error};
 
 pub(crate) struct ConnectionPool {
     connector: Box<dyn Connector>,
     pool: Arc<Mutex<Pool>>,
+    limit: Option<Arc<ConnectionLimit>>,
 }
 
 impl ConnectionPool {
     pub fn new(connector: impl Connector, config: &Config) -> Self {
+        let max = config.max_concurrent_connections();
+
         ConnectionPool {
             connector: Box::new(connector),
             pool: Arc::new(Mutex::new(Pool::new(config))),
+            limit: (max > 0).then(|| Arc::new(ConnectionLimit::new(max))),
         }
     }
 
@@ -31,14 +38,27 @@ impl ConnectionPool {
         details: &ConnectionDetails,
         max_idle_age: Duration,
     ) -> Result<Connection, Error> {
-        let key = PoolKey::new(details.uri, details.config.proxy());
+        let key = PoolKey::new(details.uri, details.config);
+
+        let permit = match &self.limit {
+            Some(limit) => Some(limit.acquire(details.timeout)?),
+            None => None,
+        };
 
-        {
+        let pooling = details.config.connection_pooling();
+
+        if pooling {
             let mut pool = self.pool.lock().unwrap();
             pool.purge(details.now);
 
-            if let Some(conn) = pool.get(&key, max_idle_age, details.now) {
+            let probe = details.config.pool_checkout_probe();
+
+            if let Some(mut conn) = pool.get(&key, max_idle_age, probe, details.now) {
                 debug!("Use pooled: {:?}", key);
+                conn.reuse_if = details.config.reuse_connection_if().cloned();
+                conn.abort = details.config.abort_handle().cloned();
+                conn.permit = permit;
+                conn.byte_counts = None;
                 return Ok(conn);
             }
         }
@@ -54,11 +74,42 @@ impl ConnectionPool {
             last_use: details.now,
             pool: Arc::downgrade(&self.pool),
             position_per_host: None,
+            reused: false,
+            created: details.now,
+            requests: 0,
+            reuse_if: details.config.reuse_connection_if().cloned(),
+            abort: details.config.abort_handle().cloned(),
+            no_reuse: !pooling,
+            permit,
+            byte_counts: None,
         };
 
         Ok(conn)
     }
 
+    /// Connect a transport that is never returned to the pool.
+    pub fn connect_unpooled(
+        &self,
+        details: &ConnectionDetails,
+    ) -> Result<Box<dyn Transport>, Error> {
+        let permit = match &self.limit {
+            Some(limit) => Some(limit.acquire(details.timeout)?),
+            None => None,
+        };
+
+        let transport = self
+            .connector
+            .connect(details, None)?
+            .ok_or(Error::ConnectionFailed)?;
+
+        Ok(PermitTransport::wrap(transport, permit))
+    }
+
+    pub fn purge(&self, now: Instant) {
+        let mut pool = self.pool.lock().unwrap();
+        pool.purge(now);
+    }
+
     #[cfg(test)]
     /// Exposed for testing the pool count.
     pub fn pool_count(&self) -> usize {
@@ -92,6 +143,29 @@ pub(crate) struct Connection {
     /// Once we have that enumeration, we can drop elements from the front where there
     /// position_per_host >= idle_per_host.
     position_per_host: Option<usize>,
+
+    /// Whether this connection has been in the pool before.
+    reused: bool,
+
+    created: Instant,
+
+    /// Number of requests finished on this connection.
+    requests: usize,
+
+    /// Predicate from the config of the current request.
+    reuse_if: Option<ReuseConnectionIf>,
+
+    /// Abort handle from the config of the current request.
+    abort: Option<AbortHandle>,
+
+    /// The connection must not go back to the pool, regardless of what the flow says.
+    no_reuse: bool,
+
+    /// Slot in max_concurrent_connections while in use.
+    permit: Option<Permit>,
+
+    /// Bytes sent and received for the current request.
+    byte_counts: Option<ByteCounts>,
 }
 
 impl Connection {
@@ -100,15 +174,38 @@ impl Connection {
     }
 
     pub fn transmit_output(&mut self, amount: usize, timeout: NextTimeout) -> Result<(), Error> {
-        self.transport.transmit_output(amount, timeout)
+        self.check_aborted()?;
+        self.transport.transmit_output(amount, timeout)?;
+        if let Some(c) = &self.byte_counts {
+            c.add_sent(amount);
+        }
+        Ok(())
     }
 
     pub fn await_input(&mut self, timeout: NextTimeout) -> Result<bool, Error> {
-        self.transport.await_input(timeout)
+        self.check_aborted()?;
+        let made_progress = self.transport.await_input(timeout)?;
+        self.check_aborted()?;
+        Ok(made_progress)
+    }
+
+    fn check_aborted(&self) -> Result<(), Error> {
+        if self.abort.as_ref().map(|a| a.is_aborted()).unwrap_or(false) {
+            return Err(Error::Aborted);
+        }
+        Ok(())
     }
 
     pub fn consume_input(&mut self, amount: usize) {
-        self.transport.buffers().input_consume(amount)
+        self.transport.buffers().input_consume(amount);
+        if let Some(c) = &self.byte_counts {
+            c.add_received(amount);
+        }
+    }
+
+    /// Count the bytes sent and received for the current request.
+    pub fn set_byte_counts(&mut self, counts: ByteCounts) {
+        self.byte_counts = Some(counts);
     }
 
     pub fn close(self) {
@@ -116,7 +213,40 @@ impl Connection {
         // Just consume self.
     }
 
+    /// Take the transport out of the connection. It will not be pooled.
+    ///
+    /// The transport keeps the slot in max_concurrent_connections until dropped.
+    pub fn into_transport(mut self) -> Box<dyn Transport> {
+        debug!("Take transport: {:?}", self.key);
+        let permit = self.permit.take();
+        PermitTransport::wrap(self.transport, permit)
+    }
+
+    /// Prevent the connection from being reused after the current request.
+    pub fn set_no_reuse(&mut self) {
+        self.no_reuse = true;
+    }
+
     pub fn reuse(mut self, now: Instant) {
+        self.requests += 1;
+
+        if self.no_reuse {
+            debug!("Not reusing: {:?}", self.key);
+            return;
+        }
+
+        if let Some(reuse_if) = &self.reuse_if {
+            let info = ConnectionInfo {
+                authority: self.key.0 .1.clone(),
+                age: *now.duration_since(self.created),
+                requests: self.requests,
+            };
+            if !reuse_if(&info) {
+                debug!("Not reusing by predicate: {:?}", self.key);
+                return;
+            }
+        }
+
         if !self.transport.is_open() {
             // The purpose of probing is that is_open() for tcp connector attempts
             // to read some more bytes. If that succeeds, the connection is considered
@@ -126,6 +256,7 @@ impl Connection {
             return;
         }
         self.last_use = now;
+        self.reused = true;
 
         let Some(arc) = self.pool.upgrade() else {
             debug!("Pool gone: {:?}", self.key);
@@ -134,18 +265,149 @@ impl Connection {
 
         debug!("Return to pool: {:?}", self.key);
 
+        // Idle connections don't count towards the limit. The slot is released
+        // once the connection is in the pool, for a waiting request to find it.
+        let permit = self.permit.take();
+
         let mut pool = arc.lock().unwrap();
 
         pool.add(self);
         pool.purge(now);
+
+        drop(pool);
+        drop(permit);
+    }
+
+    /// Whether this connection was checked out from the pool.
+    pub fn is_reused(&self) -> bool {
+        self.reused
+    }
+
+    /// Whether the transport is TLS.
+    pub fn is_tls(&self) -> bool {
+        self.transport.is_tls()
+    }
+
+    /// The TLS handshake time, but only for a newly opened connection.
+    pub fn tls_handshake_duration(&self) -> Option<std::time::Duration> {
+        if self.reused {
+            return None;
+        }
+        self.transport.tls_handshake_duration()
+    }
+
+    /// The address of the remote peer.
+    pub fn remote_addr(&self) -> Option<std::net::SocketAddr> {
+        self.transport.remote_addr()
     }
 
     fn age(&self, now: Instant) -> Duration {
-        now.duration_since(now)
+        now.duration_since(self.last_use)
+    }
+
+    fn probe(&mut self, kind: ProbeKind) -> bool {
+        self.transport.probe(kind)
+    }
+}
+
+/// Counter of connections in use for max_concurrent_connections.
+#[derive(Debug)]
+struct ConnectionLimit {
+    max: usize,
+    used: Mutex<usize>,
+    freed: Condvar,
+}
+
+impl ConnectionLimit {
+    fn new(max: usize) -> Self {
+        ConnectionLimit {
+            max,
+            used: Mutex::new(0),
+            freed: Condvar::new(),
+        }
+    }
+
+    fn acquire(self: &Arc<Self>, timeout: NextTimeout) -> Result<Permit, Error> {
+        let used = self.used.lock().unwrap();
+
+        let (mut used, result) = self
+            .freed
+            .wait_timeout_while(used, *timeout.after, |used| *used >= self.max)
+            .unwrap();
+
+        if result.timed_out() {
+            debug!("Timed out waiting for max_concurrent_connections");
+            return Err(Error::Timeout(timeout.reason));
+        }
+
+        *used += 1;
+
+        Ok(Permit(self.clone()))
+    }
+}
+
+/// Releases the slot in [`ConnectionLimit`] on drop.
+#[derive(Debug)]
+struct Permit(Arc<ConnectionLimit>);
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        let mut used = self.0.used.lock().unwrap();
+        *used -= 1;
+        self.0.freed.notify_one();
+    }
+}
+
+/// A transport outside the pool, holding a slot in max_concurrent_connections.
+#[derive(Debug)]
+struct PermitTransport {
+    inner: Box<dyn Transport>,
+    _permit: Permit,
+}
+
+impl PermitTransport {
+    fn wrap(transport: Box<dyn Transport>, permit: Option<Permit>) -> Box<dyn Transport> {
+        match permit {
+            Some(permit) => Box::new(PermitTransport {
+                inner: transport,
+                _permit: permit,
+            }),
+            None => transport,
+        }
+    }
+}
+
+impl Transport for PermitTransport {
+    fn buffers(&mut self) -> &mut dyn Buffers {
+        self.inner.buffers()
+    }
+
+    fn transmit_output(&mut self, amount: usize, timeout: NextTimeout) -> Result<(), Error> {
+        self.inner.transmit_output(amount, timeout)
+    }
+
+    fn await_input(&mut self, timeout: NextTimeout) -> Result<bool, Error> {
+        self.inner.await_input(timeout)
     }
 
     fn is_open(&mut self) -> bool {
-        self.transport.is_open()
+        self.inner.is_open()
+    }
+
+    fn probe(&mut self, kind: ProbeKind) -> bool {
+        self.inner.probe(kind)
+    }
+
+    fn is_tls(&self) -> bool {
+        self.inner.is_tls()
+    }
+
+    fn tls_handshake_duration(&self) -> Option<std::time::Duration> {
+        self.inner.tls_handshake_duration()
+    }
+
+    fn remote_addr(&self) -> Option<std::net::SocketAddr> {
+        self.inner.remote_addr()
     }
 }
 
@@ -166,11 +428,23 @@ impl Connection {
 struct PoolKey(Arc<PoolKeyInner>);
 
 impl PoolKey {
-    fn new(uri: &Uri, proxy: Option<&Proxy>) -> Self {
+    fn new(uri: &Uri, config: &Config) -> Self {
+        let scheme = uri.scheme().expect("uri with scheme");
+
         let inner = PoolKeyInner(
-            uri.scheme().expect("uri with scheme").clone(),
+            scheme.clone(),
             uri.authority().expect("uri with authority").clone(),
-            proxy.cloned(),
+            config.proxy().cloned(),
+            // A connection authenticated with one client cert must not be used for another.
+            #[cfg(feature = "_tls")]
+            (*scheme == Scheme::HTTPS)
+                .then(|| config.tls_config().client_cert().cloned())
+                .flatten(),
+            // Nor one where the server certificate was verified for another name.
+            #[cfg(feature = "_tls")]
+            (*scheme == Scheme::HTTPS)
+                .then(|| config.tls_config().sni_name_shared())
+                .flatten(),
         );
 
         PoolKey(Arc::new(inner))
@@ -178,7 +452,13 @@ impl PoolKey {
 }
 
 #[derive(PartialEq, Eq)]
-struct PoolKeyInner(Scheme, Authority, Option<Proxy>);
+struct PoolKeyInner(
+    Scheme,
+    Authority,
+    Option<Proxy>,
+    #[cfg(feature = "_tls")] Option<ClientCert>,
+    #[cfg(feature = "_tls")] Option<Arc<String>>,
+);
 
 #[derive(Debug)]
 struct Pool {
@@ -250,12 +530,18 @@ impl Pool {
         self.lru.push_back(conn)
     }
 
-    fn get(&mut self, key: &PoolKey, max_idle_age: Duration, now: Instant) -> Option<Connection> {
+    fn get(
+        &mut self,
+        key: &PoolKey,
+        max_idle_age: Duration,
+        probe: ProbeKind,
+        now: Instant,
+    ) -> Option<Connection> {
         while let Some(i) = self.lru.iter().position(|c| c.key == *key) {
             let mut conn = self.lru.remove(i).unwrap(); // unwrap ok since we just got the position
 
             // Before we release the connection, we probe that it appears to still work.
-            if !conn.is_open() {
+            if !conn.probe(probe) {
                 // This connection is broken. Try find another one.
                 continue;
             }
@@ -292,21 +578,323 @@ impl fmt::Debug for Connection {
 
 impl fmt::Debug for PoolKey {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_struct("PoolKey")
-            .field("scheme", &self.0 .0)
+        let mut dbg = f.debug_struct("PoolKey");
+
+        dbg.field("scheme", &self.0 .0)
             .field("authority", &DebugAuthority(&self.0 .1))
-            .field("proxy", &self.0 .2)
-            .finish()
+            .field("proxy", &self.0 .2);
+
+        #[cfg(feature = "_tls")]
+        dbg.field("client_cert", &self.0 .3.is_some())
+            .field("sni_name", &self.0 .4);
+
+        dbg.finish()
     }
 }
 
 #[cfg(all(test, feature = "_test"))]
 mod test {
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
     use super::*;
+    use crate::resolver::{DefaultResolver, ResolvedSocketAddrs};
+    use crate::transport::LazyBuffers;
+    use crate::Timeout;
 
     #[test]
     fn poolkey_new() {
         // Test that PoolKey::new() does not panic on unrecognized schemes.
-        PoolKey::new(&Uri::from_static("zzz://example.com"), None);
+        PoolKey::new(&Uri::from_static("zzz://example.com"), &Config::default());
+    }
+
+    struct IdleTransport(LazyBuffers, Arc<AtomicBool>);
+
+    impl fmt::Debug for IdleTransport {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_struct("IdleTransport").finish()
+        }
+    }
+
+    impl Transport for IdleTransport {
+        fn buffers(&mut self) -> &mut dyn Buffers {
+            &mut self.0
+        }
+
+        fn transmit_output(&mut self, _: usize, _: NextTimeout) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn await_input(&mut self, _: NextTimeout) -> Result<bool, Error> {
+            Ok(false)
+        }
+
+        fn is_open(&mut self) -> bool {
+            self.1.load(Ordering::SeqCst)
+        }
+    }
+
+    fn idle_connection(
+        pool: &Arc<Mutex<Pool>>,
+        uri: &'static str,
+        last_use: Instant,
+    ) -> Connection {
+        Connection {
+            transport: Box::new(IdleTransport(
+                LazyBuffers::new(1024, 1024),
+                Arc::new(AtomicBool::new(true)),
+            )),
+            key: PoolKey::new(&Uri::from_static(uri), &Config::default()),
+            last_use,
+            pool: Arc::downgrade(pool),
+            position_per_host: None,
+            reused: false,
+            created: last_use,
+            requests: 0,
+            reuse_if: None,
+            abort: None,
+            no_reuse: false,
+            permit: None,
+            byte_counts: None,
+        }
+    }
+
+    #[test]
+    fn max_idle_connections_per_host() {
+        let config = Config::builder().max_idle_connections_per_host(2).build();
+        let pool = Arc::new(Mutex::new(Pool::new(&config)));
+
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        idle_connection(&pool, "https://a.test", at(0)).reuse(at(0));
+        idle_connection(&pool, "https://b.test", at(1)).reuse(at(1));
+        idle_connection(&pool, "https://a.test", at(2)).reuse(at(2));
+        idle_connection(&pool, "https://b.test", at(3)).reuse(at(3));
+
+        // Both hosts are at the cap, nothing evicted yet.
+        assert_eq!(pool.lock().unwrap().lru.len(), 4);
+
+        idle_connection(&pool, "https://a.test", at(4)).reuse(at(4));
+
+        let lock = pool.lock().unwrap();
+        let remaining: Vec<_> = lock
+            .lru
+            .iter()
+            .map(|c| (c.key.0 .1.host().to_string(), c.last_use))
+            .collect();
+
+        // The oldest idle connection to a.test is evicted, b.test is untouched.
+        assert_eq!(
+            remaining,
+            [
+                ("b.test".to_string(), at(1)),
+                ("a.test".to_string(), at(2)),
+                ("b.test".to_string(), at(3)),
+                ("a.test".to_string(), at(4)),
+            ]
+        );
+    }
+
+    #[test]
+    fn purge_idle_too_old() {
+        let pool = ConnectionPool::new(IdleConnector::default(), &Config::default());
+
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        idle_connection(&pool.pool, "https://a.test", at(0)).reuse(at(0));
+        idle_connection(&pool.pool, "https://b.test", at(10)).reuse(at(10));
+
+        pool.purge(at(14));
+        assert_eq!(pool.pool_count(), 2);
+
+        // Default max_idle_age is 15 seconds.
+        pool.purge(at(16));
+        assert_eq!(pool.pool_count(), 1);
+
+        pool.purge(at(26));
+        assert_eq!(pool.pool_count(), 0);
+    }
+
+    #[derive(Debug, Default, Clone)]
+    struct IdleConnector {
+        connects: Arc<AtomicUsize>,
+        open: Arc<AtomicBool>,
+    }
+
+    impl Connector for IdleConnector {
+        fn connect(
+            &self,
+            _: &ConnectionDetails,
+            _: Option<Box<dyn Transport>>,
+        ) -> Result<Option<Box<dyn Transport>>, Error> {
+            self.connects.fetch_add(1, Ordering::SeqCst);
+            self.open.store(true, Ordering::SeqCst);
+            let buffers = LazyBuffers::new(1024, 1024);
+            Ok(Some(Box::new(IdleTransport(buffers, self.open.clone()))))
+        }
+    }
+
+    fn connect_with_probe(pool: &ConnectionPool, probe: ProbeKind, now: Instant) -> Connection {
+        let config = Config::builder().pool_checkout_probe(probe).build();
+        connect_with(pool, &config, Duration::NotHappening, now).unwrap()
+    }
+
+    fn connect_with(
+        pool: &ConnectionPool,
+        config: &Config,
+        timeout: Duration,
+        now: Instant,
+    ) -> Result<Connection, Error> {
+        let uri = Uri::from_static("https://a.test");
+        let details = ConnectionDetails {
+            uri: &uri,
+            addrs: ResolvedSocketAddrs::from_fn(|_| "1.2.3.4:443".parse().unwrap()),
+            resolver: &DefaultResolver::default(),
+            config,
+            now,
+            timeout: NextTimeout {
+                after: timeout,
+                reason: Timeout::Connect,
+            },
+        };
+        pool.connect(&details, Duration::from_secs(15))
+    }
+
+    #[test]
+    fn checkout_probe_replaces_closed() {
+        let connector = IdleConnector::default();
+        let open = connector.open.clone();
+        let pool = ConnectionPool::new(connector.clone(), &Config::default());
+        let now = Instant::now();
+
+        connect_with_probe(&pool, ProbeKind::NonBlockingRead, now).reuse(now);
+        assert_eq!(pool.pool_count(), 1);
+
+        // Server closes the idle connection.
+        open.store(false, Ordering::SeqCst);
+
+        // The probe detects the closed connection and a new one is made.
+        connect_with_probe(&pool, ProbeKind::NonBlockingRead, now).reuse(now);
+        assert_eq!(connector.connects.load(Ordering::SeqCst), 2);
+        assert_eq!(pool.pool_count(), 1);
+
+        open.store(false, Ordering::SeqCst);
+
+        // Without probe, the closed connection is handed out.
+        let _conn = connect_with_probe(&pool, ProbeKind::None, now);
+        assert_eq!(connector.connects.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn max_concurrent_connections() {
+        let config = Config::builder().max_concurrent_connections(1).build();
+        let pool = Arc::new(ConnectionPool::new(IdleConnector::default(), &config));
+        let now = Instant::now();
+        let short = Duration::Exact(std::time::Duration::from_millis(10));
+
+        let conn = connect_with(&pool, &config, short, now).unwrap();
+
+        let err = connect_with(&pool, &config, short, now).unwrap_err();
+        assert!(matches!(err, Error::Timeout(Timeout::Connect)));
+
+        // A closed connection frees the slot.
+        conn.close();
+        let conn = connect_with(&pool, &config, short, now).unwrap();
+
+        // A waiting request gets the connection returned to the pool.
+        let pool2 = pool.clone();
+        let config2 = config.clone();
+        let waiting = std::thread::spawn(move || {
+            connect_with(&pool2, &config2, Duration::NotHappening, now).map(|c| c.reused)
+        });
+
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        conn.reuse(now);
+
+        assert!(waiting.join().unwrap().unwrap());
+    }
+
+    #[test]
+    fn max_concurrent_connections_outside_pool() {
+        let config = Config::builder().max_concurrent_connections(1).build();
+        let pool = ConnectionPool::new(IdleConnector::default(), &config);
+        let now = Instant::now();
+        let short = Duration::Exact(std::time::Duration::from_millis(10));
+
+        // A transport taken out of the connection, like an upgraded one, holds the slot.
+        let transport = connect_with(&pool, &config, short, now)
+            .unwrap()
+            .into_transport();
+        let err = connect_with(&pool, &config, short, now).unwrap_err();
+        assert!(matches!(err, Error::Timeout(Timeout::Connect)));
+        drop(transport);
+
+        // So does an unpooled one, such as for a CONNECT tunnel.
+        let uri = Uri::from_static("https://a.test");
+        let details = ConnectionDetails {
+            uri: &uri,
+            addrs: ResolvedSocketAddrs::from_fn(|_| "1.2.3.4:443".parse().unwrap()),
+            resolver: &DefaultResolver::default(),
+            config: &config,
+            now,
+            timeout: NextTimeout {
+                after: short,
+                reason: Timeout::Connect,
+            },
+        };
+        let transport = pool.connect_unpooled(&details).unwrap();
+        assert!(pool.connect_unpooled(&details).is_err());
+        let err = connect_with(&pool, &config, short, now).unwrap_err();
+        assert!(matches!(err, Error::Timeout(Timeout::Connect)));
+        drop(transport);
+
+        connect_with(&pool, &config, short, now).unwrap();
+    }
+
+    #[cfg(feature = "_tls")]
+    #[test]
+    fn poolkey_client_cert() {
+        use crate::tls::{Certificate, KeyKind, PrivateKey, TlsConfig};
+
+        let config = |der: &'static [u8]| {
+            let cert = ClientCert::new_with_certs(
+                &[Certificate::from_der(der)],
+                PrivateKey::from_der(KeyKind::Pkcs8, b"key"),
+            );
+            let tls = TlsConfig::builder().client_cert(Some(cert)).build();
+            Config::builder().tls_config(tls).build()
+        };
+
+        let https = Uri::from_static("https://a.test");
+        let http = Uri::from_static("http://a.test");
+
+        assert!(PoolKey::new(&https, &config(b"a")) == PoolKey::new(&https, &config(b"a")));
+        assert!(PoolKey::new(&https, &config(b"a")) != PoolKey::new(&https, &config(b"b")));
+        assert!(PoolKey::new(&https, &config(b"a")) != PoolKey::new(&https, &Config::default()));
+
+        // Client certs are only used for TLS.
+        assert!(PoolKey::new(&http, &config(b"a")) == PoolKey::new(&http, &config(b"b")));
+    }
+
+    #[cfg(feature = "_tls")]
+    #[test]
+    fn poolkey_sni_name() {
+        use crate::tls::TlsConfig;
+
+        let config = |name: &str| {
+            let tls = TlsConfig::builder()
+                .sni_name(Some(name.to_string()))
+                .build();
+            Config::builder().tls_config(tls).build()
+        };
+
+        let https = Uri::from_static("https://10.0.0.1");
+
+        assert!(PoolKey::new(&https, &config("a.test")) == PoolKey::new(&https, &config("a.test")));
+        assert!(PoolKey::new(&https, &config("a.test")) != PoolKey::new(&https, &config("b.test")));
+        assert!(
+            PoolKey::new(&https, &config("a.test")) != PoolKey::new(&https, &Config::default())
+        );
     }
 }
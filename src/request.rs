@@ -2,16 +2,21 @@ use std::convert::TryFrom;
 use std::fmt;
 use std::marker::PhantomData;
 use std::ops::{Deref, DerefMut};
+use std::time::Duration;
 
 use http::{HeaderName, HeaderValue, Method, Request, Response, Uri, Version};
 
 use crate::body::Body;
 use crate::config::typestate::RequestScope;
 use crate::config::{Config, ConfigBuilder, RequestLevelConfig};
+#[cfg(feature = "digest")]
+use crate::digest::BodyDigest;
 use crate::http;
 use crate::query::url_enc;
 use crate::query::{parse_query_params, QueryParam};
+use crate::run::run_tunnel;
 use crate::send_body::AsSendBody;
+use crate::transport::Transport;
 use crate::util::private::Private;
 use crate::util::HeaderMapExt;
 use crate::util::UriExt;
@@ -74,6 +79,26 @@ impl<Any> RequestBuilder<Any> {
         self
     }
 
+    /// Sets the `User-Agent` header for this request.
+    ///
+    /// This overrides the agent default from
+    /// [`ConfigBuilder::user_agent()`](crate::config::ConfigBuilder::user_agent)
+    /// and replaces any `User-Agent` header already set on this request.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let req = ureq::get("https://httpbin.org/get")
+    ///     .user_agent("my-app/1.0");
+    /// ```
+    pub fn user_agent(mut self, ua: impl AsRef<str>) -> Self {
+        if let Some(headers) = self.builder.headers_mut() {
+            headers.remove(http::header::USER_AGENT);
+        }
+        self.builder = self.builder.header(http::header::USER_AGENT, ua.as_ref());
+        self
+    }
+
     /// Add a query parameter to the URL.
     ///
     /// Always appends a new parameter, also when using the name of
@@ -161,6 +186,24 @@ impl<Any> RequestBuilder<Any> {
         self
     }
 
+    /// Timeout for the entire call.
+    ///
+    /// Shortcut for setting [`timeout_global`](crate::config::ConfigBuilder::timeout_global)
+    /// on the request level config, without leaving the request builder.
+    ///
+    /// ```
+    /// use std::time::Duration;
+    ///
+    /// let res = ureq::get("http://httpbin.org/get")
+    ///     .timeout(Duration::from_secs(10))
+    ///     .header("x-my-header", "value")
+    ///     .call()?;
+    /// # Ok::<_, ureq::Error>(())
+    /// ```
+    pub fn timeout(self, timeout: Duration) -> Self {
+        self.config().timeout_global(Some(timeout)).build()
+    }
+
     /// Override agent level config on the request level.
     ///
     /// The agent config is copied and modified on request level.
@@ -273,6 +316,39 @@ impl RequestBuilder<WithoutBody> {
             _ph: PhantomData,
         }
     }
+
+    /// Opens a connection for the request and returns the raw transport.
+    ///
+    /// With a configured HTTP proxy, this performs the CONNECT handshake for
+    /// the request URI and returns the tunnel through the proxy. Without a proxy,
+    /// the transport is connected directly to the host, with TLS for `https`.
+    ///
+    /// No request is sent, which means the method and headers are not used.
+    /// The transport is not pooled. Read and write it using
+    /// [`TransportAdapter`](crate::unversioned::transport::TransportAdapter).
+    ///
+    /// ```no_run
+    /// use std::io::{Read, Write};
+    /// use ureq::unversioned::transport::TransportAdapter;
+    ///
+    /// let transport = ureq::connect("https://example.com").connect_tunnel()?;
+    ///
+    /// let mut stream = TransportAdapter::new(transport);
+    /// stream.write_all(b"custom protocol")?;
+    /// stream.flush()?;
+    ///
+    /// let mut buf = [0; 1024];
+    /// let n = stream.read(&mut buf)?;
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn connect_tunnel(self) -> Result<Box<dyn Transport>, Error> {
+        let mut request = self.builder.body(())?;
+        if !self.query_extra.is_empty() {
+            request.uri().ensure_valid_url()?;
+            request = amend_request_query(request, self.query_extra.into_iter());
+        }
+        run_tunnel(&self.agent, request)
+    }
 }
 
 impl RequestBuilder<WithBody> {
@@ -307,6 +383,60 @@ impl RequestBuilder<WithBody> {
         self
     }
 
+    /// Send the `Expect: 100-continue` header.
+    ///
+    /// With `true`, the request headers are sent first, and the body only once the
+    /// server responds `100 Continue`. This lets the server reject a large upload
+    /// before it is sent. If the server responds with a final status instead, the body
+    /// is not sent at all.
+    ///
+    /// Servers that don't support 100-continue never answer, which is why the body is
+    /// sent anyway after [`timeout_await_100`](crate::config::ConfigBuilder::timeout_await_100)
+    /// (default 1 second).
+    ///
+    /// With `false`, any `Expect` header set on this request is removed.
+    ///
+    /// ```
+    /// let res = ureq::put("http://httpbin.org/put")
+    ///     .expect_continue(true)
+    ///     .send(&[0_u8; 1000][..])?;
+    /// # Ok::<_, ureq::Error>(())
+    /// ```
+    pub fn expect_continue(mut self, v: bool) -> Self {
+        if let Some(headers) = self.builder.headers_mut() {
+            headers.remove(http::header::EXPECT);
+        }
+        if v {
+            self.builder = self.builder.header(http::header::EXPECT, "100-continue");
+        }
+        self
+    }
+
+    /// Send a digest of the body as a header.
+    ///
+    /// The digest is computed over the entire body before sending. [`BodyDigest::Md5`]
+    /// sets `Content-MD5` and [`BodyDigest::Sha256`] sets `Digest: sha-256=…`.
+    ///
+    /// Requires the **digest** feature.
+    ///
+    /// *Note:* The body is read into memory to compute the digest. For a streaming body,
+    /// such as a [`SendBody::from_reader()`], this means the whole stream is buffered,
+    /// and the body is sent with a `Content-Length` instead of chunked.
+    ///
+    /// ```
+    /// use ureq::BodyDigest;
+    ///
+    /// let res = ureq::put("http://httpbin.org/put")
+    ///     .with_body_digest(BodyDigest::Md5)
+    ///     .send("Hello World!")?;
+    /// # Ok::<_, ureq::Error>(())
+    /// ```
+    #[cfg(feature = "digest")]
+    pub fn with_body_digest(mut self, digest: BodyDigest) -> Self {
+        self.builder = self.builder.extension(digest);
+        self
+    }
+
     /// Send body data and blocks the caller until we receive response.
     ///
     /// ```
@@ -412,8 +542,49 @@ impl RequestBuilder<WithBody> {
     /// ```
     #[cfg(feature = "json")]
     pub fn send_json(self, data: impl serde::ser::Serialize) -> Result<Response<Body>, Error> {
-        let mut request = self.builder.body(())?;
         let body = SendBody::from_json(&data)?;
+        self.do_send_json(body)
+    }
+
+    /// Send a [`serde_json::Value`] as JSON.
+    ///
+    /// Requires the **json** feature.
+    ///
+    /// Like [`send_json()`](Self::send_json), but the value is serialized compactly
+    /// straight to bytes. Will set the content-type header `application/json`.
+    ///
+    /// ```
+    /// let value = serde_json::json!({ "thing": "yo" });
+    ///
+    /// let res = ureq::post("http://httpbin.org/post")
+    ///     .send_json_value(&value)?;
+    /// # Ok::<_, ureq::Error>(())
+    /// ```
+    #[cfg(feature = "json")]
+    pub fn send_json_value(self, value: &serde_json::Value) -> Result<Response<Body>, Error> {
+        let mut json = serde_json::to_vec(value)?;
+        self.do_send_json(json.as_body())
+    }
+
+    /// Send already serialized JSON.
+    ///
+    /// The bytes are sent as is, without checking they are valid JSON. Will set the
+    /// content-type header `application/json`. This avoids serializing again when
+    /// forwarding a JSON payload.
+    ///
+    /// ```
+    /// let json = br#"{"thing":"yo"}"#;
+    ///
+    /// let res = ureq::post("http://httpbin.org/post")
+    ///     .send_json_bytes(json)?;
+    /// # Ok::<_, ureq::Error>(())
+    /// ```
+    pub fn send_json_bytes(self, mut json: &[u8]) -> Result<Response<Body>, Error> {
+        self.do_send_json(json.as_body())
+    }
+
+    fn do_send_json(self, body: SendBody) -> Result<Response<Body>, Error> {
+        let mut request = self.builder.body(())?;
 
         if !request.headers().has_content_type() {
             request.headers_mut().append(
@@ -436,8 +607,19 @@ fn do_call(
         request.uri().ensure_valid_url()?;
         request = amend_request_query(request, query_extra.into_iter());
     }
-    let response = agent.run_via_middleware(request, body)?;
-    Ok(response)
+
+    #[cfg(feature = "digest")]
+    if let Some(digest) = request.extensions_mut().remove::<BodyDigest>() {
+        // The digest must be in the header, before we send the body.
+        let (buffered, body) = body.into_buffered()?;
+
+        let (name, value) = digest.header(&buffered);
+        request.headers_mut().insert(name, value);
+
+        return agent.run_via_middleware(request, body);
+    }
+
+    agent.run_via_middleware(request, body)
 }
 
 fn amend_request_query(
@@ -560,6 +742,41 @@ mod test {
             .build();
     }
 
+    #[test]
+    fn timeout_shortcut() {
+        let mut req = get("https://foo/bar").timeout(Duration::from_secs(3));
+        let config = req.request_level_config();
+        assert_eq!(config.timeouts().global, Some(Duration::from_secs(3)));
+    }
+
+    #[test]
+    fn user_agent_overrides_default() {
+        init_test_log();
+        let mut res = get("http://httpbin.org/echo-headers")
+            .header("user-agent", "first")
+            .user_agent("my-app/1.0")
+            .call()
+            .unwrap();
+        let body = res.body_mut().read_to_string().unwrap();
+        let ua: Vec<_> = body
+            .lines()
+            .filter(|l| l.starts_with("user-agent"))
+            .collect();
+        assert_eq!(ua, ["user-agent: my-app/1.0"]);
+    }
+
+    #[test]
+    fn send_json_bytes_sets_content_type() {
+        init_test_log();
+        let mut res = crate::post("http://httpbin.org/echo-headers")
+            .send_json_bytes(br#"{"thing":"yo"}"#)
+            .unwrap();
+        let body = res.body_mut().read_to_string().unwrap();
+        assert!(body
+            .lines()
+            .any(|l| l == "content-type: application/json; charset=utf-8"));
+    }
+
     #[test]
     fn add_params_to_request_without_query() {
         let request = Request::builder()
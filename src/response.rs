@@ -1,25 +1,587 @@
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
 use http::Uri;
 
-use crate::body::Body;
+use crate::body::{Body, BodyReader};
 use crate::http;
+use crate::timings::TimingInfo;
+use crate::util::{parse_http_date, parse_link_header, HeaderMapExt, UriExt};
+use crate::Error;
 
 #[derive(Debug, Clone)]
 pub(crate) struct ResponseUri(pub http::Uri);
 
+#[derive(Debug, Clone)]
+pub(crate) struct EarlyHints(pub Vec<http::Uri>);
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ConnectionReused(pub bool);
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct RemoteAddr(pub SocketAddr);
+
+#[derive(Debug, Clone)]
+pub(crate) struct ReasonPhrase(pub String);
+
+/// Bytes sent and received for a request. Shared with the connection, which means
+/// the received count grows while the body is read.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ByteCounts(Arc<(AtomicU64, AtomicU64)>);
+
+impl ByteCounts {
+    pub fn add_sent(&self, amount: usize) {
+        self.0 .0.fetch_add(amount as u64, Ordering::Relaxed);
+    }
+
+    pub fn add_received(&self, amount: usize) {
+        self.0 .1.fetch_add(amount as u64, Ordering::Relaxed);
+    }
+
+    pub fn sent(&self) -> u64 {
+        self.0 .0.load(Ordering::Relaxed)
+    }
+
+    pub fn received(&self) -> u64 {
+        self.0 .1.load(Ordering::Relaxed)
+    }
+}
+
+/// An entry of a `Link` header.
+///
+/// Read using [`ResponseExt::links()`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Link {
+    /// The link target, resolved against the response uri.
+    pub uri: Uri,
+
+    /// The `rel` parameter, such as `next` or `last`.
+    pub rel: Option<String>,
+
+    /// Other parameters than `rel`.
+    ///
+    /// Names are in lower case and quoted values are unquoted. A parameter without
+    /// value has an empty string.
+    pub params: Vec<(String, String)>,
+}
+
 /// Extension trait for `http::Response<Body>` objects
 ///
 /// Allows the user to access the `Uri` in http::Response
 pub trait ResponseExt {
     /// The Uri we ended up at. This can differ from the request uri when we have followed redirects.
+    ///
+    /// Same as [`ResponseExt::final_uri()`].
     fn get_uri(&self) -> &Uri;
+
+    /// The Uri we ended up at after following any redirects.
+    ///
+    /// Always set on responses from ureq, without any extra config. When no
+    /// redirect was followed, this is the request uri.
+    ///
+    /// ```
+    /// use ureq::ResponseExt;
+    ///
+    /// let res = ureq::get("http://httpbin.org/redirect-to?url=%2Fget").call()?;
+    ///
+    /// assert_eq!(res.final_uri().path(), "/get");
+    /// # Ok::<_, ureq::Error>(())
+    /// ```
+    fn final_uri(&self) -> &Uri;
+
+    /// Preload links sent in `103 Early Hints` before the final response.
+    ///
+    /// The links are taken from `Link` headers with `rel=preload`. Relative links
+    /// are resolved against the request uri.
+    fn early_hints_links(&self) -> Vec<Uri>;
+
+    /// Breakdown of the time spent making the request.
+    ///
+    /// Set on all responses from ureq. When following redirects, the timings are
+    /// for the last request.
+    ///
+    /// ```
+    /// use ureq::ResponseExt;
+    ///
+    /// let res = ureq::get("http://httpbin.org/get").call()?;
+    ///
+    /// let timings = res.timings().unwrap();
+    /// println!("Waited {:?} for the server", timings.time_to_first_byte);
+    /// # Ok::<_, ureq::Error>(())
+    /// ```
+    fn timings(&self) -> Option<&TimingInfo>;
+
+    /// Whether the request was sent on a connection reused from the pool.
+    ///
+    /// `false` means a new connection was opened. When following redirects, this
+    /// is for the last request.
+    fn connection_reused(&self) -> bool;
+
+    /// The socket address of the server the response came from.
+    ///
+    /// For proxied requests, this is the address of the proxy. When following
+    /// redirects, this is for the last request. `None` if the transport
+    /// doesn't have a socket address, such as a Unix domain socket.
+    ///
+    /// ```
+    /// use ureq::ResponseExt;
+    ///
+    /// let res = ureq::get("http://httpbin.org/get").call()?;
+    ///
+    /// if let Some(addr) = res.remote_addr() {
+    ///     println!("Connected to {}", addr.ip());
+    /// }
+    /// # Ok::<_, ureq::Error>(())
+    /// ```
+    fn remote_addr(&self) -> Option<SocketAddr>;
+
+    /// Number of bytes sent for the request.
+    ///
+    /// This is the request head and body as written to the transport, i.e. after
+    /// chunked encoding, but not counting any TLS overhead. When following redirects,
+    /// this is for the last request.
+    fn bytes_sent(&self) -> u64;
+
+    /// Number of bytes received for the response.
+    ///
+    /// This is the response head and body as read from the transport, i.e. before
+    /// decompression and not counting any TLS overhead. The count grows while the body
+    /// is read, which means it is complete once the body is read to the end. When
+    /// following redirects, this is for the last request.
+    ///
+    /// ```
+    /// use ureq::ResponseExt;
+    ///
+    /// let mut res = ureq::get("http://httpbin.org/bytes/100").call()?;
+    /// res.body_mut().read_to_vec()?;
+    ///
+    /// println!("Sent {} bytes, received {} bytes", res.bytes_sent(), res.bytes_received());
+    /// # Ok::<_, ureq::Error>(())
+    /// ```
+    fn bytes_received(&self) -> u64;
+
+    /// The reason phrase of the response status line.
+    ///
+    /// For `HTTP/1.1 404 Not Found` this is `Not Found`. The reason phrase is
+    /// whatever the server sent, which doesn't have to match the status code.
+    /// Non-UTF-8 bytes are replaced with `U+FFFD`. `None` if the server didn't
+    /// send a reason phrase.
+    ///
+    /// ```
+    /// use ureq::ResponseExt;
+    ///
+    /// let res = ureq::get("http://httpbin.org/get").call()?;
+    ///
+    /// assert_eq!(res.reason_phrase(), Some("OK"));
+    /// # Ok::<_, ureq::Error>(())
+    /// ```
+    fn reason_phrase(&self) -> Option<&str>;
+
+    /// The `Retry-After` header as a duration.
+    ///
+    /// Typically sent with `429 Too Many Requests` or `503 Service Unavailable`. The
+    /// header is either a number of seconds or an HTTP date. A date is converted to a
+    /// duration from now, and a date in the past gives a zero duration.
+    ///
+    /// Returns `None` if the header is absent or can't be parsed.
+    fn retry_after(&self) -> Option<Duration>;
+
+    /// All values of a header as string slices.
+    ///
+    /// Useful for headers that can be repeated, such as `Link` or `Set-Cookie`. Values
+    /// that are not valid utf-8 are skipped.
+    ///
+    /// ```
+    /// use ureq::ResponseExt;
+    ///
+    /// let res = ureq::get("http://httpbin.org/get").call()?;
+    ///
+    /// for link in res.header_all("link") {
+    ///     println!("{}", link);
+    /// }
+    /// # Ok::<_, ureq::Error>(())
+    /// ```
+    fn header_all(&self, name: &str) -> Vec<&str>;
+
+    /// Entries of all `Link` headers.
+    ///
+    /// A header can hold several comma separated entries, and the header can be
+    /// repeated. Relative links are resolved against the response uri. This is
+    /// commonly used for paginating REST APIs.
+    ///
+    /// ```
+    /// use ureq::ResponseExt;
+    ///
+    /// let res = ureq::get("http://httpbin.org/get").call()?;
+    ///
+    /// let next = res
+    ///     .links()
+    ///     .into_iter()
+    ///     .find(|l| l.rel.as_deref() == Some("next"));
+    ///
+    /// if let Some(next) = next {
+    ///     println!("Next page: {}", next.uri);
+    /// }
+    /// # Ok::<_, ureq::Error>(())
+    /// ```
+    fn links(&self) -> Vec<Link>;
+
+    /// Turn the response into an owned reader of the body.
+    ///
+    /// Shortcut for `response.into_parts().1.into_reader()`. The headers are dropped,
+    /// so inspect them before calling this. The reader has lifetime `'static` and can
+    /// for instance be sent to another thread.
+    ///
+    /// ```
+    /// use std::io::Read;
+    /// use std::thread;
+    /// use ureq::ResponseExt;
+    ///
+    /// let res = ureq::get("http://httpbin.org/bytes/100")
+    ///     .call()?;
+    ///
+    /// let mut reader = res.into_body_reader();
+    ///
+    /// let handle = thread::spawn(move || {
+    ///     let mut bytes = vec![];
+    ///     reader.read_to_end(&mut bytes).map(|_| bytes)
+    /// });
+    ///
+    /// let bytes = handle.join().unwrap()?;
+    /// assert_eq!(bytes.len(), 100);
+    /// # Ok::<_, ureq::Error>(())
+    /// ```
+    fn into_body_reader(self) -> BodyReader<'static>;
+
+    /// Consume the response and read the body to a vec.
+    ///
+    /// The `limit` is the max number of bytes to read. A larger body results in
+    /// an error.
+    ///
+    /// ```
+    /// use ureq::ResponseExt;
+    ///
+    /// let bytes = ureq::get("http://httpbin.org/bytes/100")
+    ///     .call()?
+    ///     .into_bytes(1024)?;
+    ///
+    /// assert_eq!(bytes.len(), 100);
+    /// # Ok::<_, ureq::Error>(())
+    /// ```
+    fn into_bytes(self, limit: u64) -> Result<Vec<u8>, Error>;
+
+    /// Consume the response and read the body to a string.
+    ///
+    /// The `limit` is the max number of bytes to read. A larger body results in
+    /// an error. Like [`Body::read_to_string()`], broken utf-8 is replaced rather
+    /// than causing an error.
+    ///
+    /// ```
+    /// use ureq::ResponseExt;
+    ///
+    /// let s = ureq::get("http://httpbin.org/robots.txt")
+    ///     .call()?
+    ///     .into_string(1024)?;
+    ///
+    /// assert_eq!(s, "User-agent: *\nDisallow: /deny\n");
+    /// # Ok::<_, ureq::Error>(())
+    /// ```
+    fn into_string(self, limit: u64) -> Result<String, Error>;
 }
 
 impl ResponseExt for http::Response<Body> {
     fn get_uri(&self) -> &Uri {
+        self.final_uri()
+    }
+
+    fn final_uri(&self) -> &Uri {
         &self
             .extensions()
             .get::<ResponseUri>()
             .expect("uri to have been set")
             .0
     }
+
+    fn early_hints_links(&self) -> Vec<Uri> {
+        self.extensions()
+            .get::<EarlyHints>()
+            .map(|h| h.0.clone())
+            .unwrap_or_default()
+    }
+
+    fn timings(&self) -> Option<&TimingInfo> {
+        self.extensions().get::<TimingInfo>()
+    }
+
+    fn connection_reused(&self) -> bool {
+        self.extensions()
+            .get::<ConnectionReused>()
+            .map(|c| c.0)
+            .unwrap_or(false)
+    }
+
+    fn remote_addr(&self) -> Option<SocketAddr> {
+        self.extensions().get::<RemoteAddr>().map(|a| a.0)
+    }
+
+    fn bytes_sent(&self) -> u64 {
+        self.extensions()
+            .get::<ByteCounts>()
+            .map(|c| c.sent())
+            .unwrap_or(0)
+    }
+
+    fn bytes_received(&self) -> u64 {
+        self.extensions()
+            .get::<ByteCounts>()
+            .map(|c| c.received())
+            .unwrap_or(0)
+    }
+
+    fn reason_phrase(&self) -> Option<&str> {
+        self.extensions()
+            .get::<ReasonPhrase>()
+            .map(|r| r.0.as_str())
+    }
+
+    fn retry_after(&self) -> Option<Duration> {
+        let value = self.headers().get_str("retry-after")?.trim();
+
+        if let Ok(secs) = value.parse::<u64>() {
+            return Some(Duration::from_secs(secs));
+        }
+
+        let date = parse_http_date(value)?;
+
+        Some(
+            date.duration_since(SystemTime::now())
+                .unwrap_or(Duration::ZERO),
+        )
+    }
+
+    fn header_all(&self, name: &str) -> Vec<&str> {
+        self.headers()
+            .get_all(name)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .collect()
+    }
+
+    fn links(&self) -> Vec<Link> {
+        let base = self.extensions().get::<ResponseUri>().map(|u| &u.0);
+
+        self.header_all("link")
+            .into_iter()
+            .flat_map(parse_link_header)
+            .filter_map(|(target, params)| {
+                let uri = match base {
+                    Some(base) => base.resolve(target)?,
+                    None => target.parse().ok()?,
+                };
+
+                let mut rel = None;
+                let mut rest = vec![];
+
+                for (name, value) in parse_link_params(params) {
+                    if name == "rel" {
+                        rel.get_or_insert(value);
+                    } else {
+                        rest.push((name, value));
+                    }
+                }
+
+                Some(Link {
+                    uri,
+                    rel,
+                    params: rest,
+                })
+            })
+            .collect()
+    }
+
+    fn into_body_reader(self) -> BodyReader<'static> {
+        self.into_body().into_reader()
+    }
+
+    fn into_bytes(self, limit: u64) -> Result<Vec<u8>, Error> {
+        self.into_body()
+            .into_with_config()
+            .limit(limit)
+            .read_to_vec()
+    }
+
+    fn into_string(self, limit: u64) -> Result<String, Error> {
+        self.into_body()
+            .into_with_config()
+            .limit(limit)
+            .lossy_utf8(true)
+            .read_to_string()
+    }
+}
+
+/// Parse the `; name=value` params following a link target.
+fn parse_link_params(params: &str) -> Vec<(String, String)> {
+    let mut result = vec![];
+    let mut chars = params.chars().peekable();
+
+    loop {
+        // Skip to the start of the next param.
+        while chars.next_if(|c| *c == ';' || c.is_whitespace()).is_some() {}
+        if chars.peek().is_none() {
+            break;
+        }
+
+        let mut name = String::new();
+        while let Some(c) = chars.next_if(|c| *c != '=' && *c != ';') {
+            name.push(c.to_ascii_lowercase());
+        }
+
+        let mut value = String::new();
+        if chars.next_if_eq(&'=').is_some() {
+            while chars.next_if(|c| c.is_whitespace()).is_some() {}
+
+            if chars.next_if_eq(&'"').is_some() {
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' => value.extend(chars.next()),
+                        _ => value.push(c),
+                    }
+                }
+                // Anything after the closing quote up to the next param is dropped.
+                while chars.next_if(|c| *c != ';').is_some() {}
+            } else {
+                while let Some(c) = chars.next_if(|c| *c != ';') {
+                    value.push(c);
+                }
+            }
+        }
+
+        result.push((name.trim().to_string(), value.trim().to_string()));
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn response_with_retry_after(v: &str) -> http::Response<Body> {
+        http::Response::builder()
+            .header("retry-after", v)
+            .body(Body::builder().data(""))
+            .unwrap()
+    }
+
+    #[test]
+    fn retry_after_seconds() {
+        let res = response_with_retry_after("120");
+        assert_eq!(res.retry_after(), Some(Duration::from_secs(120)));
+    }
+
+    #[test]
+    fn retry_after_http_date() {
+        let past = response_with_retry_after("Sun, 06 Nov 1994 08:49:37 GMT");
+        assert_eq!(past.retry_after(), Some(Duration::ZERO));
+
+        let future = response_with_retry_after("Fri, 31 Dec 9999 23:59:59 GMT");
+        assert!(future.retry_after().unwrap() > Duration::from_secs(86_400 * 365));
+    }
+
+    #[test]
+    fn retry_after_missing_or_bad() {
+        let res = http::Response::builder()
+            .body(Body::builder().data(""))
+            .unwrap();
+        assert_eq!(res.retry_after(), None);
+
+        let res = response_with_retry_after("soon");
+        assert_eq!(res.retry_after(), None);
+    }
+
+    #[test]
+    fn header_all_values() {
+        let res = http::Response::builder()
+            .header("link", "<https://a.test/?page=2>; rel=next")
+            .header("link", http::HeaderValue::from_bytes(b"\xff").unwrap())
+            .header("link", "<https://a.test/?page=9>; rel=last")
+            .body(Body::builder().data(""))
+            .unwrap();
+        assert_eq!(
+            res.header_all("link"),
+            [
+                "<https://a.test/?page=2>; rel=next",
+                "<https://a.test/?page=9>; rel=last"
+            ]
+        );
+        assert!(res.header_all("set-cookie").is_empty());
+    }
+
+    #[test]
+    fn links_multiple_entries_and_headers() {
+        let mut res = http::Response::builder()
+            .header(
+                "link",
+                r#"<https://api.test/items?page=2>; rel="next", </items?page=9>; rel=last"#,
+            )
+            .header(
+                "link",
+                r#"<other>; REL=alternate; title="a; b, \"c\""; hreflang=sv"#,
+            )
+            .body(Body::builder().data(""))
+            .unwrap();
+        res.extensions_mut().insert(ResponseUri(
+            "https://api.test/v1/items?page=1".parse().unwrap(),
+        ));
+
+        let links = res.links();
+        assert_eq!(links.len(), 3);
+
+        assert_eq!(links[0].uri, "https://api.test/items?page=2");
+        assert_eq!(links[0].rel.as_deref(), Some("next"));
+        assert!(links[0].params.is_empty());
+
+        assert_eq!(links[1].uri, "https://api.test/items?page=9");
+        assert_eq!(links[1].rel.as_deref(), Some("last"));
+
+        assert_eq!(links[2].uri, "https://api.test/v1/other");
+        assert_eq!(links[2].rel.as_deref(), Some("alternate"));
+        assert_eq!(
+            links[2].params,
+            [
+                ("title".to_string(), r#"a; b, "c""#.to_string()),
+                ("hreflang".to_string(), "sv".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn links_without_header() {
+        let res = http::Response::new(Body::builder().data(""));
+        assert!(res.links().is_empty());
+    }
+
+    #[test]
+    fn into_bytes_and_string() {
+        let res = http::Response::new(Body::builder().data("hello"));
+        assert_eq!(res.into_bytes(100).unwrap(), b"hello");
+
+        let res = http::Response::new(Body::builder().data("hello"));
+        assert_eq!(res.into_string(100).unwrap(), "hello");
+
+        let res = http::Response::new(Body::builder().data("hello"));
+        let err = res.into_bytes(4).unwrap_err();
+        assert!(matches!(err, Error::BodyExceedsLimit(4)));
+    }
+
+    #[test]
+    fn parse_http_date_epoch() {
+        let t = parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT").unwrap();
+        let secs = t.duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
+        assert_eq!(secs, 784_111_777);
+    }
 }
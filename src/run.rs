@@ -2,23 +2,28 @@ use std::sync::Arc;
 use std::{io, mem};
 
 use http::uri::Scheme;
-use http::{header, HeaderValue, Request, Response, Uri};
+use http::{header, HeaderValue, Request, Response, StatusCode, Uri, Version};
 use once_cell::sync::Lazy;
 use ureq_proto::client::flow::state::{Await100, RecvBody, RecvResponse, Redirect, SendRequest};
 use ureq_proto::client::flow::state::{Prepare, SendBody as SendBodyState};
 use ureq_proto::client::flow::{Await100Result, RecvBodyResult};
 use ureq_proto::client::flow::{RecvResponseResult, SendRequestResult};
+use ureq_proto::parser::try_parse_response;
 use ureq_proto::BodyMode;
 
 use crate::body::ResponseInfo;
-use crate::config::{Config, RequestLevelConfig, DEFAULT_USER_AGENT};
+use crate::config::{Config, RedirectAction, RequestLevelConfig};
+use crate::config::{DEFAULT_USER_AGENT, MAX_RESPONSE_HEADERS};
 use crate::http;
 use crate::pool::Connection;
-use crate::response::ResponseUri;
+use crate::resolver::{ResolvedSocketAddrs, MAX_ADDRS};
+use crate::response::{ByteCounts, ConnectionReused, EarlyHints, ReasonPhrase};
+use crate::response::{RemoteAddr, ResponseUri};
 use crate::timings::{CallTimings, CurrentTime};
 use crate::transport::time::{Duration, Instant};
-use crate::transport::ConnectionDetails;
-use crate::util::{DebugRequest, DebugResponse, DebugUri, HeaderMapExt, UriExt};
+use crate::transport::{ConnectionDetails, NextTimeout, Transport};
+use crate::util::{parse_link_header, DebugRequest, DebugResponse, DebugUri};
+use crate::util::{HeaderMapExt, SchemeExt, UriExt};
 use crate::{Agent, Body, Error, SendBody, Timeout};
 
 type Flow<T> = ureq_proto::client::flow::Flow<(), T>;
@@ -29,24 +34,48 @@ type Flow<T> = ureq_proto::client::flow::Flow<(), T>;
 pub(crate) fn run(
     agent: &Agent,
     mut request: Request<()>,
+    body: SendBody,
+) -> Result<Response<Body>, Error> {
+    let config = take_config(agent, &mut request);
+
+    if !config.error_context() {
+        return do_run(agent, &config, request, body);
+    }
+
+    let method = request.method().clone();
+    let uri = request.uri().clone();
+
+    do_run(agent, &config, request, body)
+        .map_err(|e| Error::WithContext(Box::new((method, uri, e))))
+}
+
+#[cfg_attr(not(feature = "charset"), allow(unused_mut))]
+fn do_run(
+    agent: &Agent,
+    config: &Config,
+    mut request: Request<()>,
     mut body: SendBody,
 ) -> Result<Response<Body>, Error> {
     let mut redirect_count = 0;
 
-    // Configuration on the request level overrides the agent level.
-    let config = request
-        .extensions_mut()
-        .remove::<RequestLevelConfig>()
-        .map(|rl| rl.0)
-        .map(Arc::new)
-        .unwrap_or_else(|| agent.config.clone());
-
     let timeouts = config.timeouts();
 
-    let mut timings = CallTimings::new(timeouts, CurrentTime::default());
+    let mut timings =
+        CallTimings::new(timeouts, CurrentTime::default(), config.observer().cloned());
+
+    #[cfg(feature = "charset")]
+    if let Some(charset) = body.charset() {
+        set_content_type_charset(request.headers_mut(), charset)?;
+    }
 
     let mut flow = Flow::new(request)?;
 
+    // Only kept when detecting redirect loops.
+    let mut visited = vec![];
+    if config.detect_redirect_loops() {
+        visited.push(flow.uri().clone());
+    }
+
     if config.force_send_body {
         flow.send_body_despite_method();
     }
@@ -61,19 +90,20 @@ pub(crate) fn run(
             return Err(Error::Timeout(Timeout::Global));
         }
 
-        match flow_run(
-            agent,
-            &config,
-            flow,
-            &mut body,
-            redirect_count,
-            &mut timings,
-        )? {
+        match flow_run(agent, config, flow, &mut body, redirect_count, &mut timings)? {
             // Follow redirect
             FlowResult::Redirect(rflow, rtimings) => {
                 redirect_count += 1;
 
-                flow = handle_redirect(rflow, &config)?;
+                if config.detect_redirect_loops() {
+                    let uri = rflow.uri();
+                    if visited.contains(uri) {
+                        return Err(Error::RedirectLoop(uri.clone()));
+                    }
+                    visited.push(uri.clone());
+                }
+
+                flow = rflow;
                 timings = rtimings.new_call();
             }
 
@@ -90,20 +120,114 @@ pub(crate) fn run(
         .map(|f| f.body_mode())
         .unwrap_or(BodyMode::NoBody);
 
-    let info = ResponseInfo::new(&parts.headers, recv_body_mode);
+    let info = ResponseInfo::new(&parts.headers, recv_body_mode)
+        .with_max_body_size(config.max_response_body_size());
 
     let body = Body::new(handler, info);
 
     let response = Response::from_parts(parts, body);
 
-    let status = response.status();
-    let is_err = status.is_client_error() || status.is_server_error();
+    check_status(config, response.status())?;
+
+    Ok(response)
+}
+
+/// Turn the response status into an error, if so configured.
+pub(crate) fn check_status(config: &Config, status: StatusCode) -> Result<(), Error> {
+    let is_err = if config.error_on_server_error_only() {
+        status.is_server_error()
+    } else {
+        status.is_client_error() || status.is_server_error()
+    };
 
     if config.http_status_as_error() && is_err {
         return Err(Error::StatusCode(status.as_u16()));
     }
 
-    Ok(response)
+    Ok(())
+}
+
+/// Open a connection for the request, without sending it.
+///
+/// If a CONNECT proxy is configured, the connector chain performs the CONNECT
+/// handshake, and the returned transport is the tunnel through the proxy.
+pub(crate) fn run_tunnel(
+    agent: &Agent,
+    mut request: Request<()>,
+) -> Result<Box<dyn Transport>, Error> {
+    let config = take_config(agent, &mut request);
+
+    let uri = request.uri();
+    info!("Tunnel {:?}", &DebugUri(uri));
+
+    if config.https_only() && uri.scheme() != Some(&Scheme::HTTPS) {
+        return Err(Error::RequireHttpsOnly(uri.to_string()));
+    }
+
+    if config
+        .abort_handle()
+        .map(|a| a.is_aborted())
+        .unwrap_or(false)
+    {
+        return Err(Error::Aborted);
+    }
+
+    let mut timings = CallTimings::new(
+        config.timeouts(),
+        CurrentTime::default(),
+        config.observer().cloned(),
+    );
+
+    let addrs = resolve(agent, &config, uri, &mut timings)?;
+
+    let details = ConnectionDetails {
+        uri,
+        addrs,
+        resolver: &*agent.resolver,
+        config: &config,
+        now: timings.now(),
+        timeout: timings.next_timeout(Timeout::Connect),
+    };
+
+    let transport = agent.pool.connect_unpooled(&details)?;
+
+    timings.record_time(Timeout::Connect);
+
+    Ok(transport)
+}
+
+/// Connect to the uri and put the connection in the pool.
+pub(crate) fn warm_up(agent: &Agent, uri: &Uri) -> Result<(), Error> {
+    let config = &agent.config;
+    info!("Warm up {:?}", &DebugUri(uri));
+
+    if config.https_only() && uri.scheme() != Some(&Scheme::HTTPS) {
+        return Err(Error::RequireHttpsOnly(uri.to_string()));
+    }
+
+    let mut timings = CallTimings::new(
+        config.timeouts(),
+        CurrentTime::default(),
+        config.observer().cloned(),
+    );
+
+    let mut connection = connect(agent, config, uri, &mut timings)?;
+
+    handshake(&mut connection, &mut timings)?;
+
+    cleanup(connection, false, timings.now());
+
+    Ok(())
+}
+
+/// Configuration on the request level overrides the agent level.
+fn take_config(agent: &Agent, request: &mut Request<()>) -> Arc<Config> {
+    request
+        .extensions_mut()
+        .remove::<RequestLevelConfig>()
+        .map(|rl| rl.0)
+        .map(Arc::new)
+        .unwrap_or_else(|| agent.config.clone())
 }
 
 fn flow_run(
@@ -121,10 +245,23 @@ fn flow_run(
         return Err(Error::RequireHttpsOnly(uri.to_string()));
     }
 
+    if config
+        .abort_handle()
+        .map(|a| a.is_aborted())
+        .unwrap_or(false)
+    {
+        return Err(Error::Aborted);
+    }
+
     add_headers(&mut flow, agent, config, body, &uri)?;
 
     let mut connection = connect(agent, config, &uri, timings)?;
 
+    handshake(&mut connection, timings)?;
+
+    let byte_counts = ByteCounts::default();
+    connection.set_byte_counts(byte_counts.clone());
+
     let mut flow = flow.proceed();
 
     if log_enabled!(log::Level::Info) {
@@ -139,22 +276,29 @@ fn flow_run(
         info!("{:?}", r);
     }
 
-    let flow = match send_request(flow, &mut connection, timings)? {
+    let (result, pending) = send_request(flow, &mut connection, body, config, timings)?;
+
+    let flow = match result {
         SendRequestResult::Await100(flow) => match await_100(flow, &mut connection, timings)? {
-            Await100Result::SendBody(flow) => send_body(flow, body, &mut connection, timings)?,
+            Await100Result::SendBody(flow) => {
+                send_body(flow, body, &mut connection, 0, config, timings)?
+            }
             Await100Result::RecvResponse(flow) => flow,
         },
-        SendRequestResult::SendBody(flow) => send_body(flow, body, &mut connection, timings)?,
+        SendRequestResult::SendBody(flow) => {
+            send_body(flow, body, &mut connection, pending, config, timings)?
+        }
         SendRequestResult::RecvResponse(flow) => flow,
     };
 
-    let (mut response, response_result) = recv_response(flow, &mut connection, config, timings)?;
+    let (mut response, response_result) =
+        recv_response(flow, &mut connection, config, timings, &uri)?;
 
     info!("{:?}", DebugResponse(&response));
 
     #[cfg(feature = "cookies")]
     {
-        let mut jar = agent.cookie_jar_lock();
+        let mut jar = config.cookie_jar().unwrap_or(&agent.jar).lock();
 
         let iter = response
             .headers()
@@ -166,23 +310,42 @@ fn flow_run(
         jar.store_response_cookies(iter, &uri);
     }
 
-    response.extensions_mut().insert(ResponseUri(uri));
+    response.extensions_mut().insert(ResponseUri(uri.clone()));
+
+    response.extensions_mut().insert(timings.timing_info());
+
+    response
+        .extensions_mut()
+        .insert(ConnectionReused(connection.is_reused()));
+
+    if is_http10_close(&response) {
+        debug!("HTTP/1.0 response without keep-alive, not reusing connection");
+        connection.set_no_reuse();
+    }
+
+    if let Some(addr) = connection.remote_addr() {
+        response.extensions_mut().insert(RemoteAddr(addr));
+    }
+
+    response.extensions_mut().insert(byte_counts);
 
     let ret = match response_result {
         RecvResponseResult::RecvBody(flow) => {
             let timings = mem::take(timings);
-            let mut handler = BodyHandler {
-                flow: Some(flow),
-                connection: Some(connection),
-                timings,
-                ..Default::default()
-            };
+            let mut handler = BodyHandler::default();
+            handler.flow = Some(flow);
+            handler.connection = Some(connection);
+            handler.timings = timings;
 
             if response.status().is_redirection() {
                 if redirect_count < config.max_redirects() {
                     let flow = handler.consume_redirect_body()?;
 
-                    FlowResult::Redirect(flow, handler.timings)
+                    match handle_redirect(flow, config, &uri)? {
+                        Some(flow) => FlowResult::Redirect(flow, mem::take(&mut handler.timings)),
+                        // The body is consumed already.
+                        None => FlowResult::Response(response, BodyHandler::default()),
+                    }
                 } else if config.max_redirects_do_error() {
                     return Err(Error::TooManyRedirects);
                 } else {
@@ -196,15 +359,35 @@ fn flow_run(
             cleanup(connection, flow.must_close_connection(), timings.now());
 
             if redirect_count < config.max_redirects() {
-                FlowResult::Redirect(flow, mem::take(timings))
+                match handle_redirect(flow, config, &uri)? {
+                    Some(flow) => FlowResult::Redirect(flow, mem::take(timings)),
+                    None => FlowResult::Response(response, BodyHandler::default()),
+                }
             } else if config.max_redirects_do_error() {
                 return Err(Error::TooManyRedirects);
             } else {
                 FlowResult::Response(response, BodyHandler::default())
             }
         }
+        RecvResponseResult::Cleanup(_) if response.status() == StatusCode::SWITCHING_PROTOCOLS => {
+            // The connection now speaks another protocol. It is handed out
+            // via Body::into_upgraded() rather than cleaned up.
+            let mut handler = BodyHandler::default();
+            handler.connection = Some(connection);
+            handler.timings = mem::take(timings);
+            FlowResult::Response(response, handler)
+        }
         RecvResponseResult::Cleanup(flow) => {
-            cleanup(connection, flow.must_close_connection(), timings.now());
+            let unexpected_body = has_unexpected_body(&response, &mut connection);
+
+            if unexpected_body && config.no_content_body_as_error() {
+                connection.close();
+                return Err(Error::UnexpectedResponseBody(response.status().as_u16()));
+            }
+
+            // Any body data would desync the next request on a pooled connection.
+            let must_close = flow.must_close_connection() || unexpected_body;
+            cleanup(connection, must_close, timings.now());
             FlowResult::Response(response, BodyHandler::default())
         }
     };
@@ -215,13 +398,54 @@ fn flow_run(
 /// Return type of [`flow_run`].
 #[allow(clippy::large_enum_variant)]
 enum FlowResult {
-    /// Flow resulted in a redirect.
-    Redirect(Flow<Redirect>, CallTimings),
+    /// Flow resulted in a redirect to follow.
+    Redirect(Flow<Prepare>, CallTimings),
 
     /// Flow resulted in a response.
     Response(Response<()>, BodyHandler),
 }
 
+/// Add the charset of an encoded send body to the content-type.
+#[cfg(feature = "charset")]
+pub(crate) fn set_content_type_charset(
+    headers: &mut http::HeaderMap,
+    charset: &'static str,
+) -> Result<(), Error> {
+    let value = match headers.get(header::CONTENT_TYPE) {
+        Some(v) => {
+            let v = v.to_str().unwrap_or_default();
+
+            if let Some(existing) = content_type_charset(v) {
+                let same = encoding_rs::Encoding::for_label(existing.as_bytes())
+                    == encoding_rs::Encoding::for_label(charset.as_bytes());
+                if !same {
+                    return Err(Error::CharsetMismatch(existing.to_string(), charset));
+                }
+                return Ok(());
+            }
+
+            format!("{}; charset={}", v, charset)
+        }
+        None => format!("text/plain; charset={}", charset),
+    };
+
+    let value = HeaderValue::from_str(&value).map_err(http::Error::from)?;
+    headers.insert(header::CONTENT_TYPE, value);
+
+    Ok(())
+}
+
+/// The charset parameter of a content-type header value.
+#[cfg(feature = "charset")]
+fn content_type_charset(value: &str) -> Option<&str> {
+    value.split(';').skip(1).find_map(|param| {
+        let (name, value) = param.split_once('=')?;
+        name.trim()
+            .eq_ignore_ascii_case("charset")
+            .then(|| value.trim().trim_matches('"'))
+    })
+}
+
 fn add_headers(
     flow: &mut Flow<Prepare>,
     agent: &Agent,
@@ -229,16 +453,34 @@ fn add_headers(
     body: &SendBody,
     uri: &Uri,
 ) -> Result<(), Error> {
+    // Default headers only for names not set on the request.
+    let defaults = config.default_headers();
+    let missing: Vec<_> = defaults
+        .keys()
+        .filter(|name| !flow.headers().contains_key(*name))
+        .collect();
+
+    for name in missing {
+        for value in defaults.get_all(name) {
+            flow.header(name, value.clone())?;
+        }
+    }
+
     let headers = flow.headers();
 
+    let close_connection = !config.connection_pooling()
+        && !headers.contains_key(header::CONNECTION)
+        && !defaults.contains_key(header::CONNECTION);
+
     let send_body_mode = if headers.has_send_body_mode() {
         None
     } else {
         Some(body.body_mode())
     };
-    let has_header_accept_enc = headers.has_accept_encoding();
-    let has_header_ua = headers.has_user_agent();
-    let has_header_accept = headers.has_accept();
+    // The headers added above are not visible in flow.headers().
+    let has_header_accept_enc = headers.has_accept_encoding() || defaults.has_accept_encoding();
+    let has_header_ua = headers.has_user_agent() || defaults.has_user_agent();
+    let has_header_accept = headers.has_accept() || defaults.has_accept();
 
     #[cfg(not(feature = "cookies"))]
     {
@@ -247,7 +489,8 @@ fn add_headers(
     }
     #[cfg(feature = "cookies")]
     {
-        let value = agent.jar.get_request_cookies(uri);
+        let jar = config.cookie_jar().unwrap_or(&agent.jar);
+        let value = jar.get_request_cookies(uri);
         if !value.is_empty() {
             let value = HeaderValue::from_str(&value)
                 .map_err(|_| Error::CookieValue("Cookie value is an invalid http-header"))?;
@@ -277,6 +520,10 @@ fn add_headers(
         }
     }
 
+    if close_connection {
+        flow.header(header::CONNECTION, HeaderValue::from_static("close"))?;
+    }
+
     if let Some(send_body_mode) = send_body_mode {
         match send_body_mode {
             BodyMode::LengthDelimited(v) => {
@@ -318,22 +565,7 @@ fn connect(
     uri: &Uri,
     timings: &mut CallTimings,
 ) -> Result<Connection, Error> {
-    // If we're using a CONNECT proxy, we need to resolve that hostname.
-    let maybe_connect_uri = config.connect_proxy_uri();
-
-    let effective_uri = maybe_connect_uri.unwrap_or(uri);
-
-    // Before resolving the URI we need to ensure it is a full URI. We
-    // cannot make requests with partial uri like "/path".
-    effective_uri.ensure_valid_url()?;
-
-    let addrs = agent.resolver.resolve(
-        effective_uri,
-        config,
-        timings.next_timeout(Timeout::Resolve),
-    )?;
-
-    timings.record_time(Timeout::Resolve);
+    let addrs = resolve(agent, config, uri, timings)?;
 
     let details = ConnectionDetails {
         uri,
@@ -351,29 +583,150 @@ fn connect(
     Ok(connection)
 }
 
+/// Complete the TLS handshake of a newly opened connection.
+fn handshake(connection: &mut Connection, timings: &mut CallTimings) -> Result<(), Error> {
+    if connection.is_tls() && !connection.is_reused() {
+        // TLS transports complete the handshake on the first IO. Transmitting
+        // nothing drives it without sending anything.
+        connection.transmit_output(0, timings.next_timeout(Timeout::TlsHandshake))?;
+
+        if let Some(duration) = connection.tls_handshake_duration() {
+            timings.record_tls_handshake(duration);
+        }
+    }
+
+    timings.record_time(Timeout::TlsHandshake);
+
+    Ok(())
+}
+
+fn resolve(
+    agent: &Agent,
+    config: &Config,
+    uri: &Uri,
+    timings: &mut CallTimings,
+) -> Result<ResolvedSocketAddrs, Error> {
+    // If we're using a CONNECT proxy, we need to resolve that hostname.
+    let maybe_connect_uri = config.connect_proxy_uri();
+
+    let effective_uri = maybe_connect_uri.unwrap_or(uri);
+
+    // Before resolving the URI we need to ensure it is a full URI. We
+    // cannot make requests with partial uri like "/path".
+    effective_uri.ensure_valid_url()?;
+
+    // A socks5h proxy resolves the target, which means we must not look it up.
+    let proxy_resolves = config.proxy().map(|p| p.proto().resolves_target()) == Some(true);
+
+    let mut addrs = match resolve_override(config, effective_uri) {
+        Some(addr) => {
+            let mut v = ResolvedSocketAddrs::from_fn(|_| addr);
+            v.push(addr);
+            v
+        }
+        None if proxy_resolves => {
+            trace!("Target resolved by proxy");
+            ResolvedSocketAddrs::from_fn(|_| "0.0.0.0:0".parse().unwrap())
+        }
+        None => agent.resolver.resolve(
+            effective_uri,
+            config,
+            timings.next_timeout(Timeout::Resolve),
+        )?,
+    };
+
+    timings.record_time(Timeout::Resolve);
+
+    if let Some(sort) = config.address_sort() {
+        let mut sorted: Vec<_> = addrs.iter().copied().collect();
+        sort(&mut sorted);
+
+        addrs.truncate(0);
+        for addr in sorted.into_iter().take(MAX_ADDRS) {
+            addrs.push(addr);
+        }
+    }
+
+    Ok(addrs)
+}
+
+fn resolve_override(config: &Config, uri: &Uri) -> Option<std::net::SocketAddr> {
+    // unwraps are ok because ensure_valid_url() is checked before.
+    let port = uri
+        .port_u16()
+        .unwrap_or_else(|| uri.scheme().unwrap().default_port().unwrap());
+
+    config.resolve_override(uri.host()?, port)
+}
+
+/// Sends the request header.
+///
+/// A small body of known size is sent in the same write as the header, if the
+/// buffers keep the output. In that case the header is left in the output buffer,
+/// and the returned amount pending must be transmitted by [`send_body`], which also
+/// records the time of [`Timeout::SendRequest`].
 fn send_request(
     mut flow: Flow<SendRequest>,
     connection: &mut Connection,
+    body: &SendBody,
+    config: &Config,
     timings: &mut CallTimings,
-) -> Result<SendRequestResult<()>, Error> {
+) -> Result<(SendRequestResult<()>, usize), Error> {
+    let body_len = match body.body_mode() {
+        BodyMode::LengthDelimited(v) if connection.buffers().keeps_output() => Some(v),
+        _ => None,
+    };
+
+    let mut pending = 0;
+    let mut written = 0;
+
     loop {
         if flow.can_proceed() {
             break;
         }
 
-        let buffers = connection.buffers();
-        let amount = flow.write(buffers.output())?;
+        let output = connection.buffers().output();
+        let amount = flow.write(output)?;
+
+        written += amount;
+        if written > config.max_request_header_size() {
+            return Err(Error::LargeRequestHeader(
+                written,
+                config.max_request_header_size(),
+            ));
+        }
+
+        let body_fits = body_len
+            .map(|v| amount as u64 + v <= output.len() as u64)
+            .unwrap_or(false);
+
+        if flow.can_proceed() && body_fits {
+            pending = amount;
+            break;
+        }
+
         let timeout = timings.next_timeout(Timeout::SendRequest);
         connection.transmit_output(amount, timeout)?;
     }
 
-    timings.record_time(Timeout::SendRequest);
-
     // The request might be misconfigured.
     let flow = flow.proceed()?;
 
     // We checked can_proceed() above, this unwrap is fine.
-    Ok(flow.unwrap())
+    let flow = flow.unwrap();
+
+    // Only a body to send goes in the same write.
+    if pending > 0 && !matches!(flow, SendRequestResult::SendBody(_)) {
+        let timeout = timings.next_timeout(Timeout::SendRequest);
+        connection.transmit_output(pending, timeout)?;
+        pending = 0;
+    }
+
+    if pending == 0 {
+        timings.record_time(Timeout::SendRequest);
+    }
+
+    Ok((flow, pending))
 }
 
 fn await_100(
@@ -423,8 +776,13 @@ fn send_body(
     mut flow: Flow<SendBodyState>,
     body: &mut SendBody,
     connection: &mut Connection,
+    mut pending: usize,
+    config: &Config,
     timings: &mut CallTimings,
 ) -> Result<Flow<RecvResponse>, Error> {
+    // A zero chunk would be taken as the end of the body.
+    let chunk_size = config.output_chunk_size().map(|v| v.max(1));
+
     loop {
         if flow.can_proceed() {
             break;
@@ -434,10 +792,15 @@ fn send_body(
 
         let (tmp, output) = buffers.tmp_and_output();
 
+        // After any request header left by send_request().
+        let output = &mut output[pending..];
+
         let input_len = tmp.len();
 
         let input_fitting_in_output = flow.calculate_max_input(output.len());
-        let max_input = input_len.min(input_fitting_in_output);
+        let max_input = input_len
+            .min(input_fitting_in_output)
+            .min(chunk_size.unwrap_or(usize::MAX));
 
         let output_used = if !flow.is_chunked() {
             // For non-chunked, The body can be written directly to the output.
@@ -461,8 +824,22 @@ fn send_body(
             output_used
         };
 
-        let timeout = timings.next_timeout(Timeout::SendBody);
-        connection.transmit_output(output_used, timeout)?;
+        if pending > 0 {
+            // The request header goes in this write, still under its own timeout.
+            let timeout = timings.next_timeout(Timeout::SendRequest);
+            connection.transmit_output(pending + output_used, timeout)?;
+            timings.record_time(Timeout::SendRequest);
+            pending = 0;
+        } else {
+            let timeout = timings.next_timeout(Timeout::SendBody);
+            connection.transmit_output(output_used, timeout)?;
+        }
+    }
+
+    if pending > 0 {
+        let timeout = timings.next_timeout(Timeout::SendRequest);
+        connection.transmit_output(pending, timeout)?;
+        timings.record_time(Timeout::SendRequest);
     }
 
     timings.record_time(Timeout::SendBody);
@@ -474,14 +851,46 @@ fn recv_response(
     connection: &mut Connection,
     config: &Config,
     timings: &mut CallTimings,
+    uri: &Uri,
 ) -> Result<(Response<()>, RecvResponseResult<()>), Error> {
-    let response = loop {
+    let mut early_hints = vec![];
+    let max_headers = config.max_response_headers();
+
+    let mut response = loop {
         let timeout = timings.next_timeout(Timeout::RecvResponse);
         let made_progress = connection.await_input(timeout)?;
 
+        // Any 103 Early Hints precede the final response. These are consumed
+        // here, before handing the input to the flow.
+        while let Some((amount, hints)) =
+            try_early_hints(connection.buffers().input(), max_headers)?
+        {
+            connection.consume_input(amount);
+
+            let links = hints
+                .headers()
+                .get_all(header::LINK)
+                .iter()
+                .filter_map(|v| v.to_str().ok())
+                .flat_map(parse_link_header)
+                .filter(|(_, params)| is_preload(params))
+                .filter_map(|(target, _)| uri.resolve(target));
+
+            early_hints.extend(links);
+        }
+
         let input = connection.buffers().input();
 
-        let (amount, maybe_response) = flow.try_response(input)?;
+        let (amount, maybe_response) = flow.try_response(input).map_err(|e| match e {
+            ureq_proto::Error::HttpParseTooManyHeaders => Error::TooManyHeaders(max_headers),
+            e => e.into(),
+        })?;
+
+        if let Some(response) = &maybe_response {
+            if response.headers().len() > max_headers {
+                return Err(Error::TooManyHeaders(max_headers));
+            }
+        }
 
         if input.len() > config.max_response_header_size() {
             return Err(Error::LargeResponseHeader(
@@ -490,10 +899,19 @@ fn recv_response(
             ));
         }
 
+        // The http crate has no place for the reason phrase, so we pick
+        // it from the status line before the input is consumed.
+        let reason = maybe_response
+            .as_ref()
+            .and_then(|_| parse_reason_phrase(input));
+
         connection.consume_input(amount);
 
-        if let Some(response) = maybe_response {
+        if let Some(mut response) = maybe_response {
             assert!(flow.can_proceed());
+            if let Some(reason) = reason {
+                response.extensions_mut().insert(ReasonPhrase(reason));
+            }
             break response;
         } else if !made_progress {
             return Err(Error::disconnected());
@@ -501,25 +919,167 @@ fn recv_response(
     };
 
     timings.record_time(Timeout::RecvResponse);
+
+    if !early_hints.is_empty() {
+        response.extensions_mut().insert(EarlyHints(early_hints));
+    }
+
     Ok((response, flow.proceed().unwrap()))
 }
 
-fn handle_redirect(mut flow: Flow<Redirect>, config: &Config) -> Result<Flow<Prepare>, Error> {
+fn try_early_hints(
+    input: &[u8],
+    max_headers: usize,
+) -> Result<Option<(usize, Response<()>)>, Error> {
+    // Avoid parsing the response twice unless the status line is a 103.
+    if parse_status_code(input) != Some(b"103") {
+        return Ok(None);
+    }
+
+    let parsed = try_parse_response::<MAX_RESPONSE_HEADERS>(input).map_err(|e| match e {
+        ureq_proto::Error::HttpParseTooManyHeaders => Error::TooManyHeaders(max_headers),
+        e => e.into(),
+    })?;
+
+    if let Some((_, hints)) = &parsed {
+        if hints.headers().len() > max_headers {
+            return Err(Error::TooManyHeaders(max_headers));
+        }
+    }
+
+    Ok(parsed)
+}
+
+/// Extract the status code from a status line such as `HTTP/1.1 103 Early Hints`.
+fn parse_status_code(input: &[u8]) -> Option<&[u8]> {
+    let end = input.iter().position(|b| *b == b'\n')?;
+    let line = input[..end].strip_suffix(b"\r").unwrap_or(&input[..end]);
+
+    line.splitn(3, |b| *b == b' ').nth(1)
+}
+
+/// Extract the reason phrase from a status line such as `HTTP/1.1 200 OK`.
+fn parse_reason_phrase(input: &[u8]) -> Option<String> {
+    let end = input.iter().position(|b| *b == b'\n')?;
+    let line = input[..end].strip_suffix(b"\r").unwrap_or(&input[..end]);
+
+    // Skip the version and status code.
+    let mut parts = line.splitn(3, |b| *b == b' ');
+    parts.next()?;
+    parts.next()?;
+    let reason = parts.next()?;
+
+    if reason.is_empty() {
+        return None;
+    }
+
+    Some(String::from_utf8_lossy(reason).into_owned())
+}
+
+fn is_preload(params: &str) -> bool {
+    params.split(';').any(|p| {
+        let Some((k, v)) = p.split_once('=') else {
+            return false;
+        };
+        k.trim().eq_ignore_ascii_case("rel")
+            && v.trim()
+                .trim_matches('"')
+                .split_ascii_whitespace()
+                .any(|r| r.eq_ignore_ascii_case("preload"))
+    })
+}
+
+/// Whether an HTTP/1.0 response means the server closes the connection.
+///
+/// HTTP/1.0 connections close after the response unless the server sends
+/// `Connection: keep-alive`. HTTP/1.0 requests are never reused, since
+/// ureq-proto always closes them.
+fn is_http10_close(response: &Response<()>) -> bool {
+    if response.version() != Version::HTTP_10 {
+        return false;
+    }
+
+    let keep_alive = response
+        .headers()
+        .get_all(header::CONNECTION)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .any(|v| v.trim().eq_ignore_ascii_case("keep-alive"));
+
+    !keep_alive
+}
+
+/// Check whether a bodyless `204`/`304` response shows signs of a body.
+fn has_unexpected_body(response: &Response<()>, connection: &mut Connection) -> bool {
+    let status = response.status().as_u16();
+
+    if status != 204 && status != 304 {
+        return false;
+    }
+
+    // A 304 may legitimately have a content-length describing the resource
+    // that would have been sent. A 204 must not have either header.
+    let headers = response.headers();
+    let body_header =
+        status == 204 && (headers.is_chunked() || headers.content_length().unwrap_or(0) > 0);
+
+    // Data following the response header can only be a body.
+    let leftover_input = !connection.buffers().input().is_empty();
+
+    body_header || leftover_input
+}
+
+/// The flow for following the redirect, or `None` if the redirect should
+/// not be followed.
+fn handle_redirect(
+    mut flow: Flow<Redirect>,
+    config: &Config,
+    from: &Uri,
+) -> Result<Option<Flow<Prepare>>, Error> {
     let maybe_new_flow = flow.as_new_flow(config.redirect_auth_headers())?;
     let status = flow.status();
 
-    if let Some(flow) = maybe_new_flow {
-        info!(
-            "Redirect ({}): {} {:?}",
-            status,
-            flow.method(),
-            DebugUri(flow.uri())
-        );
+    let Some(mut flow) = maybe_new_flow else {
+        return Err(Error::RedirectFailed);
+    };
 
-        Ok(flow)
-    } else {
-        Err(Error::RedirectFailed)
+    if let Some(on_redirect) = config.on_redirect() {
+        match on_redirect(from, status, flow.uri()) {
+            RedirectAction::Follow => {}
+            RedirectAction::Stop => {
+                info!("Redirect ({}) stopped: {:?}", status, DebugUri(flow.uri()));
+                return Ok(None);
+            }
+            RedirectAction::FollowTo(uri) => flow = redirect_to(flow, uri)?,
+        }
+    }
+
+    info!(
+        "Redirect ({}): {} {:?}",
+        status,
+        flow.method(),
+        DebugUri(flow.uri())
+    );
+
+    Ok(Some(flow))
+}
+
+/// Same redirect flow, but to another uri.
+fn redirect_to(flow: Flow<Prepare>, uri: Uri) -> Result<Flow<Prepare>, Error> {
+    let mut request = Request::new(());
+    *request.method_mut() = flow.method().clone();
+    *request.version_mut() = flow.version();
+    *request.headers_mut() = flow.headers().clone();
+
+    // The decision to keep the authorization was for the original target.
+    if uri.authority() != flow.uri().authority() {
+        request.headers_mut().remove(header::AUTHORIZATION);
     }
+
+    *request.uri_mut() = uri;
+
+    Ok(Flow::new(request)?)
 }
 
 fn cleanup(connection: Connection, must_close: bool, now: Instant) {
@@ -537,9 +1097,57 @@ pub(crate) struct BodyHandler {
     timings: CallTimings,
     remote_closed: bool,
     redirect: Option<Flow<Redirect>>,
+    /// Set once reading the body has started.
+    started: bool,
+    /// Length of a body handed out by buffered_str(), still in the input buffer.
+    buffered: Option<usize>,
+    /// Set by BodyWithConfig::deadline().
+    deadline: Option<Instant>,
 }
 
 impl BodyHandler {
+    /// The entire body as a `&str` borrowed from the input buffer.
+    ///
+    /// Only possible if nothing is read yet, the body has a content-length up
+    /// to `max`, is completely in the input buffer, and is valid utf-8. The
+    /// body is consumed from the buffer on the next read, or when dropped.
+    ///
+    /// Gives back `self` if not possible.
+    pub(crate) fn buffered_str(&mut self, max: u64) -> Result<&str, &mut Self> {
+        if self.flow.is_none() {
+            // Either no body, or already read to end.
+            return Ok("");
+        }
+
+        let Some(len) = self.buffered_utf8_len(max) else {
+            return Err(self);
+        };
+
+        self.started = true;
+        self.buffered = Some(len);
+
+        // unwraps are ok because buffered_utf8_len() checked these.
+        let connection = self.connection.as_mut().unwrap();
+        let body = &connection.buffers().input()[..len];
+        Ok(std::str::from_utf8(body).unwrap())
+    }
+
+    fn buffered_utf8_len(&mut self, max: u64) -> Option<usize> {
+        let BodyMode::LengthDelimited(len) = self.flow.as_ref()?.body_mode() else {
+            return None;
+        };
+
+        if self.started || len > max {
+            return None;
+        }
+
+        let input = self.connection.as_mut()?.buffers().input();
+        let body = input.get(..len as usize)?;
+        std::str::from_utf8(body).ok()?;
+
+        Some(body.len())
+    }
+
     fn do_read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
         let (Some(flow), Some(connection), timings) =
             (&mut self.flow, &mut self.connection, &mut self.timings)
@@ -547,9 +1155,26 @@ impl BodyHandler {
             return Ok(0);
         };
 
+        self.started = true;
+
+        if let Some(mut left) = self.buffered.take() {
+            // Skip the body already handed out by buffered_str().
+            let mut scratch = [0_u8; 1024];
+            while left > 0 {
+                let input = &connection.buffers().input()[..left];
+                let (input_used, _) = flow.read(input, &mut scratch)?;
+                connection.consume_input(input_used);
+                if input_used == 0 {
+                    return Err(Error::BodyStalled);
+                }
+                left -= input_used;
+            }
+        }
+
         loop {
             let body_fulfilled = match flow.body_mode() {
-                BodyMode::NoBody => unreachable!("must be a BodyMode for BodyHandler"),
+                // Nothing to await, such as a response to HEAD that has a content-length.
+                BodyMode::NoBody => true,
                 // These modes are fulfilled by either reaching the content-length or
                 // receiving an end chunk delimiter.
                 BodyMode::LengthDelimited(_) | BodyMode::Chunked => flow.can_proceed(),
@@ -586,7 +1211,20 @@ impl BodyHandler {
                 return Ok(0);
             }
 
-            let timeout = timings.next_timeout(Timeout::RecvBody);
+            let mut timeout = timings.next_timeout(Timeout::RecvBody);
+
+            if let Some(deadline) = self.deadline {
+                let left = deadline.duration_since(timings.now());
+                if left.is_zero() {
+                    return Err(Error::Timeout(Timeout::RecvBody));
+                }
+                if left < timeout.after {
+                    timeout = NextTimeout {
+                        after: left,
+                        reason: Timeout::RecvBody,
+                    };
+                }
+            }
 
             let made_progress = match connection.await_input(timeout) {
                 Ok(v) => v,
@@ -649,6 +1287,18 @@ impl BodyHandler {
         Ok(())
     }
 
+    pub(crate) fn set_deadline(&mut self, deadline: std::time::Instant) {
+        self.deadline = Some(Instant::Exact(deadline));
+    }
+
+    /// The transport of a connection upgraded by `101 Switching Protocols`.
+    pub(crate) fn take_upgraded(&mut self) -> Option<Box<dyn Transport>> {
+        if self.flow.is_some() {
+            return None;
+        }
+        self.connection.take().map(Connection::into_transport)
+    }
+
     fn consume_redirect_body(&mut self) -> Result<Flow<Redirect>, Error> {
         let mut buf = vec![0; 1024];
         loop {
@@ -665,6 +1315,16 @@ impl BodyHandler {
     }
 }
 
+impl Drop for BodyHandler {
+    fn drop(&mut self) {
+        if self.buffered.is_some() {
+            // The body is already in memory. Reading it to end means the
+            // connection can go back to the pool.
+            let _ = self.do_read(&mut []);
+        }
+    }
+}
+
 impl io::Read for BodyHandler {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
         self.do_read(buf).map_err(|e| e.into_io())
@@ -1,10 +1,13 @@
 use std::fs::File;
-use std::io::{self, Read, Stdin};
+use std::io::{self, Read, Seek, Stdin};
 use std::net::TcpStream;
+#[cfg(feature = "digest")]
+use std::sync::Arc;
 
 use crate::body::{Body, BodyReader};
 use crate::http;
 use crate::util::private::Private;
+use crate::Error;
 
 /// Request body for sending data via POST, PUT and PATCH.
 ///
@@ -19,6 +22,20 @@ use crate::util::private::Private;
 pub struct SendBody<'a> {
     inner: BodyInner<'a>,
     ended: bool,
+    limit: Option<u64>,
+    /// Number of bytes read from the body so far.
+    read_so_far: u64,
+    progress: Option<Progress<'a>>,
+    /// Size declared by from_reader_sized().
+    size: Option<u64>,
+    /// Charset name from encode_charset(), for the content-type header.
+    #[cfg(feature = "charset")]
+    charset: Option<&'static str>,
+}
+
+enum Progress<'a> {
+    Owned(Box<dyn FnMut(u64) + Send + 'a>),
+    Borrowed(&'a mut (dyn FnMut(u64) + Send + 'a)),
 }
 
 impl<'a> SendBody<'a> {
@@ -32,11 +49,111 @@ impl<'a> SendBody<'a> {
         BodyInner::Reader(reader).into()
     }
 
+    /// Creates a body from a shared [`Read`] impl of a known size.
+    ///
+    /// Unlike [`SendBody::from_reader()`], the body is sent with a `Content-Length`
+    /// header instead of `transfer-encoding: chunked`. Some servers don't accept
+    /// chunked uploads.
+    ///
+    /// The reader must produce exactly `len` bytes, or the request fails.
+    ///
+    /// ```no_run
+    /// use std::fs::File;
+    /// use ureq::SendBody;
+    ///
+    /// let mut file = File::open("upload.bin")?;
+    /// let len = file.metadata()?.len();
+    ///
+    /// ureq::put("http://httpbin.org/put")
+    ///     .send(SendBody::from_reader_sized(&mut file, len))?;
+    /// # Ok::<_, ureq::Error>(())
+    /// ```
+    pub fn from_reader_sized(reader: &'a mut dyn Read, len: u64) -> SendBody<'a> {
+        let mut body: SendBody = BodyInner::Reader(reader).into();
+        body.size = Some(len);
+        body
+    }
+
+    /// Creates a body from a file.
+    ///
+    /// The size is taken from the file metadata, minus the current position, which
+    /// means the body is sent with a `Content-Length` header. Sending a `File` or
+    /// `&File` directly uses `transfer-encoding: chunked`.
+    ///
+    /// ```no_run
+    /// use std::fs::File;
+    /// use ureq::SendBody;
+    ///
+    /// let mut file = File::open("upload.bin")?;
+    ///
+    /// ureq::put("http://httpbin.org/put")
+    ///     .send(SendBody::from_file(&mut file))?;
+    /// # Ok::<_, ureq::Error>(())
+    /// ```
+    pub fn from_file(file: &'a mut File) -> SendBody<'a> {
+        let size = file_size(file);
+        let mut body: SendBody = BodyInner::Reader(file).into();
+        body.size = size;
+        body
+    }
+
     /// Creates a body from an owned [`Read]` impl.
     pub fn from_owned_reader(reader: impl Read + 'static) -> SendBody<'static> {
         BodyInner::OwnedReader(Box::new(reader)).into()
     }
 
+    /// Creates a body from the response of a previous call, for proxying.
+    ///
+    /// Takes ownership of the response body. Like sending a `Response<Body>` directly,
+    /// the body is sent with `Content-Length` when the response had one and is not
+    /// transformed by decompression or charset decoding. Otherwise it is sent with
+    /// `transfer-encoding: chunked`.
+    ///
+    /// ```
+    /// use ureq::SendBody;
+    ///
+    /// let res = ureq::get("http://httpbin.org/bytes/100").call()?;
+    ///
+    /// let body = SendBody::from_response(res);
+    /// ureq::post("http://httpbin.org/post").send(body)?;
+    /// # Ok::<_, ureq::Error>(())
+    /// ```
+    pub fn from_response(response: Response<Body>) -> SendBody<'static> {
+        BodyInner::Body(response.into_body().into_reader()).into()
+    }
+
+    /// Creates a body from an iterator of byte chunks.
+    ///
+    /// The chunks are pulled lazily while sending. The size is unknown, which means
+    /// the body is sent with `transfer-encoding: chunked`. An error from the iterator
+    /// fails the request.
+    ///
+    /// ```
+    /// use std::sync::mpsc;
+    /// use std::thread;
+    /// use ureq::SendBody;
+    ///
+    /// let (tx, rx) = mpsc::channel::<Vec<u8>>();
+    ///
+    /// thread::spawn(move || {
+    ///     tx.send(b"hello ".to_vec()).unwrap();
+    ///     tx.send(b"world".to_vec()).unwrap();
+    /// });
+    ///
+    /// let body = SendBody::from_chunks(rx.into_iter().map(Ok));
+    ///
+    /// let res = ureq::post("http://httpbin.org/post").send(body)?;
+    /// # Ok::<_, ureq::Error>(())
+    /// ```
+    pub fn from_chunks(
+        chunks: impl Iterator<Item = io::Result<Vec<u8>>> + Send + 'static,
+    ) -> SendBody<'static> {
+        Self::from_owned_reader(ChunksReader {
+            chunks,
+            current: io::Cursor::new(vec![]),
+        })
+    }
+
     /// Creates a body to send as JSON from any [`Serialize`](serde::ser::Serialize) value.
     #[cfg(feature = "json")]
     pub fn from_json(
@@ -46,23 +163,154 @@ impl<'a> SendBody<'a> {
         Ok(Self::from_owned_reader(io::Cursor::new(json)))
     }
 
+    /// Encode the body from utf-8 to another charset while sending.
+    ///
+    /// The body must be utf-8, such as a `&str`. The label is looked up using
+    /// [`Encoding::for_label`](encoding_rs::Encoding::for_label), e.g. `"iso-8859-1"`
+    /// or `"shift_jis"`, and fails with [`Error::UnknownCharset`] if unknown.
+    ///
+    /// The canonical name of the charset is added as `charset` parameter to the
+    /// `content-type` header. Without a `content-type`, it is set to
+    /// `text/plain; charset=<name>`. If the `content-type` already has a different
+    /// charset, the request fails with [`Error::CharsetMismatch`].
+    ///
+    /// Characters that can't be represented in the charset fail the request with
+    /// [`Error::UnmappableChar`]. The encoded size is not known up front, which
+    /// means the body is sent with `transfer-encoding: chunked`.
+    ///
+    /// *Note:* `encoding_rs` treats `iso-8859-1` and its aliases (such as `latin1`)
+    /// as `windows-1252`. For those labels the body is encoded as actual ISO-8859-1,
+    /// where characters above `U+00FF` (such as `€`) are unmappable.
+    ///
+    /// ```
+    /// use ureq::SendBody;
+    ///
+    /// let xml = "<name>Åsa</name>";
+    ///
+    /// let res = ureq::post("http://httpbin.org/post")
+    ///     .content_type("text/xml")
+    ///     .send(SendBody::from_reader(&mut xml.as_bytes()).encode_charset("iso-8859-1")?)?;
+    /// # Ok::<_, ureq::Error>(())
+    /// ```
+    #[cfg(feature = "charset")]
+    pub fn encode_charset(mut self, label: &str) -> Result<SendBody<'a>, Error> {
+        let encoding = encoding_rs::Encoding::for_label(label.as_bytes())
+            .ok_or_else(|| Error::UnknownCharset(label.to_string()))?;
+
+        // The WHATWG spec maps ISO-8859-1 labels to windows-1252, which would send
+        // characters like € as bytes that aren't ISO-8859-1.
+        let latin1 = encoding == encoding_rs::WINDOWS_1252
+            && !["windows-1252", "cp1252", "x-cp1252"]
+                .iter()
+                .any(|l| label.trim().eq_ignore_ascii_case(l));
+
+        let inner = std::mem::replace(&mut self.inner, BodyInner::None);
+
+        self.inner = BodyInner::Encoded(Box::new(CharsetEncoder {
+            inner,
+            encoder: encoding.new_encoder(),
+            latin1,
+            input: Vec::new(),
+            output: io::Cursor::new(Vec::new()),
+            input_ended: false,
+        }));
+        self.charset = Some(if latin1 { LATIN1 } else { encoding.name() });
+        // The encoded size isn't known.
+        self.size = None;
+
+        Ok(self)
+    }
+
+    /// Limit the number of bytes to read from the body.
+    ///
+    /// Sending the request fails with [`Error::BodyExceedsLimit`] if the body
+    /// is larger than the limit. This is useful when relaying an untrusted source,
+    /// such as the upload from some other client.
+    ///
+    /// ```
+    /// use std::io::Cursor;
+    /// use ureq::{Error, SendBody};
+    ///
+    /// let mut untrusted = Cursor::new(vec![0_u8; 1000]);
+    ///
+    /// let result = ureq::post("http://httpbin.org/post")
+    ///     .send(SendBody::from_reader(&mut untrusted).limit(100));
+    ///
+    /// assert!(matches!(result, Err(Error::BodyExceedsLimit(100))));
+    /// ```
+    pub fn limit(mut self, limit: u64) -> SendBody<'a> {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Get a callback with the number of bytes sent so far.
+    ///
+    /// The callback is invoked for every chunk read from the body, with the total
+    /// amount of bytes read from the body up until that point. This counts the body
+    /// data, not the bytes on the wire (i.e. any chunked encoding overhead is not
+    /// included).
+    ///
+    /// ```
+    /// use std::io::Cursor;
+    /// use ureq::SendBody;
+    ///
+    /// let mut file = Cursor::new(vec![0_u8; 1000]);
+    ///
+    /// let body = SendBody::from_reader(&mut file)
+    ///     .with_progress(|sent| println!("Uploaded {} bytes", sent));
+    ///
+    /// ureq::post("http://httpbin.org/post")
+    ///     .send(body)?;
+    /// # Ok::<_, ureq::Error>(())
+    /// ```
+    pub fn with_progress(mut self, callback: impl FnMut(u64) + Send + 'a) -> SendBody<'a> {
+        self.progress = Some(Progress::Owned(Box::new(callback)));
+        self
+    }
+
     pub(crate) fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        let n = match &mut self.inner {
-            BodyInner::None => {
-                return Ok(0);
+        let buf = match self.limit {
+            Some(limit) => {
+                // Allow reading one byte more than the limit to detect overrun.
+                let left = limit.saturating_sub(self.read_so_far).saturating_add(1);
+                let max = left.min(buf.len() as u64) as usize;
+                &mut buf[..max]
             }
-            BodyInner::ByteSlice(v) => {
-                let max = v.len().min(buf.len());
+            None => buf,
+        };
 
-                buf[..max].copy_from_slice(&v[..max]);
-                *v = &v[max..];
+        let n = self.read_inner(buf)?;
 
-                Ok(max)
+        self.read_so_far += n as u64;
+
+        if let Some(size) = self.size {
+            if n == 0 && !buf.is_empty() && self.read_so_far < size {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "body is shorter than the declared size",
+                ));
             }
-            BodyInner::Reader(v) => v.read(buf),
-            BodyInner::OwnedReader(v) => v.read(buf),
-            BodyInner::Body(v) => v.read(buf),
-        }?;
+        }
+
+        if n > 0 {
+            match &mut self.progress {
+                Some(Progress::Owned(f)) => f(self.read_so_far),
+                Some(Progress::Borrowed(f)) => f(self.read_so_far),
+                None => {}
+            }
+        }
+
+        if let Some(limit) = self.limit {
+            if self.read_so_far > limit {
+                return Err(Error::BodyExceedsLimit(limit).into_io());
+            }
+        }
+
+        Ok(n)
+    }
+
+    fn read_inner(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
 
         if n == 0 {
             self.ended = true;
@@ -72,7 +320,42 @@ impl<'a> SendBody<'a> {
     }
 
     pub(crate) fn body_mode(&self) -> BodyMode {
-        self.inner.body_mode()
+        match self.size {
+            Some(size) => BodyMode::LengthDelimited(size),
+            None => self.inner.body_mode(),
+        }
+    }
+
+    /// Charset the body is encoded to, if not utf-8.
+    #[cfg(feature = "charset")]
+    pub(crate) fn charset(&self) -> Option<&'static str> {
+        self.charset
+    }
+
+    /// Read the body to memory, to compute a digest before sending it.
+    ///
+    /// Returns the data and a body sending it, which keeps the charset and reports
+    /// progress as the data is sent rather than when it is read to memory.
+    #[cfg(feature = "digest")]
+    pub(crate) fn into_buffered(mut self) -> io::Result<(Arc<[u8]>, SendBody<'a>)> {
+        let progress = self.progress.take();
+        #[cfg(feature = "charset")]
+        let charset = self.charset;
+
+        let mut data = vec![];
+        self.into_reader().read_to_end(&mut data)?;
+        let data: Arc<[u8]> = data.into();
+
+        let reader = io::Cursor::new(data.clone());
+        let mut body: SendBody<'a> = BodyInner::OwnedReader(Box::new(reader)).into();
+        body.size = Some(data.len() as u64);
+        body.progress = progress;
+        #[cfg(feature = "charset")]
+        {
+            body.charset = charset;
+        }
+
+        Ok((data, body))
     }
 
     /// Turn this `SendBody` into a reader.
@@ -178,6 +461,15 @@ pub trait AsSendBody: Private {
 impl<'a> Private for SendBody<'a> {}
 impl<'a> AsSendBody for SendBody<'a> {
     fn as_body(&mut self) -> SendBody {
+        // A response body borrowed as a plain reader would lose its content-length.
+        let size = match &self.inner {
+            BodyInner::Body(v) => match v.body_mode() {
+                BodyMode::LengthDelimited(len) => self.size.or(Some(len)),
+                _ => self.size,
+            },
+            _ => self.size,
+        };
+
         SendBody {
             inner: match &mut self.inner {
                 BodyInner::None => BodyInner::None,
@@ -185,8 +477,45 @@ impl<'a> AsSendBody for SendBody<'a> {
                 BodyInner::Reader(v) => BodyInner::Reader(v),
                 BodyInner::Body(v) => BodyInner::Reader(v),
                 BodyInner::OwnedReader(v) => BodyInner::Reader(v),
+                #[cfg(feature = "charset")]
+                BodyInner::Encoded(v) => BodyInner::Reader(v),
             },
             ended: self.ended,
+            limit: self.limit,
+            read_so_far: self.read_so_far,
+            size,
+            progress: match &mut self.progress {
+                Some(Progress::Owned(f)) => Some(Progress::Borrowed(&mut **f)),
+                Some(Progress::Borrowed(f)) => Some(Progress::Borrowed(&mut **f)),
+                None => None,
+            },
+            #[cfg(feature = "charset")]
+            charset: self.charset,
+        }
+    }
+}
+
+struct ChunksReader<I> {
+    chunks: I,
+    current: io::Cursor<Vec<u8>>,
+}
+
+impl<I> Read for ChunksReader<I>
+where
+    I: Iterator<Item = io::Result<Vec<u8>>>,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let n = self.current.read(buf)?;
+            if n > 0 || buf.is_empty() {
+                return Ok(n);
+            }
+
+            // Skip over empty chunks, since 0 means the end.
+            match self.chunks.next() {
+                Some(chunk) => self.current = io::Cursor::new(chunk?),
+                None => return Ok(0),
+            }
         }
     }
 }
@@ -197,6 +526,8 @@ pub(crate) enum BodyInner<'a> {
     Body(BodyReader<'a>),
     Reader(&'a mut dyn Read),
     OwnedReader(Box<dyn Read>),
+    #[cfg(feature = "charset")]
+    Encoded(Box<CharsetEncoder<'a>>),
 }
 
 impl<'a> BodyInner<'a> {
@@ -207,6 +538,114 @@ impl<'a> BodyInner<'a> {
             BodyInner::Body(v) => v.body_mode(),
             BodyInner::Reader(_) => BodyMode::Chunked,
             BodyInner::OwnedReader(_) => BodyMode::Chunked,
+            #[cfg(feature = "charset")]
+            BodyInner::Encoded(_) => BodyMode::Chunked,
+        }
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            BodyInner::None => Ok(0),
+            BodyInner::ByteSlice(v) => {
+                let max = v.len().min(buf.len());
+
+                buf[..max].copy_from_slice(&v[..max]);
+                *v = &v[max..];
+
+                Ok(max)
+            }
+            BodyInner::Reader(v) => v.read(buf),
+            BodyInner::OwnedReader(v) => v.read(buf),
+            BodyInner::Body(v) => v.read(buf),
+            #[cfg(feature = "charset")]
+            BodyInner::Encoded(v) => v.read(buf),
+        }
+    }
+}
+
+#[cfg(feature = "charset")]
+const LATIN1: &str = "ISO-8859-1";
+
+/// Encodes a utf-8 body to another charset.
+#[cfg(feature = "charset")]
+pub(crate) struct CharsetEncoder<'a> {
+    inner: BodyInner<'a>,
+    encoder: encoding_rs::Encoder,
+    /// Encode as ISO-8859-1 rather than using the (windows-1252) encoder.
+    latin1: bool,
+    /// Input not yet encoded, such as an incomplete utf-8 sequence.
+    input: Vec<u8>,
+    /// Encoded output not yet read.
+    output: io::Cursor<Vec<u8>>,
+    input_ended: bool,
+}
+
+#[cfg(feature = "charset")]
+impl<'a> Read for CharsetEncoder<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        use encoding_rs::EncoderResult;
+
+        loop {
+            let n = self.output.read(buf)?;
+            if n > 0 || buf.is_empty() || self.input_ended {
+                return Ok(n);
+            }
+
+            let mut tmp = [0_u8; 1024];
+            let amount = self.inner.read(&mut tmp)?;
+            self.input.extend_from_slice(&tmp[..amount]);
+            self.input_ended = amount == 0;
+
+            // Encode up until an incomplete utf-8 sequence at the end.
+            let valid = match std::str::from_utf8(&self.input) {
+                Ok(s) => s,
+                Err(e) if e.error_len().is_none() && !self.input_ended => {
+                    // unwrap is ok because valid_up_to() is valid utf-8.
+                    std::str::from_utf8(&self.input[..e.valid_up_to()]).unwrap()
+                }
+                Err(_) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "body to encode is not utf-8",
+                    ))
+                }
+            };
+
+            let output = self.output.get_mut();
+            output.clear();
+
+            if self.latin1 {
+                // ISO-8859-1 is the first 256 code points.
+                if let Some(c) = valid.chars().find(|c| *c > '\u{ff}') {
+                    return Err(Error::UnmappableChar(c, LATIN1).into_io());
+                }
+                output.extend(valid.chars().map(|c| c as u8));
+
+                let used = valid.len();
+                self.input.drain(..used);
+                self.output.set_position(0);
+                continue;
+            }
+
+            let max = self
+                .encoder
+                .max_buffer_length_from_utf8_without_replacement(valid.len())
+                .unwrap_or(valid.len() * 4);
+            output.reserve(max);
+
+            let (result, used) = self.encoder.encode_from_utf8_to_vec_without_replacement(
+                valid,
+                output,
+                self.input_ended,
+            );
+
+            if let EncoderResult::Unmappable(c) = result {
+                let name = self.encoder.encoding().name();
+                return Err(Error::UnmappableChar(c, name).into_io());
+            }
+
+            self.input.drain(..used);
+            self.output.set_position(0);
         }
     }
 }
@@ -249,6 +688,16 @@ impl_into_body!(Stdin, Reader);
 // MSRV 1.78
 // impl_into_body!(&Stdin, Reader);
 
+/// Remaining bytes of a regular file, from the current position to the end.
+fn file_size(mut file: &File) -> Option<u64> {
+    let meta = file.metadata().ok()?;
+    if !meta.is_file() {
+        return None;
+    }
+    let pos = file.stream_position().ok()?;
+    Some(meta.len().saturating_sub(pos))
+}
+
 #[cfg(target_family = "unix")]
 use std::os::unix::net::UnixStream;
 
@@ -260,6 +709,12 @@ impl<'a> From<BodyInner<'a>> for SendBody<'a> {
         SendBody {
             inner,
             ended: false,
+            limit: None,
+            read_so_far: 0,
+            progress: None,
+            size: None,
+            #[cfg(feature = "charset")]
+            charset: None,
         }
     }
 }
@@ -0,0 +1,381 @@
+//! Test helpers for code using ureq.
+//!
+//! Requires the feature flag **testing**.
+//!
+//! The [`MockAgent`] provides an [`Agent`] that never touches the network. Expected
+//! requests are registered up front together with canned responses. Once the code
+//! under test has run, [`MockAgent::assert_done()`] checks that all expectations
+//! were met, and that no unexpected requests were made.
+//!
+//! ```
+//! use ureq::http::Method;
+//! use ureq::testing::MockAgent;
+//!
+//! // The code under test.
+//! fn fetch_user(agent: &ureq::Agent) -> Result<String, ureq::Error> {
+//!     agent
+//!         .get("https://api.example.test/users/1")
+//!         .call()?
+//!         .body_mut()
+//!         .read_to_string()
+//! }
+//!
+//! let mock = MockAgent::new();
+//!
+//! mock.expect(Method::GET, "https://api.example.test/users/1")
+//!     .header("content-type", "application/json")
+//!     .respond(200, r#"{"id":1}"#);
+//!
+//! let body = fetch_user(&mock.agent()).unwrap();
+//! assert_eq!(body, r#"{"id":1}"#);
+//!
+//! mock.assert_done();
+//! ```
+
+use std::fmt;
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+use std::sync::{Arc, Mutex};
+
+use http::{Method, Request, Uri};
+
+use crate::config::Config;
+use crate::http;
+use crate::resolver::{ResolvedSocketAddrs, Resolver};
+use crate::transport::{Buffers, ConnectionDetails, Connector, LazyBuffers};
+use crate::transport::{NextTimeout, Transport};
+use crate::{Agent, Error};
+
+/// Agent for testing code using ureq without network access.
+///
+/// See [module documentation](self).
+pub struct MockAgent {
+    state: Arc<Mutex<MockState>>,
+    agent: Agent,
+}
+
+#[derive(Default)]
+struct MockState {
+    expectations: Vec<Expectation>,
+    unexpected: Vec<String>,
+}
+
+struct Expectation {
+    method: Method,
+    uri: Uri,
+    response: Arc<Vec<u8>>,
+    matched: bool,
+}
+
+/// Builder of a canned response for an expected request.
+///
+/// Created by [`MockAgent::expect()`]. The expectation is registered by
+/// calling [`MockExpect::respond()`].
+pub struct MockExpect<'a> {
+    mock: &'a MockAgent,
+    method: Method,
+    uri: Uri,
+    headers: Vec<(String, String)>,
+}
+
+impl MockAgent {
+    /// Creates a mock agent with default config.
+    pub fn new() -> Self {
+        Self::with_config(Config::default())
+    }
+
+    /// Creates a mock agent with the given config.
+    pub fn with_config(config: Config) -> Self {
+        let state = Arc::new(Mutex::new(MockState::default()));
+
+        let connector = MockConnector {
+            state: state.clone(),
+        };
+
+        let agent = Agent::with_parts(config, connector, MockResolver);
+
+        MockAgent { state, agent }
+    }
+
+    /// The agent to pass to the code under test.
+    ///
+    /// All clones of the agent share the same expectations.
+    pub fn agent(&self) -> Agent {
+        self.agent.clone()
+    }
+
+    /// Expect a request with the given method and uri.
+    ///
+    /// The uri is either a full uri, such as `https://example.test/path?q=1`, which must
+    /// match exactly, or just the path and query `/path?q=1`, which matches any host.
+    ///
+    /// Each expectation is matched once, in the order they were registered.
+    pub fn expect(&self, method: Method, uri: &str) -> MockExpect<'_> {
+        MockExpect {
+            mock: self,
+            method,
+            uri: uri.parse().expect("valid uri for MockAgent::expect()"),
+            headers: vec![],
+        }
+    }
+
+    /// Assert all expectations have been met.
+    ///
+    /// Panics if some expected request was never made, or if any unexpected
+    /// request was made.
+    pub fn assert_done(&self) {
+        let state = self.state.lock().unwrap();
+
+        let unmet: Vec<_> = state
+            .expectations
+            .iter()
+            .filter(|e| !e.matched)
+            .map(|e| format!("{} {}", e.method, e.uri))
+            .collect();
+
+        if !unmet.is_empty() || !state.unexpected.is_empty() {
+            panic!(
+                "MockAgent expectations not met. Unmet: {:?}, unexpected: {:?}",
+                unmet, state.unexpected
+            );
+        }
+    }
+}
+
+impl Default for MockAgent {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> MockExpect<'a> {
+    /// Add a header to the response.
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Register the expectation, responding with the status and body.
+    pub fn respond(self, status: u16, body: impl AsRef<[u8]>) {
+        let body = body.as_ref();
+
+        let reason = http::StatusCode::from_u16(status)
+            .ok()
+            .and_then(|s| s.canonical_reason())
+            .unwrap_or("Unknown");
+
+        let mut response = format!("HTTP/1.1 {} {}\r\n", status, reason);
+        for (name, value) in &self.headers {
+            response.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        response.push_str(&format!("content-length: {}\r\n", body.len()));
+        // The mock transport only serves a single request.
+        response.push_str("connection: close\r\n\r\n");
+
+        let mut response = response.into_bytes();
+        response.extend_from_slice(body);
+
+        let mut state = self.mock.state.lock().unwrap();
+        state.expectations.push(Expectation {
+            method: self.method,
+            uri: self.uri,
+            response: Arc::new(response),
+            matched: false,
+        });
+    }
+}
+
+impl Expectation {
+    fn matches(&self, req: &Request<()>) -> bool {
+        if self.matched || self.method != req.method() {
+            return false;
+        }
+
+        if self.uri.scheme().is_some() {
+            self.uri == *req.uri()
+        } else {
+            self.uri.path_and_query() == req.uri().path_and_query()
+        }
+    }
+}
+
+#[derive(Debug)]
+struct MockResolver;
+
+impl Resolver for MockResolver {
+    fn resolve(
+        &self,
+        _uri: &Uri,
+        _config: &Config,
+        _timeout: NextTimeout,
+    ) -> Result<ResolvedSocketAddrs, Error> {
+        let addr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 1), 80));
+        let mut v = ResolvedSocketAddrs::from_fn(|_| addr);
+        v.push(addr);
+        Ok(v)
+    }
+}
+
+struct MockConnector {
+    state: Arc<Mutex<MockState>>,
+}
+
+impl Connector for MockConnector {
+    fn connect(
+        &self,
+        details: &ConnectionDetails,
+        _chained: Option<Box<dyn Transport>>,
+    ) -> Result<Option<Box<dyn Transport>>, Error> {
+        let config = details.config;
+
+        let transport = MockTransport {
+            buffers: LazyBuffers::new(config.input_buffer_size(), config.output_buffer_size()),
+            uri: details.uri.clone(),
+            state: self.state.clone(),
+            request: vec![],
+            response: None,
+            response_pos: 0,
+        };
+
+        Ok(Some(Box::new(transport)))
+    }
+}
+
+struct MockTransport {
+    buffers: LazyBuffers,
+    uri: Uri,
+    state: Arc<Mutex<MockState>>,
+    request: Vec<u8>,
+    response: Option<Arc<Vec<u8>>>,
+    response_pos: usize,
+}
+
+impl MockTransport {
+    fn try_handle_request(&mut self) -> Result<(), Error> {
+        if self.response.is_some() {
+            // Any further output is the request body, which we ignore.
+            return Ok(());
+        }
+
+        let Some((_, req)) = ureq_proto::parser::try_parse_request::<100>(&self.request)? else {
+            return Ok(());
+        };
+
+        // The parsed request only has the path, put back the full uri.
+        let (mut parts, _) = req.into_parts();
+        let mut uri = self.uri.clone().into_parts();
+        uri.path_and_query = parts.uri.path_and_query().cloned();
+        parts.uri = Uri::from_parts(uri).expect("valid uri");
+        let req = Request::from_parts(parts, ());
+
+        let mut state = self.state.lock().unwrap();
+
+        let Some(expectation) = state.expectations.iter_mut().find(|e| e.matches(&req)) else {
+            let desc = format!("{} {}", req.method(), req.uri());
+            state.unexpected.push(desc.clone());
+            return Err(Error::Io(io::Error::new(
+                io::ErrorKind::Other,
+                format!("MockAgent got unexpected request: {}", desc),
+            )));
+        };
+
+        expectation.matched = true;
+        self.response = Some(expectation.response.clone());
+
+        Ok(())
+    }
+}
+
+impl Transport for MockTransport {
+    fn buffers(&mut self) -> &mut dyn Buffers {
+        &mut self.buffers
+    }
+
+    fn transmit_output(&mut self, amount: usize, _timeout: NextTimeout) -> Result<(), Error> {
+        let output = &self.buffers.output()[..amount];
+        self.request.extend_from_slice(output);
+        self.try_handle_request()
+    }
+
+    fn await_input(&mut self, _timeout: NextTimeout) -> Result<bool, Error> {
+        let Some(response) = &self.response else {
+            return Ok(false);
+        };
+
+        let left = &response[self.response_pos..];
+        let input = self.buffers.input_append_buf();
+        let max = input.len().min(left.len());
+        input[..max].copy_from_slice(&left[..max]);
+        self.buffers.input_appended(max);
+        self.response_pos += max;
+
+        Ok(max > 0)
+    }
+
+    fn is_open(&mut self) -> bool {
+        // Each connection serves a single request.
+        false
+    }
+}
+
+impl fmt::Debug for MockAgent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MockAgent").finish()
+    }
+}
+
+impl fmt::Debug for MockConnector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MockConnector").finish()
+    }
+}
+
+impl fmt::Debug for MockTransport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MockTransport").finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn match_path_only() {
+        let mock = MockAgent::new();
+        mock.expect(Method::POST, "/submit").respond(201, "created");
+
+        let mut res = mock
+            .agent()
+            .post("http://any.host.test/submit")
+            .send("data")
+            .unwrap();
+
+        assert_eq!(res.status(), 201);
+        assert_eq!(res.body_mut().read_to_string().unwrap(), "created");
+        mock.assert_done();
+    }
+
+    #[test]
+    #[should_panic(expected = "Unmet")]
+    fn unmet_expectation() {
+        let mock = MockAgent::new();
+        mock.expect(Method::GET, "https://example.test/never")
+            .respond(200, "");
+        mock.assert_done();
+    }
+
+    #[test]
+    fn unexpected_request() {
+        let mock = MockAgent::new();
+        let err = mock
+            .agent()
+            .get("https://example.test/surprise")
+            .call()
+            .unwrap_err();
+        assert!(err.to_string().contains("unexpected request"));
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| mock.assert_done()));
+        assert!(result.is_err());
+    }
+}
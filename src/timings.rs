@@ -2,7 +2,7 @@ use std::fmt;
 use std::sync::Arc;
 use ureq_proto::ArrayVec;
 
-use crate::config::Timeouts;
+use crate::config::{Observer, Timeouts};
 use crate::transport::time::{Duration, Instant};
 
 /// The various timeouts.
@@ -24,6 +24,9 @@ pub enum Timeout {
     /// Timeout while opening the connection.
     Connect,
 
+    /// Timeout for the TLS handshake of a new connection.
+    TlsHandshake,
+
     /// Timeout while sending the request headers.
     SendRequest,
 
@@ -48,7 +51,8 @@ impl Timeout {
         let prev: &[Timeout] = match self {
             Timeout::Resolve => &[Timeout::PerCall],
             Timeout::Connect => &[Timeout::Resolve],
-            Timeout::SendRequest => &[Timeout::Connect],
+            Timeout::TlsHandshake => &[Timeout::Connect],
+            Timeout::SendRequest => &[Timeout::TlsHandshake],
             Timeout::Await100 => &[Timeout::SendRequest],
             Timeout::SendBody => &[Timeout::SendRequest, Timeout::Await100],
             Timeout::RecvResponse => &[Timeout::SendRequest, Timeout::SendBody],
@@ -72,6 +76,7 @@ impl Timeout {
             Timeout::PerCall => timeouts.per_call,
             Timeout::Resolve => timeouts.resolve,
             Timeout::Connect => timeouts.connect,
+            Timeout::TlsHandshake => timeouts.tls_handshake,
             Timeout::SendRequest => timeouts.send_request,
             Timeout::Await100 => timeouts.await_100,
             Timeout::SendBody => timeouts.send_body,
@@ -86,7 +91,9 @@ impl Timeout {
 pub(crate) struct CallTimings {
     timeouts: Timeouts,
     current_time: CurrentTime,
-    times: ArrayVec<(Timeout, Instant), 8>,
+    times: ArrayVec<(Timeout, Instant), 10>,
+    tls_handshake: Option<std::time::Duration>,
+    observer: MaybeObserver,
 }
 
 impl Default for CallTimings {
@@ -95,38 +102,56 @@ impl Default for CallTimings {
             timeouts: Default::default(),
             current_time: Default::default(),
             times: empty_times(),
+            tls_handshake: None,
+            observer: MaybeObserver(None),
         }
     }
 }
 
-fn empty_times() -> ArrayVec<(Timeout, Instant), 8> {
+fn empty_times() -> ArrayVec<(Timeout, Instant), 10> {
     ArrayVec::from_fn(|_| (Timeout::Global, Instant::AlreadyHappened))
 }
 
 impl CallTimings {
-    pub(crate) fn new(timeouts: Timeouts, current_time: CurrentTime) -> Self {
+    pub(crate) fn new(
+        timeouts: Timeouts,
+        current_time: CurrentTime,
+        observer: Option<Observer>,
+    ) -> Self {
         let mut times = empty_times();
 
         let now = current_time.now();
         times.push((Timeout::Global, now));
         times.push((Timeout::PerCall, now));
 
-        CallTimings {
+        let timings = CallTimings {
             timeouts,
             current_time,
             times,
-        }
+            tls_handshake: None,
+            observer: MaybeObserver(observer),
+        };
+
+        timings.notify(LifecycleStage::CallStart);
+
+        timings
     }
 
     pub(crate) fn new_call(mut self) -> CallTimings {
         self.times.truncate(1); // Global is in position 0.
         self.times.push((Timeout::PerCall, self.current_time.now()));
 
-        CallTimings {
+        let timings = CallTimings {
             timeouts: self.timeouts,
             current_time: self.current_time,
             times: self.times,
-        }
+            tls_handshake: None,
+            observer: self.observer,
+        };
+
+        timings.notify(LifecycleStage::CallStart);
+
+        timings
     }
 
     pub(crate) fn now(&self) -> Instant {
@@ -152,6 +177,57 @@ impl CallTimings {
 
         // Record the time
         self.times.push((timeout, self.current_time.now()));
+
+        if let Some(stage) = LifecycleStage::from_timeout(timeout) {
+            self.notify(stage);
+        }
+    }
+
+    /// Record the TLS handshake of a newly opened connection.
+    pub(crate) fn record_tls_handshake(&mut self, duration: std::time::Duration) {
+        self.tls_handshake = Some(duration);
+        self.notify(LifecycleStage::TlsHandshake);
+    }
+
+    fn notify(&self, stage: LifecycleStage) {
+        let Some(observer) = &self.observer.0 else {
+            return;
+        };
+
+        let elapsed = match self.time_of(Timeout::PerCall) {
+            Some(start) => *self.now().duration_since(start),
+            None => std::time::Duration::ZERO,
+        };
+
+        observer(LifecycleEvent { stage, elapsed });
+    }
+
+    /// Breakdown of the times recorded so far in this call.
+    pub(crate) fn timing_info(&self) -> TimingInfo {
+        let span = |start: Option<Instant>, end: Option<Instant>| match (start, end) {
+            (Some(start), Some(end)) => *end.duration_since(start),
+            _ => std::time::Duration::ZERO,
+        };
+
+        // The request is sent once the last of these is recorded.
+        let sent = [Timeout::SendRequest, Timeout::Await100, Timeout::SendBody]
+            .iter()
+            .filter_map(|t| self.time_of(*t))
+            .max();
+
+        TimingInfo {
+            resolve: span(
+                self.time_of(Timeout::PerCall),
+                self.time_of(Timeout::Resolve),
+            ),
+            connect: span(
+                self.time_of(Timeout::Resolve),
+                self.time_of(Timeout::Connect),
+            ),
+            tls_handshake: self.tls_handshake,
+            send: span(self.time_of(Timeout::TlsHandshake), sent),
+            time_to_first_byte: span(sent, self.time_of(Timeout::RecvResponse)),
+        }
     }
 
     fn time_of(&self, timeout: Timeout) -> Option<Instant> {
@@ -159,23 +235,134 @@ impl CallTimings {
     }
 
     pub(crate) fn next_timeout(&self, timeout: Timeout) -> NextTimeout {
+        let now = self.now();
+
+        // The idle timeout restarts every time we wait for more of the body.
+        let idle = match timeout {
+            Timeout::RecvBody => self
+                .timeouts
+                .recv_body_idle
+                .map(|v| (Timeout::RecvBody, now + v.into())),
+            _ => None,
+        };
+
         let (reason, at) = timeout
             .timeouts_to_check()
             .filter_map(|to_check| {
                 let time = self.time_of(to_check)?;
-                let timeout = to_check.configured_timeout(&self.timeouts)?;
-                Some((to_check, time + timeout))
+                // A preceeding time is when the timeout starts counting, while
+                // Global and PerCall count from their own time.
+                let reason = match to_check {
+                    Timeout::Global | Timeout::PerCall => to_check,
+                    _ => timeout,
+                };
+                let after = reason.configured_timeout(&self.timeouts)?;
+                Some((reason, time + after))
             })
+            .chain(idle)
             .min_by(|a, b| a.1.cmp(&b.1))
             .unwrap_or((Timeout::Global, Instant::NotHappening));
 
-        let now = self.now();
         let after = at.duration_since(now);
 
         NextTimeout { after, reason }
     }
 }
 
+/// Breakdown of the time spent in a call.
+///
+/// Attached to every response and read using
+/// [`ResponseExt::timings()`](crate::ResponseExt::timings). When following redirects,
+/// the timings are for the last call only.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct TimingInfo {
+    /// Time to resolve the host name.
+    pub resolve: std::time::Duration,
+
+    /// Time to open the connection, or to check out a pooled connection.
+    pub connect: std::time::Duration,
+
+    /// Time for the TLS handshake.
+    ///
+    /// Only set for a newly opened TLS connection.
+    pub tls_handshake: Option<std::time::Duration>,
+
+    /// Time to send the request headers and body.
+    pub send: std::time::Duration,
+
+    /// Time from having sent the request until the response headers were received.
+    pub time_to_first_byte: std::time::Duration,
+}
+
+/// A stage in the lifecycle of a call.
+///
+/// See [`ConfigBuilder::observer()`](crate::config::ConfigBuilder::observer).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum LifecycleStage {
+    /// The call is starting. When following redirects, each call starts anew.
+    CallStart,
+
+    /// The host name is resolved.
+    Resolved,
+
+    /// The connection is opened, or checked out from the pool.
+    Connected,
+
+    /// The TLS handshake of a newly opened connection is done.
+    TlsHandshake,
+
+    /// The request headers are sent.
+    RequestSent,
+
+    /// The request body is sent.
+    BodySent,
+
+    /// The response headers are received.
+    ResponseReceived,
+
+    /// The response body is fully read.
+    BodyReceived,
+}
+
+impl LifecycleStage {
+    fn from_timeout(timeout: Timeout) -> Option<LifecycleStage> {
+        let stage = match timeout {
+            Timeout::Resolve => LifecycleStage::Resolved,
+            Timeout::Connect => LifecycleStage::Connected,
+            Timeout::SendRequest => LifecycleStage::RequestSent,
+            Timeout::SendBody => LifecycleStage::BodySent,
+            Timeout::RecvResponse => LifecycleStage::ResponseReceived,
+            Timeout::RecvBody => LifecycleStage::BodyReceived,
+            _ => return None,
+        };
+        Some(stage)
+    }
+}
+
+/// Event passed to the [`ConfigBuilder::observer()`](crate::config::ConfigBuilder::observer).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct LifecycleEvent {
+    /// The stage that was reached.
+    pub stage: LifecycleStage,
+
+    /// Time since the start of the call.
+    pub elapsed: std::time::Duration,
+}
+
+#[derive(Clone)]
+struct MaybeObserver(Option<Observer>);
+
+impl fmt::Debug for MaybeObserver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("MaybeObserver")
+            .field(&self.0.is_some())
+            .finish()
+    }
+}
+
 #[derive(Clone)]
 pub(crate) struct CurrentTime(Arc<dyn Fn() -> Instant + Send + Sync + 'static>);
 
@@ -225,6 +412,7 @@ impl fmt::Display for Timeout {
             Timeout::PerCall => "per call",
             Timeout::Resolve => "resolve",
             Timeout::Connect => "connect",
+            Timeout::TlsHandshake => "TLS handshake",
             Timeout::SendRequest => "send request",
             Timeout::SendBody => "send body",
             Timeout::Await100 => "await 100",
@@ -234,3 +422,71 @@ impl fmt::Display for Timeout {
         write!(f, "{}", r)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::sync::Mutex;
+    use std::time;
+
+    use super::*;
+
+    #[test]
+    fn global_timeout_spans_redirects() {
+        let start = time::Instant::now();
+        let elapsed = Arc::new(Mutex::new(time::Duration::ZERO));
+        let elapsed2 = elapsed.clone();
+        let current_time = CurrentTime(Arc::new(move || {
+            Instant::Exact(start + *elapsed2.lock().unwrap())
+        }));
+
+        let timeouts = Timeouts {
+            global: Some(time::Duration::from_secs(10)),
+            per_call: Some(time::Duration::from_secs(5)),
+            ..Default::default()
+        };
+
+        let timings = CallTimings::new(timeouts, current_time, None);
+
+        // Redirect after 6 seconds.
+        *elapsed.lock().unwrap() = time::Duration::from_secs(6);
+        let timings = timings.new_call();
+
+        // The per call timeout restarted, the global did not.
+        *elapsed.lock().unwrap() = time::Duration::from_secs(8);
+        let next = timings.next_timeout(Timeout::Global);
+        assert_eq!(next.reason, Timeout::Global);
+        assert_eq!(next.after, Duration::from_secs(2));
+    }
+
+    #[test]
+    fn phase_timeout_uses_own_setting() {
+        let start = time::Instant::now();
+        let current_time = CurrentTime(Arc::new(move || Instant::Exact(start)));
+
+        let timeouts = Timeouts {
+            resolve: Some(time::Duration::from_secs(5)),
+            connect: Some(time::Duration::from_secs(1)),
+            recv_response: Some(time::Duration::from_secs(10)),
+            recv_body: Some(time::Duration::from_secs(3)),
+            ..Default::default()
+        };
+
+        let mut timings = CallTimings::new(timeouts, current_time, None);
+
+        // The connect timeout counts from when the resolve finished, but is the
+        // connect setting, not the resolve one.
+        timings.record_time(Timeout::Resolve);
+        let next = timings.next_timeout(Timeout::Connect);
+        assert_eq!(next.reason, Timeout::Connect);
+        assert_eq!(next.after, Duration::from_secs(1));
+
+        timings.record_time(Timeout::Connect);
+        timings.record_time(Timeout::TlsHandshake);
+        timings.record_time(Timeout::SendRequest);
+        timings.record_time(Timeout::RecvResponse);
+
+        let next = timings.next_timeout(Timeout::RecvBody);
+        assert_eq!(next.reason, Timeout::RecvBody);
+        assert_eq!(next.after, Duration::from_secs(3));
+    }
+}
@@ -1,10 +1,12 @@
 //! TLS for handling `https`.
 
 use std::fmt;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+
+use crate::Error;
 
 mod cert;
-pub use cert::{parse_pem, Certificate, PemItem, PrivateKey};
+pub use cert::{parse_pem, Certificate, KeyKind, PemItem, PrivateKey};
 
 #[cfg(feature = "rustls")]
 pub(crate) mod rustls;
@@ -69,6 +71,8 @@ pub struct TlsConfig {
     client_cert: Option<ClientCert>,
     root_certs: RootCerts,
     use_sni: bool,
+    sni_name: Option<Arc<String>>,
+    spki_pins: Option<Arc<Vec<String>>>,
     disable_verification: bool,
 }
 
@@ -113,6 +117,34 @@ impl TlsConfig {
         self.use_sni
     }
 
+    /// Server name to use for the TLS handshake instead of the host in the uri.
+    ///
+    /// This name is sent as SNI (unless [`use_sni`](Self::use_sni) is `false`), and
+    /// the server certificate is verified against it. Useful when connecting to a
+    /// server by IP address that has a certificate for a host name.
+    ///
+    /// Defaults to `None`.
+    pub fn sni_name(&self) -> Option<&str> {
+        self.sni_name.as_deref().map(|s| s.as_str())
+    }
+
+    /// Pinned public keys of the server certificate.
+    ///
+    /// Each pin is a base64 encoded SHA-256 hash of the leaf certificate's
+    /// SubjectPublicKeyInfo. When set, the server certificate must match one of the
+    /// pins in addition to passing normal verification.
+    ///
+    /// Only supported by **rustls** with feature flag **spki-pins**. Otherwise,
+    /// connecting fails with [`Error::Tls`](crate::Error::Tls) when pins are set.
+    ///
+    /// Defaults to empty.
+    pub fn spki_pins(&self) -> &[String] {
+        self.spki_pins
+            .as_deref()
+            .map(|v| v.as_slice())
+            .unwrap_or(&[])
+    }
+
     /// **WARNING** Disable all server certificate verification.
     ///
     /// This breaks encryption and leaks secrets. Must never be enabled for code where
@@ -120,6 +152,29 @@ impl TlsConfig {
     pub fn disable_verification(&self) -> bool {
         self.disable_verification
     }
+
+    /// The SNI name override, shared to cheaply put it in a pool key.
+    pub(crate) fn sni_name_shared(&self) -> Option<Arc<String>> {
+        self.sni_name.clone()
+    }
+
+    /// Whether a TLS setup built from `other` can be used for this config.
+    pub(crate) fn is_same(&self, other: &TlsConfig) -> bool {
+        let same_roots = match (&self.root_certs, &other.root_certs) {
+            (RootCerts::Specific(a), RootCerts::Specific(b)) => Arc::ptr_eq(a, b),
+            (RootCerts::PlatformVerifier, RootCerts::PlatformVerifier) => true,
+            (RootCerts::WebPki, RootCerts::WebPki) => true,
+            _ => false,
+        };
+
+        same_roots
+            && self.provider == other.provider
+            && self.client_cert == other.client_cert
+            && self.use_sni == other.use_sni
+            && self.sni_name == other.sni_name
+            && self.spki_pins == other.spki_pins
+            && self.disable_verification == other.disable_verification
+    }
 }
 
 /// Builder of [`TlsConfig`]
@@ -163,6 +218,53 @@ impl TlsConfigBuilder {
         self
     }
 
+    /// Server name to use for the TLS handshake instead of the host in the uri.
+    ///
+    /// This name is sent as SNI (unless [`use_sni`](Self::use_sni) is `false`), and
+    /// the server certificate is verified against it. Useful when connecting to a
+    /// server by IP address that has a certificate for a host name.
+    ///
+    /// ```
+    /// use ureq::tls::TlsConfig;
+    ///
+    /// let tls_config = TlsConfig::builder()
+    ///     .sni_name(Some("internal.example.test".to_string()))
+    ///     .build();
+    /// ```
+    ///
+    /// Defaults to `None`.
+    pub fn sni_name(mut self, v: Option<String>) -> Self {
+        self.config.sni_name = v.map(Arc::new);
+        self
+    }
+
+    /// Pinned public keys of the server certificate.
+    ///
+    /// Each pin is a base64 encoded SHA-256 hash of the leaf certificate's
+    /// SubjectPublicKeyInfo. When set, the server certificate must match one of the
+    /// pins in addition to passing normal verification.
+    ///
+    /// A pin can be obtained from a certificate using openssl:
+    ///
+    /// ```text
+    /// openssl x509 -in cert.pem -pubkey -noout \
+    ///   | openssl pkey -pubin -outform der \
+    ///   | openssl dgst -sha256 -binary | base64
+    /// ```
+    ///
+    /// Only supported by **rustls**. With **native-tls**, connecting fails with
+    /// [`Error::Tls`](crate::Error::Tls) when pins are set.
+    ///
+    /// Defaults to empty.
+    pub fn spki_pins(mut self, v: Vec<String>) -> Self {
+        self.config.spki_pins = if v.is_empty() {
+            None
+        } else {
+            Some(Arc::new(v))
+        };
+        self
+    }
+
     /// **WARNING** Disable all server certificate verification.
     ///
     /// This breaks encryption and leaks secrets. Must never be enabled for code where
@@ -199,6 +301,27 @@ impl ClientCert {
     }
 }
 
+impl PartialEq for ClientCert {
+    fn eq(&self, other: &Self) -> bool {
+        if Arc::ptr_eq(&self.0, &other.0) {
+            return true;
+        }
+
+        let (certs, key) = &*self.0;
+        let (other_certs, other_key) = &*other.0;
+
+        certs.len() == other_certs.len()
+            && certs
+                .iter()
+                .zip(other_certs)
+                .all(|(a, b)| a.der() == b.der())
+            && key.kind() == other_key.kind()
+            && key.der() == other_key.der()
+    }
+}
+
+impl Eq for ClientCert {}
+
 /// Configuration setting for root certs.
 #[derive(Debug, Clone)]
 #[non_exhaustive]
@@ -222,6 +345,45 @@ pub enum RootCerts {
     WebPki,
 }
 
+/// Max number of TLS setups a connector keeps, the agent's plus request level ones.
+const MAX_CACHED: usize = 8;
+
+/// TLS setups (rustls configs, native-tls connectors) built per [`TlsConfig`].
+///
+/// Building one is costly, and request level configs would otherwise be built
+/// again for every connection.
+pub(crate) struct TlsCache<T>(Mutex<Vec<(TlsConfig, T)>>);
+
+impl<T: Clone> TlsCache<T> {
+    pub(crate) fn get_or_build(
+        &self,
+        tls_config: &TlsConfig,
+        build: impl FnOnce(&TlsConfig) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        let mut cache = self.0.lock().unwrap();
+
+        if let Some((_, built)) = cache.iter().find(|(c, _)| c.is_same(tls_config)) {
+            return Ok(built.clone());
+        }
+
+        let built = build(tls_config)?;
+
+        if cache.len() >= MAX_CACHED {
+            // The first is the agent's config, drop the oldest request level one.
+            cache.remove(1);
+        }
+        cache.push((tls_config.clone(), built.clone()));
+
+        Ok(built)
+    }
+}
+
+impl<T> Default for TlsCache<T> {
+    fn default() -> Self {
+        TlsCache(Mutex::new(Vec::new()))
+    }
+}
+
 impl RootCerts {
     /// Use these specific root certificates
     pub fn new_with_certs(certs: &[Certificate<'static>]) -> Self {
@@ -243,6 +405,8 @@ impl Default for TlsConfig {
             client_cert: None,
             root_certs: RootCerts::WebPki,
             use_sni: true,
+            sni_name: None,
+            spki_pins: None,
             disable_verification: false,
         }
     }
@@ -261,6 +425,8 @@ impl fmt::Debug for TlsConfig {
             .field("client_cert", &self.client_cert)
             .field("root_certs", &self.root_certs)
             .field("use_sni", &self.use_sni)
+            .field("sni_name", &self.sni_name)
+            .field("spki_pins", &self.spki_pins)
             .field("disable_verification", &self.disable_verification)
             .finish()
     }
@@ -275,5 +441,35 @@ mod test {
     fn tls_config_clone_does_not_allocate() {
         let c = TlsConfig::default();
         assert_no_alloc(|| c.clone());
+
+        let c = TlsConfig::builder()
+            .sni_name(Some("example.test".to_string()))
+            .spki_pins(vec![
+                "U8fWW+rYLiGwEu798F4xNAhGllq0rrhHCSanSRRFsJ4=".to_string()
+            ])
+            .build();
+        assert_no_alloc(|| c.clone());
+    }
+
+    #[test]
+    fn tls_cache_builds_once_per_config() {
+        let cache = TlsCache::default();
+        let mut built = 0;
+
+        let agent = TlsConfig::default();
+        let request = TlsConfig::builder()
+            .sni_name(Some("example.test".to_string()))
+            .build();
+
+        for config in [&agent, &request, &agent, &request] {
+            cache
+                .get_or_build(config, |_| {
+                    built += 1;
+                    Ok(())
+                })
+                .unwrap();
+        }
+
+        assert_eq!(built, 2);
     }
 }
@@ -1,15 +1,17 @@
 use std::convert::TryFrom;
 use std::fmt;
 use std::io::{Read, Write};
+use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use crate::tls::{RootCerts, TlsProvider};
+use crate::config::ProbeKind;
+use crate::tls::{RootCerts, TlsCache, TlsProvider};
 use crate::{transport::*, Error};
 use der::pem::LineEnding;
 use der::Document;
 use native_tls::{Certificate, HandshakeError, Identity, TlsConnector};
 use native_tls::{TlsConnectorBuilder, TlsStream};
-use once_cell::sync::OnceCell;
 
 use super::TlsConfig;
 
@@ -18,7 +20,7 @@ use super::TlsConfig;
 /// Requires feature flag **native-tls**.
 #[derive(Default)]
 pub struct NativeTlsConnector {
-    connector: OnceCell<Arc<TlsConnector>>,
+    connectors: TlsCache<Arc<TlsConnector>>,
 }
 
 impl Connector for NativeTlsConnector {
@@ -47,25 +49,16 @@ impl Connector for NativeTlsConnector {
 
         let tls_config = &details.config.tls_config();
 
-        // Initialize the connector on first run.
-        let connector_ref = match self.connector.get() {
-            Some(v) => v,
-            None => {
-                // This is unlikely to be racy, but if it is, doesn't matter much.
-                let c = build_connector(tls_config)?;
-                // Maybe someone else set it first. Weird, but ok.
-                let _ = self.connector.set(c);
-                self.connector.get().unwrap()
-            }
-        };
-        let connector = connector_ref.clone(); // cheap clone due to Arc
+        // Cheap clone due to Arc, built on first use of each TlsConfig.
+        let connector = self.connectors.get_or_build(tls_config, build_connector)?;
 
-        let domain = details
+        let host = details
             .uri
             .authority()
             .expect("uri authority for tls")
-            .host()
-            .to_string();
+            .host();
+
+        let domain = tls_config.sni_name().unwrap_or(host).to_string();
 
         let adapter = TransportAdapter::new(transport);
         let stream = LazyStream::Unstarted(Some((connector, domain, adapter)));
@@ -84,6 +77,11 @@ impl Connector for NativeTlsConnector {
 }
 
 fn build_connector(tls_config: &TlsConfig) -> Result<Arc<TlsConnector>, Error> {
+    if !tls_config.spki_pins().is_empty() {
+        // Connecting without checking the pins would silently fail open.
+        return Err(Error::Tls("spki_pins not supported by native-tls"));
+    }
+
     let mut builder = TlsConnector::builder();
 
     if tls_config.disable_verification {
@@ -178,6 +176,7 @@ impl Transport for NativeTlsTransport {
     }
 
     fn transmit_output(&mut self, amount: usize, timeout: NextTimeout) -> Result<(), Error> {
+        self.stream.set_handshake_timeout(timeout);
         let stream = self.stream.handshaken()?;
         stream.get_mut().set_timeout(timeout);
 
@@ -192,6 +191,7 @@ impl Transport for NativeTlsTransport {
             return Ok(true);
         }
 
+        self.stream.set_handshake_timeout(timeout);
         let stream = self.stream.handshaken()?;
         stream.get_mut().set_timeout(timeout);
 
@@ -209,32 +209,62 @@ impl Transport for NativeTlsTransport {
             .unwrap_or(false)
     }
 
+    fn probe(&mut self, kind: ProbeKind) -> bool {
+        self.stream
+            .handshaken()
+            .map(|c| c.get_mut().get_mut().probe(kind))
+            .unwrap_or(false)
+    }
+
     fn is_tls(&self) -> bool {
         true
     }
+
+    fn tls_handshake_duration(&self) -> Option<Duration> {
+        match &self.stream {
+            LazyStream::Unstarted(_) => None,
+            LazyStream::Started(_, duration) => Some(*duration),
+        }
+    }
+
+    fn remote_addr(&self) -> Option<SocketAddr> {
+        match &self.stream {
+            LazyStream::Unstarted(Some((_, _, adapter))) => adapter.get_ref().remote_addr(),
+            LazyStream::Unstarted(None) => None,
+            LazyStream::Started(stream, _) => stream.get_ref().get_ref().remote_addr(),
+        }
+    }
 }
 
 /// Helper to delay the handshake until we are starting IO.
 /// This normalizes native-tls to behave like rustls.
 enum LazyStream {
     Unstarted(Option<(Arc<TlsConnector>, String, TransportAdapter)>),
-    Started(TlsStream<TransportAdapter>),
+    Started(TlsStream<TransportAdapter>, Duration),
 }
 
 impl LazyStream {
+    /// The timeout for the handshake, unless it is already done.
+    fn set_handshake_timeout(&mut self, timeout: NextTimeout) {
+        if let LazyStream::Unstarted(Some((_, _, adapter))) = self {
+            adapter.set_timeout(timeout);
+        }
+    }
+
     fn handshaken(&mut self) -> Result<&mut TlsStream<TransportAdapter>, Error> {
         match self {
             LazyStream::Unstarted(v) => {
                 let (conn, domain, adapter) = v.take().unwrap();
+                let start = Instant::now();
                 let stream = conn.connect(&domain, adapter).map_err(|e| match e {
                     HandshakeError::Failure(e) => e,
                     HandshakeError::WouldBlock(_) => unreachable!(),
                 })?;
-                *self = LazyStream::Started(stream);
+                *self = LazyStream::Started(stream, start.elapsed());
                 // Next time we hit the other match arm
                 return self.handshaken();
             }
-            LazyStream::Started(v) => Ok(v),
+            LazyStream::Started(v, _) => Ok(v),
         }
     }
 }
@@ -249,3 +279,22 @@ impl fmt::Debug for NativeTlsTransport {
         f.debug_struct("NativeTlsTransport").finish()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn spki_pins_not_supported() {
+        let tls_config = TlsConfig::builder()
+            .provider(TlsProvider::NativeTls)
+            .spki_pins(vec!["U8fWW+rYLiGwEu798F4xNAhGllq0rrhHCSanSRRFsJ4=".into()])
+            .build();
+
+        let err = build_connector(&tls_config).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::Tls("spki_pins not supported by native-tls")
+        ));
+    }
+}
@@ -1,16 +1,27 @@
 use std::convert::TryInto;
 use std::fmt;
 use std::io::{Read, Write};
+use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use once_cell::sync::OnceCell;
+#[cfg(feature = "spki-pins")]
+use base64::prelude::BASE64_STANDARD;
+#[cfg(feature = "spki-pins")]
+use base64::Engine;
 use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
-use rustls::{ClientConfig, ClientConnection, RootCertStore, StreamOwned, ALL_VERSIONS};
+use rustls::client::{WantsClientCert, WebPkiServerVerifier};
+use rustls::crypto::CryptoProvider;
+use rustls::{ClientConfig, ClientConnection, ConfigBuilder, RootCertStore, StreamOwned};
+use rustls::{WantsVerifier, ALL_VERSIONS};
 use rustls_pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs1KeyDer, PrivatePkcs8KeyDer};
 use rustls_pki_types::{PrivateSec1KeyDer, ServerName};
+#[cfg(feature = "spki-pins")]
+use sha2::{Digest, Sha256};
 
+use crate::config::ProbeKind;
 use crate::tls::cert::KeyKind;
-use crate::tls::{RootCerts, TlsProvider};
+use crate::tls::{RootCerts, TlsCache, TlsProvider};
 use crate::transport::{Buffers, ConnectionDetails, Connector, LazyBuffers};
 use crate::transport::{NextTimeout, Transport, TransportAdapter};
 use crate::Error;
@@ -22,7 +33,7 @@ use super::TlsConfig;
 /// Requires feature flag **rustls**.
 #[derive(Default)]
 pub struct RustlsConnector {
-    config: OnceCell<Arc<ClientConfig>>,
+    configs: TlsCache<Arc<ClientConfig>>,
 }
 
 impl Connector for RustlsConnector {
@@ -51,15 +62,27 @@ impl Connector for RustlsConnector {
 
         let tls_config = details.config.tls_config();
 
-        // Initialize the config on first run.
-        let config_ref = self.config.get_or_init(|| build_config(tls_config));
-        let config = config_ref.clone(); // cheap clone due to Arc
+        #[cfg(not(feature = "spki-pins"))]
+        if !tls_config.spki_pins().is_empty() {
+            // Connecting without checking the pins would silently fail open.
+            return Err(Error::Tls("spki_pins requires feature: spki-pins"));
+        }
+
+        // Cheap clone due to Arc, built on first use of each TlsConfig.
+        let config = self.configs.get_or_build(tls_config, |c| {
+            debug!("Build rustls config");
+            Ok(build_config(c))
+        })?;
 
-        let name_borrowed: ServerName<'_> = details
+        let host = details
             .uri
             .authority()
             .expect("uri authority for tls")
-            .host()
+            .host();
+
+        let name_borrowed: ServerName<'_> = tls_config
+            .sni_name()
+            .unwrap_or(host)
             .try_into()
             .map_err(|e| {
                 warn!("rustls invalid dns name: {}", e);
@@ -79,7 +102,11 @@ impl Connector for RustlsConnector {
             details.config.output_buffer_size(),
         );
 
-        let transport = Box::new(RustlsTransport { buffers, stream });
+        let transport = Box::new(RustlsTransport {
+            buffers,
+            stream,
+            handshake: None,
+        });
 
         debug!("Wrapped TLS");
 
@@ -100,9 +127,7 @@ fn build_config(tls_config: &TlsConfig) -> Arc<ClientConfig> {
 
     let builder = if tls_config.disable_verification {
         debug!("Certificate verification disabled");
-        builder
-            .dangerous()
-            .with_custom_certificate_verifier(Arc::new(DisabledVerifier))
+        with_verifier(builder, Arc::new(DisabledVerifier), tls_config)
     } else {
         match &tls_config.root_certs {
             RootCerts::Specific(certs) => {
@@ -112,24 +137,24 @@ fn build_config(tls_config: &TlsConfig) -> Arc<ClientConfig> {
                 let (added, ignored) = root_store.add_parsable_certificates(root_certs);
                 debug!("Added {} and ignored {} root certs", added, ignored);
 
-                builder.with_root_certificates(root_store)
+                with_root_store(builder, root_store, provider, tls_config)
             }
             #[cfg(not(feature = "platform-verifier"))]
             RootCerts::PlatformVerifier => {
                 panic!("Rustls + PlatformVerifier requires feature: platform-verifier");
             }
             #[cfg(feature = "platform-verifier")]
-            RootCerts::PlatformVerifier => builder
+            RootCerts::PlatformVerifier => with_verifier(
+                builder,
                 // This actually not dangerous. The rustls_platform_verifier is safe.
-                .dangerous()
-                .with_custom_certificate_verifier(Arc::new(
-                    rustls_platform_verifier::Verifier::new().with_provider(provider),
-                )),
+                Arc::new(rustls_platform_verifier::Verifier::new().with_provider(provider)),
+                tls_config,
+            ),
             RootCerts::WebPki => {
                 let root_store = RootCertStore {
                     roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
                 };
-                builder.with_root_certificates(root_store)
+                with_root_store(builder, root_store, provider, tls_config)
             }
         }
     };
@@ -166,9 +191,70 @@ fn build_config(tls_config: &TlsConfig) -> Arc<ClientConfig> {
     Arc::new(config)
 }
 
+fn with_root_store(
+    builder: ConfigBuilder<ClientConfig, WantsVerifier>,
+    root_store: RootCertStore,
+    provider: Arc<CryptoProvider>,
+    tls_config: &TlsConfig,
+) -> ConfigBuilder<ClientConfig, WantsClientCert> {
+    if cfg!(not(feature = "spki-pins")) || tls_config.spki_pins().is_empty() {
+        return builder.with_root_certificates(root_store);
+    }
+
+    match WebPkiServerVerifier::builder_with_provider(Arc::new(root_store), provider).build() {
+        Ok(verifier) => with_verifier(builder, verifier, tls_config),
+        Err(e) => {
+            // Only fails without root certs, in which case no server is trusted anyway.
+            debug!("Failed to build verifier for pinning: {}", e);
+            builder.with_root_certificates(RootCertStore::empty())
+        }
+    }
+}
+
+fn with_verifier(
+    builder: ConfigBuilder<ClientConfig, WantsVerifier>,
+    verifier: Arc<dyn ServerCertVerifier>,
+    tls_config: &TlsConfig,
+) -> ConfigBuilder<ClientConfig, WantsClientCert> {
+    #[cfg(feature = "spki-pins")]
+    let verifier = if tls_config.spki_pins().is_empty() {
+        verifier
+    } else {
+        Arc::new(PinningVerifier::new(verifier, tls_config.spki_pins()))
+    };
+
+    // Pins are rejected before building the config without the feature.
+    #[cfg(not(feature = "spki-pins"))]
+    let _ = tls_config;
+
+    builder
+        .dangerous()
+        .with_custom_certificate_verifier(verifier)
+}
+
 struct RustlsTransport {
     buffers: LazyBuffers,
     stream: StreamOwned<ClientConnection, TransportAdapter>,
+    handshake: Option<Duration>,
+}
+
+impl RustlsTransport {
+    /// Drive the handshake to completion before the first IO, timing how long it takes.
+    fn complete_handshake(&mut self, timeout: NextTimeout) -> Result<(), Error> {
+        if !self.stream.conn.is_handshaking() {
+            return Ok(());
+        }
+
+        self.stream.get_mut().set_timeout(timeout);
+
+        let start = Instant::now();
+        while self.stream.conn.is_handshaking() {
+            self.stream.conn.complete_io(&mut self.stream.sock)?;
+        }
+        self.handshake = Some(start.elapsed());
+
+        Ok(())
+    }
 }
 
 impl Transport for RustlsTransport {
@@ -177,6 +263,7 @@ impl Transport for RustlsTransport {
     }
 
     fn transmit_output(&mut self, amount: usize, timeout: NextTimeout) -> Result<(), Error> {
+        self.complete_handshake(timeout)?;
         self.stream.get_mut().set_timeout(timeout);
 
         let output = &self.buffers.output()[..amount];
@@ -190,6 +277,7 @@ impl Transport for RustlsTransport {
             return Ok(true);
         }
 
+        self.complete_handshake(timeout)?;
         self.stream.get_mut().set_timeout(timeout);
 
         let input = self.buffers.input_append_buf();
@@ -203,9 +291,21 @@ impl Transport for RustlsTransport {
         self.stream.get_mut().get_mut().is_open()
     }
 
+    fn probe(&mut self, kind: ProbeKind) -> bool {
+        self.stream.get_mut().get_mut().probe(kind)
+    }
+
     fn is_tls(&self) -> bool {
         true
     }
+
+    fn tls_handshake_duration(&self) -> Option<Duration> {
+        self.handshake
+    }
+
+    fn remote_addr(&self) -> Option<SocketAddr> {
+        self.stream.get_ref().get_ref().remote_addr()
+    }
 }
 
 #[derive(Debug)]
@@ -259,6 +359,143 @@ impl ServerCertVerifier for DisabledVerifier {
     }
 }
 
+/// Verifier that checks the leaf certificate against SPKI pins after
+/// the wrapped verifier accepted it.
+#[cfg(feature = "spki-pins")]
+#[derive(Debug)]
+struct PinningVerifier {
+    inner: Arc<dyn ServerCertVerifier>,
+    pins: Vec<[u8; 32]>,
+}
+
+#[cfg(feature = "spki-pins")]
+impl PinningVerifier {
+    fn new(inner: Arc<dyn ServerCertVerifier>, pins: &[String]) -> Self {
+        let pins = pins
+            .iter()
+            .filter_map(|p| {
+                let pin = BASE64_STANDARD
+                    .decode(p)
+                    .ok()
+                    .and_then(|v| v.try_into().ok());
+                if pin.is_none() {
+                    // An invalid pin never matches. We do not drop pinning altogether
+                    // since that would silently accept any server.
+                    warn!("Ignoring invalid SPKI pin: {}", p);
+                }
+                pin
+            })
+            .collect();
+
+        PinningVerifier { inner, pins }
+    }
+}
+
+#[cfg(feature = "spki-pins")]
+impl ServerCertVerifier for PinningVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &rustls_pki_types::ServerName<'_>,
+        ocsp_response: &[u8],
+        now: rustls_pki_types::UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let verified = self.inner.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            ocsp_response,
+            now,
+        )?;
+
+        let Some(spki) = spki_of(end_entity) else {
+            return Err(rustls::Error::InvalidCertificate(
+                rustls::CertificateError::BadEncoding,
+            ));
+        };
+
+        let hash: [u8; 32] = Sha256::digest(spki).into();
+
+        if !self.pins.contains(&hash) {
+            debug!("Server certificate does not match any SPKI pin");
+            return Err(rustls::Error::InvalidCertificate(
+                rustls::CertificateError::ApplicationVerificationFailure,
+            ));
+        }
+
+        Ok(verified)
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &rustls::DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &rustls::DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<rustls::SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+/// The DER encoded SubjectPublicKeyInfo of a certificate.
+#[cfg(feature = "spki-pins")]
+fn spki_of(cert: &[u8]) -> Option<&[u8]> {
+    // Certificate ::= SEQUENCE { tbsCertificate, signatureAlgorithm, signature }
+    let (header, cert, _) = der_next(cert)?;
+    let (header, tbs, _) = der_next(&cert[header..])?;
+
+    // The version is an optional explicit [0] before the serial number.
+    let (_, first, rest) = der_next(&tbs[header..])?;
+    let mut rest = if first[0] == 0xa0 {
+        der_next(rest)?.2
+    } else {
+        rest
+    };
+
+    // Skip signature, issuer, validity and subject.
+    for _ in 0..4 {
+        rest = der_next(rest)?.2;
+    }
+
+    let (_, spki, _) = der_next(rest)?;
+    Some(spki)
+}
+
+/// Split off the next DER element as (header length, element, rest).
+#[cfg(feature = "spki-pins")]
+fn der_next(v: &[u8]) -> Option<(usize, &[u8], &[u8])> {
+    let first = *v.get(1)?;
+
+    let (len, header) = if first < 0x80 {
+        (first as usize, 2)
+    } else {
+        let n = (first & 0x7f) as usize;
+        if n == 0 || n > 4 {
+            return None;
+        }
+        let bytes = v.get(2..2 + n)?;
+        let len = bytes.iter().fold(0, |acc, b| acc << 8 | *b as usize);
+        (len, 2 + n)
+    };
+
+    let element = v.get(..header.checked_add(len)?)?;
+
+    Some((header, element, &v[element.len()..]))
+}
+
 impl fmt::Debug for RustlsConnector {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("RustlsConnector").finish()
@@ -272,3 +509,85 @@ impl fmt::Debug for RustlsTransport {
             .finish()
     }
 }
+
+#[cfg(all(test, feature = "spki-pins"))]
+mod test {
+    use super::*;
+    use crate::tls::Certificate;
+
+    // Self-signed for "pin.test".
+    const CERT: &[u8] = b"-----BEGIN CERTIFICATE-----
+MIIBfDCCASOgAwIBAgIUSFLh5E2o2FgCAaNNHft3j/SAJsAwCgYIKoZIzj0EAwIw
+EzERMA8GA1UEAwwIcGluLnRlc3QwIBcNMjYxMDE2MTE1NzIxWhgPMjEyNjA5MjIx
+MTU3MjFaMBMxETAPBgNVBAMMCHBpbi50ZXN0MFkwEwYHKoZIzj0CAQYIKoZIzj0D
+AQcDQgAEL6Cc8SyUDfOFDLWp5FmtPvx+5uBmyJ7KOWOpjhltiQ99vV2nytjw2Aam
+E4Qj6ahFSZ4K17hc1pmjvZAEY3dyh6NTMFEwHQYDVR0OBBYEFCFZ9/86XJi+3JT1
+Pxrv7MoNUokOMB8GA1UdIwQYMBaAFCFZ9/86XJi+3JT1Pxrv7MoNUokOMA8GA1Ud
+EwEB/wQFMAMBAf8wCgYIKoZIzj0EAwIDRwAwRAIgFggZonmfEfVbBMwz5bw7S48s
+eyQ4l3PBpC5vGHsB0W4CIGh1YhRFLvxbeeGT9YDNMIAi3ERBlWGCYZPUBf2+bshq
+-----END CERTIFICATE-----";
+
+    // openssl x509 -pubkey -noout | openssl pkey -pubin -outform der | openssl dgst -sha256
+    const PIN: &str = "U8fWW+rYLiGwEu798F4xNAhGllq0rrhHCSanSRRFsJ4=";
+
+    fn verify(inner: Arc<dyn ServerCertVerifier>, pin: &str) -> Result<(), rustls::Error> {
+        let cert = Certificate::from_pem(CERT).unwrap();
+        let verifier = PinningVerifier::new(inner, &[pin.to_string()]);
+        verifier
+            .verify_server_cert(
+                &CertificateDer::from(cert.der()),
+                &[],
+                &"pin.test".try_into().unwrap(),
+                &[],
+                rustls_pki_types::UnixTime::now(),
+            )
+            .map(|_| ())
+    }
+
+    #[test]
+    fn spki_pin_of_known_cert() {
+        let cert = Certificate::from_pem(CERT).unwrap();
+        let spki = spki_of(cert.der()).unwrap();
+        assert_eq!(BASE64_STANDARD.encode(Sha256::digest(spki)), PIN);
+    }
+
+    #[test]
+    fn spki_pin_match_and_mismatch() {
+        assert!(verify(Arc::new(DisabledVerifier), PIN).is_ok());
+
+        let err = verify(
+            Arc::new(DisabledVerifier),
+            "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=",
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            rustls::Error::InvalidCertificate(
+                rustls::CertificateError::ApplicationVerificationFailure
+            )
+        );
+
+        // An invalid pin never matches.
+        assert!(verify(Arc::new(DisabledVerifier), "not base64").is_err());
+    }
+
+    #[test]
+    fn spki_pin_does_not_replace_verification() {
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let root_store = RootCertStore {
+            roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+        };
+        let webpki = WebPkiServerVerifier::builder_with_provider(Arc::new(root_store), provider)
+            .build()
+            .unwrap();
+
+        // The pin matches, but the self-signed cert isn't trusted.
+        let err = verify(webpki, PIN).unwrap_err();
+        assert_ne!(
+            err,
+            rustls::Error::InvalidCertificate(
+                rustls::CertificateError::ApplicationVerificationFailure
+            )
+        );
+    }
+}
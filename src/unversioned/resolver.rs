@@ -10,10 +10,13 @@
 //!
 //! In some situations it might be desirable to not do this lookup, or to use another system
 //! than DNS for it.
+use std::collections::HashMap;
 use std::fmt::{self, Debug};
 use std::net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4, ToSocketAddrs};
 use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Mutex;
 use std::thread::{self};
+use std::time::{Duration, Instant};
 use std::vec::IntoIter;
 
 use http::uri::{Authority, Scheme};
@@ -39,7 +42,7 @@ pub trait Resolver: Debug + Send + Sync + 'static {
 }
 
 /// Max number of socket addresses to keep from the resolver.
-const MAX_ADDRS: usize = 16;
+pub(crate) const MAX_ADDRS: usize = 16;
 
 pub use ureq_proto::ArrayVec;
 
@@ -50,12 +53,59 @@ pub type ResolvedSocketAddrs = ArrayVec<SocketAddr, MAX_ADDRS>;
 ///
 /// Uses std::net [`ToSocketAddrs`](https://doc.rust-lang.org/std/net/trait.ToSocketAddrs.html) to
 /// do the lookup. Can optionally spawn a thread to abort lookup if the relevant timeout is set.
+///
+/// The resolver can optionally cache lookups, see [`DefaultResolverBuilder::cache_ttl()`].
 #[derive(Default)]
 pub struct DefaultResolver {
-    _private: (),
+    cache: Option<DnsCache>,
+}
+
+/// Builder of [`DefaultResolver`]
+///
+/// ```
+/// use std::time::Duration;
+/// use ureq::Agent;
+/// use ureq::config::Config;
+/// use ureq::unversioned::resolver::DefaultResolver;
+/// use ureq::unversioned::transport::DefaultConnector;
+///
+/// let resolver = DefaultResolver::builder()
+///     .cache_ttl(Duration::from_secs(60))
+///     .build();
+///
+/// let agent = Agent::with_parts(Config::default(), DefaultConnector::new(), resolver);
+/// ```
+#[derive(Debug, Default)]
+pub struct DefaultResolverBuilder {
+    cache_ttl: Option<Duration>,
+}
+
+impl DefaultResolverBuilder {
+    /// Cache resolved addresses for the given duration.
+    ///
+    /// Lookups are cached per host and port. The cache is in memory, and shared by all
+    /// clones of the [`Agent`](crate::Agent) using the resolver.
+    ///
+    /// Defaults to `None`, meaning every request does a fresh lookup.
+    pub fn cache_ttl(mut self, ttl: Duration) -> Self {
+        self.cache_ttl = Some(ttl);
+        self
+    }
+
+    /// Build the resolver.
+    pub fn build(self) -> DefaultResolver {
+        DefaultResolver {
+            cache: self.cache_ttl.map(DnsCache::new),
+        }
+    }
 }
 
 impl DefaultResolver {
+    /// Builder to configure a resolver.
+    pub fn builder() -> DefaultResolverBuilder {
+        DefaultResolverBuilder::default()
+    }
+
     /// Helper to combine scheme host and port to a single string.
     ///
     /// This knows about the default ports for http, https and socks proxies which
@@ -100,13 +150,28 @@ impl Resolver for DefaultResolver {
         // Determine if we want to use the async behavior.
         let use_sync = timeout.after.is_not_happening();
 
-        let iter = if use_sync {
-            trace!("Resolve: {}", addr);
-            // When timeout is not set, we do not spawn any threads.
-            addr.to_socket_addrs()?
+        let cached = self.cache.as_ref().and_then(|c| c.get(&addr));
+
+        let iter = if let Some(v) = cached {
+            trace!("Resolve from cache: {}", addr);
+            v.into_iter()
         } else {
-            trace!("Resolve with timeout ({:?}): {} ", timeout, addr);
-            resolve_async(addr, timeout)?
+            let iter = if use_sync {
+                trace!("Resolve: {}", addr);
+                // When timeout is not set, we do not spawn any threads.
+                addr.clone().to_socket_addrs()?
+            } else {
+                trace!("Resolve with timeout ({:?}): {} ", timeout, addr);
+                resolve_async(addr.clone(), timeout)?
+            };
+
+            if let Some(cache) = &self.cache {
+                let v: Vec<_> = iter.collect();
+                cache.insert(addr, v.clone());
+                v.into_iter()
+            } else {
+                iter
+            }
         };
 
         let ip_family = config.ip_family();
@@ -149,9 +214,49 @@ fn resolve_async(addr: String, timeout: NextTimeout) -> Result<IntoIter<SocketAd
     }
 }
 
+/// In memory cache of resolved addresses, keyed by "host:port".
+struct DnsCache {
+    ttl: Duration,
+    entries: Mutex<HashMap<String, (Instant, Vec<SocketAddr>)>>,
+}
+
+impl DnsCache {
+    fn new(ttl: Duration) -> Self {
+        DnsCache {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<Vec<SocketAddr>> {
+        let mut entries = self.entries.lock().unwrap();
+
+        let (resolved_at, addrs) = entries.get(key)?;
+
+        if resolved_at.elapsed() >= self.ttl {
+            entries.remove(key);
+            return None;
+        }
+
+        Some(addrs.clone())
+    }
+
+    fn insert(&self, key: String, addrs: Vec<SocketAddr>) {
+        let mut entries = self.entries.lock().unwrap();
+
+        // Prune expired entries to not grow unbounded.
+        let ttl = self.ttl;
+        entries.retain(|_, (resolved_at, _)| resolved_at.elapsed() < ttl);
+
+        entries.insert(key, (Instant::now(), addrs));
+    }
+}
+
 impl fmt::Debug for DefaultResolver {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_struct("DefaultResolver").finish()
+        f.debug_struct("DefaultResolver")
+            .field("cache_ttl", &self.cache.as_ref().map(|c| c.ttl))
+            .finish()
     }
 }
 
@@ -178,4 +283,19 @@ mod test {
         assert!(matches!(err, Error::BadUri(_)));
         assert_eq!(err.to_string(), "bad uri: unknown scheme: foo");
     }
+
+    #[test]
+    fn dns_cache_ttl() {
+        let addr: SocketAddr = "10.0.0.1:80".parse().unwrap();
+
+        let cache = DnsCache::new(std::time::Duration::from_secs(60));
+        assert_eq!(cache.get("example.test:80"), None);
+        cache.insert("example.test:80".into(), vec![addr]);
+        assert_eq!(cache.get("example.test:80"), Some(vec![addr]));
+        assert_eq!(cache.get("example.test:443"), None);
+
+        let cache = DnsCache::new(std::time::Duration::ZERO);
+        cache.insert("example.test:80".into(), vec![addr]);
+        assert_eq!(cache.get("example.test:80"), None);
+    }
 }
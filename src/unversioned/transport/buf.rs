@@ -44,6 +44,18 @@ pub trait Buffers {
     /// [`SendBody`](crate::SendBody) into tmp and then write it to the output buffer.
     fn tmp_and_output(&mut self) -> (&mut [u8], &mut [u8]);
 
+    /// Whether the output of [`Buffers::tmp_and_output()`] is the same buffer as
+    /// [`Buffers::output()`], with the contents kept.
+    ///
+    /// When true, a small request body is written after the request header, which is
+    /// not yet transmitted, to send both in one
+    /// [`Transport::transmit_output()`](crate::transport::Transport::transmit_output).
+    ///
+    /// Defaults to `false`.
+    fn keeps_output(&self) -> bool {
+        false
+    }
+
     /// Helper to determine if the `&input` already holds unconsumed data or we need to
     /// read more input from the transport. This indicates two things:
     ///
@@ -135,6 +147,10 @@ impl Buffers for LazyBuffers {
         (self.input.free_mut(), &mut self.output)
     }
 
+    fn keeps_output(&self) -> bool {
+        true
+    }
+
     fn input_appended(&mut self, amount: usize) {
         self.input.add_filled(amount);
     }
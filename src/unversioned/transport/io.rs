@@ -55,9 +55,13 @@ impl TransportAdapter {
 
 impl io::Read for TransportAdapter {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        self.transport
-            .await_input(self.timeout)
-            .map_err(|e| e.into_io())?;
+        // Input left in the buffer, such as after an upgraded response
+        // header, is handed out before waiting for more.
+        if self.transport.buffers().input().is_empty() {
+            self.transport
+                .await_input(self.timeout)
+                .map_err(|e| e.into_io())?;
+        }
         let input = self.transport.buffers().input();
 
         let max = buf.len().min(input.len());
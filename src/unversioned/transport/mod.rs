@@ -17,6 +17,8 @@
 //! * HTTPS/TLS using rustls (feature flag **rustls**)
 //! * HTTPS/TLS using native-tls (feature flag **native-tls** + [config](crate::tls::TlsProvider::NativeTls))
 //!
+//! On unix, the `UnixConnector` connects to Unix domain sockets instead of TCP.
+//!
 //! The [`Connector`] trait anticipates a chain of connectors that each decide
 //! whether to help perform the connection or not. It is for instance possible to make a
 //! connector handling other schemes than `http`/`https` without affecting "regular" connections
@@ -24,11 +26,12 @@
 //! up a chain of concrete connectors.
 
 use std::fmt::Debug;
+use std::net::SocketAddr;
 
 use http::uri::Scheme;
 use http::Uri;
 
-use crate::config::Config;
+use crate::config::{Config, ProbeKind};
 use crate::http;
 use crate::proxy::Proto;
 use crate::Error;
@@ -43,14 +46,24 @@ pub use buf::{Buffers, LazyBuffers};
 
 mod tcp;
 
+#[cfg(unix)]
+mod unix;
+#[cfg(unix)]
+pub use self::unix::UnixConnector;
+
 mod io;
 pub use io::TransportAdapter;
 
 mod chain;
 pub use chain::ChainedConnector;
 
+mod throttle;
+pub use throttle::ThrottleConnector;
+
 #[cfg(feature = "_test")]
 mod test;
+#[cfg(all(test, feature = "_test"))]
+pub(crate) use test::one_shot_server;
 #[cfg(feature = "_test")]
 pub use test::set_handler;
 
@@ -204,12 +217,40 @@ pub trait Transport: Debug + Send + Sync {
     /// for connection pooling to work.
     fn is_open(&mut self) -> bool;
 
+    /// Check a pooled connection before it is used for another request.
+    ///
+    /// The default implementation uses [`Transport::is_open()`] for any probe other
+    /// than [`ProbeKind::None`]. Transports can override this to do something more
+    /// specific for [`ProbeKind::TcpKeepAliveCheck`].
+    fn probe(&mut self, kind: ProbeKind) -> bool {
+        match kind {
+            ProbeKind::None => true,
+            ProbeKind::NonBlockingRead | ProbeKind::TcpKeepAliveCheck => self.is_open(),
+        }
+    }
+
     /// Whether the transport is TLS.
     ///
     /// Defaults to `false`, override in TLS transports.
     fn is_tls(&self) -> bool {
         false
     }
+
+    /// Time spent on the TLS handshake.
+    ///
+    /// This is separate from the time to open the underlying connection. Defaults
+    /// to `None`, override in TLS transports once the handshake is done.
+    fn tls_handshake_duration(&self) -> Option<std::time::Duration> {
+        None
+    }
+
+    /// The address of the remote peer this transport is connected to.
+    ///
+    /// Defaults to `None`, override in transports over sockets. Transports
+    /// wrapping another transport should pass this on.
+    fn remote_addr(&self) -> Option<SocketAddr> {
+        None
+    }
 }
 
 /// Default connector providing TCP sockets, TLS and SOCKS proxy.
@@ -225,7 +266,9 @@ pub trait Transport: Debug + Send + Sync {
 /// 4. [`NativeTlsConnector`] which wraps
 ///    the connection from 1 or 2 in TLS if the scheme is `https` and
 ///    [`TlsConfig`](crate::tls::TlsConfig) indicate we are using **native-tls**.
+/// 5. [`ConnectProxyConnector`] which tunnels through a CONNECT proxy, if configured.
 ///
+/// Use [`DefaultConnector::builder()`] to add connectors to this chain.
 #[derive(Debug)]
 pub struct DefaultConnector {
     chain: ChainedConnector,
@@ -236,52 +279,136 @@ impl DefaultConnector {
     pub fn new() -> Self {
         Self::default()
     }
+
+    /// Builder to add connectors to the default chain.
+    ///
+    /// This avoids re-declaring the entire chain (with its feature flags) in
+    /// a [`ChainedConnector`] to add a single connector.
+    ///
+    /// ```
+    /// use ureq::Agent;
+    /// use ureq::config::Config;
+    /// use ureq::unversioned::resolver::DefaultResolver;
+    /// use ureq::unversioned::transport::{ConnectionDetails, Connector};
+    /// use ureq::unversioned::transport::{DefaultConnector, Transport};
+    ///
+    /// #[derive(Debug)]
+    /// struct LogConnector;
+    ///
+    /// impl Connector for LogConnector {
+    ///     fn connect(
+    ///         &self,
+    ///         details: &ConnectionDetails,
+    ///         chained: Option<Box<dyn Transport>>,
+    ///     ) -> Result<Option<Box<dyn Transport>>, ureq::Error> {
+    ///         println!("Connected to: {}", details.uri);
+    ///         Ok(chained)
+    ///     }
+    /// }
+    ///
+    /// let connector = DefaultConnector::builder()
+    ///     .after_tcp(LogConnector)
+    ///     .build();
+    ///
+    /// let agent = Agent::with_parts(Config::default(), connector, DefaultResolver::default());
+    /// ```
+    pub fn builder() -> DefaultConnectorBuilder {
+        DefaultConnectorBuilder::default()
+    }
+}
+
+/// Builder of [`DefaultConnector`].
+///
+/// Connectors are added at named positions of the default chain. Several
+/// connectors at the same position are called in the order they are added.
+#[derive(Debug, Default)]
+pub struct DefaultConnectorBuilder {
+    before_tcp: Vec<Box<dyn Connector>>,
+    after_tcp: Vec<Box<dyn Connector>>,
+    after_tls: Vec<Box<dyn Connector>>,
+}
+
+impl DefaultConnectorBuilder {
+    /// Add a connector before the SOCKS and TCP connectors.
+    ///
+    /// A transport returned here is used instead of opening a socket.
+    pub fn before_tcp(mut self, connector: impl Connector) -> Self {
+        self.before_tcp.push(connector.boxed());
+        self
+    }
+
+    /// Add a connector after the SOCKS and TCP connectors, before TLS.
+    ///
+    /// The chained transport is the plain socket, which makes this the place
+    /// to wrap the raw connection, like for bandwidth throttling.
+    pub fn after_tcp(mut self, connector: impl Connector) -> Self {
+        self.after_tcp.push(connector.boxed());
+        self
+    }
+
+    /// Add a connector after TLS, before tunneling through a CONNECT proxy.
+    pub fn after_tls(mut self, connector: impl Connector) -> Self {
+        self.after_tls.push(connector.boxed());
+        self
+    }
+
+    /// Build the connector.
+    pub fn build(self) -> DefaultConnector {
+        let mut chain: Vec<Box<dyn Connector>> = vec![];
+
+        // When enabled, all tests are connected to a dummy server and will not
+        // make requests to the internet.
+        #[cfg(feature = "_test")]
+        chain.push(test::TestConnector.boxed());
+
+        chain.extend(self.before_tcp);
+
+        // If we are using socks-proxy, that takes precedence over TcpConnector.
+        #[cfg(feature = "socks-proxy")]
+        chain.push(SocksConnector::default().boxed());
+
+        // If the config indicates we ought to use a socks proxy
+        // and the feature flag isn't enabled, we should warn the user.
+        #[cfg(not(feature = "socks-proxy"))]
+        chain.push(no_proxy::WarnOnNoSocksConnector.boxed());
+
+        // If we didn't get a socks-proxy, open a Tcp connection
+        chain.push(TcpConnector::default().boxed());
+
+        chain.extend(self.after_tcp);
+
+        // If rustls is enabled, prefer that
+        #[cfg(feature = "rustls")]
+        chain.push(RustlsConnector::default().boxed());
+
+        // Panic if the config calls for rustls, the uri scheme is https and that
+        // TLS provider is not enabled by feature flags (unless panic_on_missing_tls is false).
+        #[cfg(feature = "_tls")]
+        chain.push(no_tls::WarnOnMissingTlsProvider(crate::tls::TlsProvider::Rustls).boxed());
+
+        // As a fallback if rustls isn't enabled, use native-tls
+        #[cfg(feature = "native-tls")]
+        chain.push(NativeTlsConnector::default().boxed());
+
+        // Panic if the config calls for native-tls, the uri scheme is https and that
+        // TLS provider is not enabled by feature flags (unless panic_on_missing_tls is false).
+        #[cfg(feature = "_tls")]
+        chain.push(no_tls::WarnOnMissingTlsProvider(crate::tls::TlsProvider::NativeTls).boxed());
+
+        chain.extend(self.after_tls);
+
+        // Do the final CONNECT proxy on top of the connection if indicated by config.
+        chain.push(ConnectProxyConnector.boxed());
+
+        DefaultConnector {
+            chain: ChainedConnector::new(chain),
+        }
+    }
 }
 
 impl Default for DefaultConnector {
     fn default() -> Self {
-        let chain = ChainedConnector::new([
-            //
-            // When enabled, all tests are connected to a dummy server and will not
-            // make requests to the internet.
-            #[cfg(feature = "_test")]
-            test::TestConnector.boxed(),
-            //
-            // If we are using socks-proxy, that takes precedence over TcpConnector.
-            #[cfg(feature = "socks-proxy")]
-            SocksConnector::default().boxed(),
-            //
-            // If the config indicates we ought to use a socks proxy
-            // and the feature flag isn't enabled, we should warn the user.
-            #[cfg(not(feature = "socks-proxy"))]
-            no_proxy::WarnOnNoSocksConnector.boxed(),
-            //
-            // If we didn't get a socks-proxy, open a Tcp connection
-            TcpConnector::default().boxed(),
-            //
-            // If rustls is enabled, prefer that
-            #[cfg(feature = "rustls")]
-            RustlsConnector::default().boxed(),
-            //
-            // Panic if the config calls for rustls, the uri scheme is https and that
-            // TLS provider is not enabled by feature flags.
-            #[cfg(feature = "_tls")]
-            no_tls::WarnOnMissingTlsProvider(crate::tls::TlsProvider::Rustls).boxed(),
-            //
-            // As a fallback if rustls isn't enabled, use native-tls
-            #[cfg(feature = "native-tls")]
-            NativeTlsConnector::default().boxed(),
-            //
-            // Panic if the config calls for native-tls, the uri scheme is https and that
-            // TLS provider is not enabled by feature flags.
-            #[cfg(feature = "_tls")]
-            no_tls::WarnOnMissingTlsProvider(crate::tls::TlsProvider::NativeTls).boxed(),
-            //
-            // Do the final CONNECT proxy on top of the connection if indicated by config.
-            ConnectProxyConnector.boxed(),
-        ]);
-
-        DefaultConnector { chain }
+        DefaultConnectorBuilder::default().build()
     }
 }
 
@@ -359,6 +486,10 @@ mod no_tls {
                 && tls_config.provider() == self.0
                 && !self.0.is_feature_enabled()
             {
+                if !details.config.panic_on_missing_tls() {
+                    return Err(Error::NoTlsProvider(self.0));
+                }
+
                 panic!(
                     "uri scheme is https, provider is {:?} but feature is not enabled: {}",
                     self.0,
@@ -369,4 +500,57 @@ mod no_tls {
             Ok(chained)
         }
     }
+
+    #[cfg(all(test, not(feature = "native-tls")))]
+    mod test {
+        use crate::http::Uri;
+
+        use crate::config::Config;
+        use crate::resolver::{DefaultResolver, ResolvedSocketAddrs};
+        use crate::tls::TlsConfig;
+        use crate::transport::time::{Duration, Instant};
+        use crate::transport::NextTimeout;
+        use crate::Timeout;
+
+        use super::*;
+
+        fn connect(config: &Config) -> Result<Option<Box<dyn Transport>>, Error> {
+            let uri = Uri::from_static("https://a.test");
+            let details = ConnectionDetails {
+                uri: &uri,
+                addrs: ResolvedSocketAddrs::from_fn(|_| "1.2.3.4:443".parse().unwrap()),
+                resolver: &DefaultResolver::default(),
+                config,
+                now: Instant::now(),
+                timeout: NextTimeout {
+                    after: Duration::NotHappening,
+                    reason: Timeout::Connect,
+                },
+            };
+            WarnOnMissingTlsProvider(TlsProvider::NativeTls).connect(&details, None)
+        }
+
+        fn native_tls_config() -> crate::config::ConfigBuilder<crate::config::typestate::AgentScope>
+        {
+            Config::builder().tls_config(
+                TlsConfig::builder()
+                    .provider(TlsProvider::NativeTls)
+                    .build(),
+            )
+        }
+
+        #[test]
+        fn missing_tls_provider_error() {
+            let config = native_tls_config().panic_on_missing_tls(false).build();
+            let err = connect(&config).unwrap_err();
+            assert!(matches!(err, Error::NoTlsProvider(TlsProvider::NativeTls)));
+        }
+
+        #[test]
+        #[should_panic(expected = "feature is not enabled: native-tls")]
+        fn missing_tls_provider_panic() {
+            let config = native_tls_config().build();
+            let _ = connect(&config);
+        }
+    }
 }
@@ -3,14 +3,15 @@ use std::net::{SocketAddr, TcpStream};
 use std::sync::mpsc::{self, RecvTimeoutError};
 use std::{io, thread};
 
-use socks::{Socks4Stream, Socks5Stream};
+use socks::{Socks4Stream, Socks5Stream, TargetAddr};
 
 use crate::proxy::{Proto, Proxy};
+use crate::util::{SchemeExt, UriExt};
 use crate::Error;
 
 use super::ResolvedSocketAddrs;
 
-use super::tcp::TcpTransport;
+use super::tcp::{configure_stream, TcpTransport};
 use super::{ConnectionDetails, Connector, LazyBuffers, NextTimeout, Transport};
 
 /// Connector for SOCKS proxies.
@@ -46,11 +47,23 @@ impl Connector for SocksConnector {
             .resolver
             .resolve(proxy.uri(), details.config, details.timeout)?;
 
-        let stream = try_connect(&proxy_addrs, &details.addrs, proxy, details.timeout)?;
+        let targets: Vec<TargetAddr> = if proxy.proto().resolves_target() {
+            // The host name is sent to the proxy, which resolves it.
+            details.uri.ensure_valid_url()?;
+            // unwraps are ok because ensure_valid_url() above.
+            let host = details.uri.host().unwrap();
+            let port = details
+                .uri
+                .port_u16()
+                .unwrap_or_else(|| details.uri.scheme().unwrap().default_port().unwrap());
+            vec![TargetAddr::Domain(host.to_string(), port)]
+        } else {
+            details.addrs.iter().map(|a| TargetAddr::Ip(*a)).collect()
+        };
 
-        if details.config.no_delay() {
-            stream.set_nodelay(true)?;
-        }
+        let stream = try_connect(&proxy_addrs, &targets, proxy, details.timeout)?;
+
+        configure_stream(&stream, details.config)?;
 
         let buffers = LazyBuffers::new(
             details.config.input_buffer_size(),
@@ -64,23 +77,23 @@ impl Connector for SocksConnector {
 
 fn try_connect(
     proxy_addrs: &ResolvedSocketAddrs,
-    target_addrs: &ResolvedSocketAddrs,
+    target_addrs: &[TargetAddr],
     proxy: &Proxy,
     timeout: NextTimeout,
 ) -> Result<TcpStream, Error> {
     for target_addr in target_addrs {
         for proxy_addr in proxy_addrs {
             trace!(
-                "Try connect {} {} -> {}",
+                "Try connect {} {} -> {:?}",
                 proxy.proto(),
                 proxy_addr,
                 target_addr
             );
 
-            match try_connect_single(*proxy_addr, *target_addr, proxy, timeout) {
+            match try_connect_single(*proxy_addr, target_addr.clone(), proxy, timeout) {
                 Ok(v) => {
                     debug!(
-                        "{} connected {} -> {}",
+                        "{} connected {} -> {:?}",
                         proxy.proto(),
                         proxy_addr,
                         target_addr
@@ -89,7 +102,11 @@ fn try_connect(
                 }
                 // Intercept ConnectionRefused to try next addrs
                 Err(Error::Io(e)) if e.kind() == io::ErrorKind::ConnectionRefused => {
-                    trace!("{} -> {} proxy connection refused", proxy_addr, target_addr);
+                    trace!(
+                        "{} -> {:?} proxy connection refused",
+                        proxy_addr,
+                        target_addr
+                    );
                     continue;
                 }
                 // Other errors bail
@@ -107,7 +124,7 @@ fn try_connect(
 
 fn try_connect_single(
     proxy_addr: SocketAddr,
-    target_addr: SocketAddr,
+    target_addr: TargetAddr,
     proxy: &Proxy,
     timeout: NextTimeout,
 ) -> Result<TcpStream, Error> {
@@ -133,7 +150,7 @@ fn try_connect_single(
 fn connect_proxy(
     proxy: &Proxy,
     proxy_addr: SocketAddr,
-    target_addr: SocketAddr,
+    target_addr: TargetAddr,
 ) -> Result<TcpStream, Error> {
     let stream = match proxy.proto() {
         Proto::Socks4 | Proto::Socks4A => {
@@ -143,7 +160,7 @@ fn connect_proxy(
 
             Socks4Stream::connect(proxy_addr, target_addr, "")?.into_inner()
         }
-        Proto::Socks5 => {
+        Proto::Socks5 | Proto::Socks5H => {
             if let Some(username) = proxy.username() {
                 // Connect with authentication.
                 let password = proxy.password().unwrap_or("");
@@ -165,3 +182,92 @@ impl fmt::Debug for SocksConnector {
         f.debug_struct("SocksConnector").finish()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    use super::*;
+    use crate::config::Config;
+    use crate::http::Uri;
+    use crate::resolver::Resolver;
+    use crate::Agent;
+
+    #[derive(Debug)]
+    struct OnlyProxy(SocketAddr);
+
+    impl Resolver for OnlyProxy {
+        fn resolve(
+            &self,
+            uri: &Uri,
+            _config: &Config,
+            _timeout: NextTimeout,
+        ) -> Result<ResolvedSocketAddrs, Error> {
+            assert_eq!(uri.host(), Some("127.0.0.1"), "local lookup of target");
+            let mut v = ResolvedSocketAddrs::from_fn(|_| self.0);
+            v.push(self.0);
+            Ok(v)
+        }
+    }
+
+    #[test]
+    fn socks5h_sends_host_name() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+
+            // Greeting: version, number of methods, methods.
+            let mut buf = [0_u8; 2];
+            stream.read_exact(&mut buf).unwrap();
+            let mut methods = vec![0; buf[1] as usize];
+            stream.read_exact(&mut methods).unwrap();
+            stream.write_all(&[5, 0]).unwrap();
+
+            // Request: version, CONNECT, reserved, address type.
+            let mut buf = [0_u8; 4];
+            stream.read_exact(&mut buf).unwrap();
+            assert_eq!(buf[3], 3, "address type is domain name");
+            let mut len = [0_u8; 1];
+            stream.read_exact(&mut len).unwrap();
+            let mut host = vec![0; len[0] as usize];
+            stream.read_exact(&mut host).unwrap();
+            let mut port = [0_u8; 2];
+            stream.read_exact(&mut port).unwrap();
+            stream.write_all(&[5, 0, 0, 1, 0, 0, 0, 0, 0, 0]).unwrap();
+
+            // The HTTP request follows.
+            let mut req = vec![];
+            while !req.ends_with(b"\r\n\r\n") {
+                let mut b = [0_u8; 1];
+                stream.read_exact(&mut b).unwrap();
+                req.push(b[0]);
+            }
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok")
+                .unwrap();
+
+            (String::from_utf8(host).unwrap(), u16::from_be_bytes(port))
+        });
+
+        let proxy = Proxy::new(&format!("socks5h://127.0.0.1:{}", addr.port())).unwrap();
+        let config = Config::builder().proxy(Some(proxy)).build();
+
+        let agent = Agent::with_parts(config, SocksConnector::default(), OnlyProxy(addr));
+
+        let body = agent
+            .get("http://remote.example.test/path")
+            .call()
+            .unwrap()
+            .body_mut()
+            .read_to_string()
+            .unwrap();
+        assert_eq!(body, "ok");
+
+        let (host, port) = server.join().unwrap();
+        assert_eq!(host, "remote.example.test");
+        assert_eq!(port, 80);
+    }
+}
@@ -2,7 +2,10 @@ use std::io::{Read, Write};
 use std::net::{SocketAddr, TcpStream};
 use std::{fmt, io, time};
 
-use crate::config::Config;
+#[cfg(feature = "socket2")]
+use socket2::{Domain, Protocol, Socket, Type};
+
+use crate::config::{Config, ProbeKind};
 use crate::util::IoResultExt;
 use crate::Error;
 
@@ -43,7 +46,20 @@ fn try_connect(
     timeout: NextTimeout,
     config: &Config,
 ) -> Result<TcpStream, Error> {
+    // Whether any address was tried, and not skipped for the local address.
+    let mut any_tried = false;
+
     for addr in addrs {
+        #[cfg(feature = "socket2")]
+        if let Some(local) = config.local_address() {
+            if local.is_ipv4() != addr.is_ipv4() {
+                trace!("{} skipped, not same IP family as {}", addr, local);
+                continue;
+            }
+        }
+
+        any_tried = true;
+
         match try_connect_single(*addr, timeout, config) {
             // First that connects
             Ok(v) => return Ok(v),
@@ -57,6 +73,14 @@ fn try_connect(
         }
     }
 
+    if !any_tried && !addrs.is_empty() {
+        debug!("No resolved address in the IP family of the local address");
+        return Err(Error::Io(io::Error::new(
+            io::ErrorKind::AddrNotAvailable,
+            "No resolved address in the IP family of the local address",
+        )));
+    }
+
     debug!("Failed to connect to any resolved address");
     Err(Error::Io(io::Error::new(
         io::ErrorKind::ConnectionRefused,
@@ -71,12 +95,7 @@ fn try_connect_single(
 ) -> Result<TcpStream, Error> {
     trace!("Try connect TcpStream to {}", addr);
 
-    let maybe_stream = if let Some(when) = timeout.not_zero() {
-        TcpStream::connect_timeout(&addr, *when)
-    } else {
-        TcpStream::connect(addr)
-    }
-    .normalize_would_block();
+    let maybe_stream = connect_stream(addr, timeout, config).normalize_would_block();
 
     let stream = match maybe_stream {
         Ok(v) => v,
@@ -86,13 +105,81 @@ fn try_connect_single(
         Err(e) => return Err(e.into()),
     };
 
+    configure_stream(&stream, config)?;
+
+    debug!("Connected TcpStream to {}", addr);
+
+    Ok(stream)
+}
+
+fn connect_plain(addr: SocketAddr, timeout: NextTimeout) -> io::Result<TcpStream> {
+    if let Some(when) = timeout.not_zero() {
+        TcpStream::connect_timeout(&addr, *when)
+    } else {
+        TcpStream::connect(addr)
+    }
+}
+
+#[cfg(not(feature = "socket2"))]
+fn connect_stream(
+    addr: SocketAddr,
+    timeout: NextTimeout,
+    _config: &Config,
+) -> io::Result<TcpStream> {
+    connect_plain(addr, timeout)
+}
+
+#[cfg(feature = "socket2")]
+fn connect_stream(
+    addr: SocketAddr,
+    timeout: NextTimeout,
+    config: &Config,
+) -> io::Result<TcpStream> {
+    #[cfg(target_os = "linux")]
+    let bind_device = config.bind_device();
+    #[cfg(not(target_os = "linux"))]
+    let bind_device: Option<&str> = None;
+
+    if config.local_address().is_none() && bind_device.is_none() {
+        return connect_plain(addr, timeout);
+    }
+
+    // Binding must happen before connecting, which std::net can't do.
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+
+    #[cfg(target_os = "linux")]
+    if let Some(device) = bind_device {
+        trace!("Bind to device {}", device);
+        socket.bind_device(Some(device.as_bytes()))?;
+    }
+
+    if let Some(local) = config.local_address() {
+        trace!("Bind to local address {}", local);
+        socket.bind(&SocketAddr::new(local, 0).into())?;
+    }
+
+    if let Some(when) = timeout.not_zero() {
+        socket.connect_timeout(&addr.into(), *when)?;
+    } else {
+        socket.connect(&addr.into())?;
+    }
+
+    Ok(socket.into())
+}
+
+/// Apply the socket options from the config to a connected stream.
+pub(super) fn configure_stream(stream: &TcpStream, config: &Config) -> Result<(), Error> {
     if config.no_delay() {
         stream.set_nodelay(true)?;
     }
 
-    debug!("Connected TcpStream to {}", addr);
+    #[cfg(feature = "socket2")]
+    if let Some(time) = config.tcp_keepalive() {
+        let keepalive = socket2::TcpKeepalive::new().with_time(time);
+        socket2::SockRef::from(stream).set_tcp_keepalive(&keepalive)?;
+    }
 
-    Ok(stream)
+    Ok(())
 }
 
 pub struct TcpTransport {
@@ -114,11 +201,11 @@ impl TcpTransport {
 }
 
 // The goal here is to only cause a syscall to set the timeout if it's necessary.
-fn maybe_update_timeout(
+pub(super) fn maybe_update_timeout<S>(
     timeout: NextTimeout,
     previous: &mut Option<Duration>,
-    stream: &TcpStream,
-    f: impl Fn(&TcpStream, Option<time::Duration>) -> io::Result<()>,
+    stream: &S,
+    f: impl Fn(&S, Option<time::Duration>) -> io::Result<()>,
 ) -> io::Result<()> {
     let maybe_timeout = timeout.not_zero();
 
@@ -178,13 +265,35 @@ impl Transport for TcpTransport {
     }
 
     fn is_open(&mut self) -> bool {
-        probe_tcp_stream(&mut self.stream).unwrap_or(false)
+        probe_stream(&mut self.stream, TcpStream::set_nonblocking).unwrap_or(false)
+    }
+
+    fn probe(&mut self, kind: ProbeKind) -> bool {
+        match kind {
+            ProbeKind::None => true,
+            ProbeKind::NonBlockingRead => self.is_open(),
+            ProbeKind::TcpKeepAliveCheck => {
+                // A failed keep-alive surfaces as a pending socket error.
+                if !matches!(self.stream.take_error(), Ok(None)) {
+                    info!("Pending socket error. Closing connection");
+                    return false;
+                }
+                self.is_open()
+            }
+        }
+    }
+
+    fn remote_addr(&self) -> Option<SocketAddr> {
+        self.stream.peer_addr().ok()
     }
 }
 
-fn probe_tcp_stream(stream: &mut TcpStream) -> Result<bool, Error> {
+pub(super) fn probe_stream<S: Read>(
+    stream: &mut S,
+    set_nonblocking: impl Fn(&S, bool) -> io::Result<()>,
+) -> Result<bool, Error> {
     // Temporary do non-blocking IO
-    stream.set_nonblocking(true)?;
+    (set_nonblocking)(stream, true)?;
 
     let mut buf = [0];
     match stream.read(&mut buf) {
@@ -202,7 +311,7 @@ fn probe_tcp_stream(stream: &mut TcpStream) -> Result<bool, Error> {
     };
 
     // Reset back to blocking
-    stream.set_nonblocking(false)?;
+    (set_nonblocking)(stream, false)?;
 
     Ok(true)
 }
@@ -220,3 +329,73 @@ impl fmt::Debug for TcpTransport {
             .finish()
     }
 }
+
+#[cfg(all(test, feature = "socket2"))]
+mod test {
+    use std::net::TcpListener;
+
+    use super::*;
+
+    #[test]
+    fn configure_stream_keepalive() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+
+        let config = Config::builder()
+            .no_delay(false)
+            .tcp_keepalive(Some(time::Duration::from_secs(30)))
+            .build();
+        configure_stream(&stream, &config).unwrap();
+
+        let sock = socket2::SockRef::from(&stream);
+        assert!(sock.keepalive().unwrap());
+        assert!(!stream.nodelay().unwrap());
+    }
+
+    #[test]
+    fn connect_local_address() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let config = Config::builder()
+            .local_address(Some("127.0.0.1".parse().unwrap()))
+            .build();
+        let timeout = NextTimeout {
+            after: Duration::NotHappening,
+            reason: crate::Timeout::Connect,
+        };
+        let stream = connect_stream(addr, timeout, &config).unwrap();
+
+        let (accepted, peer) = listener.accept().unwrap();
+        assert_eq!(peer, stream.local_addr().unwrap());
+        assert_eq!(accepted.local_addr().unwrap(), addr);
+    }
+
+    #[test]
+    #[cfg(feature = "_test")]
+    fn local_address_skips_other_family() {
+        use crate::resolver::DefaultResolver;
+        use crate::transport::one_shot_server;
+        use crate::Agent;
+
+        let (addr, server) =
+            one_shot_server(vec!["HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok"]);
+        let uri = format!("http://localhost:{}/", addr.port());
+
+        let agent = |local: &str| {
+            let config = Config::builder()
+                .resolve_override("localhost", addr.port(), addr)
+                .local_address(Some(local.parse().unwrap()))
+                .build();
+            Agent::with_parts(config, TcpConnector::default(), DefaultResolver::default())
+        };
+
+        let mut res = agent("127.0.0.1").get(&uri).call().unwrap();
+        assert_eq!(res.body_mut().read_to_string().unwrap(), "ok");
+        assert_eq!(server.join().unwrap().len(), 1);
+
+        // The IPv4 server address is skipped for an IPv6 local address.
+        let err = agent("::1").get(&uri).call().unwrap_err();
+        assert!(matches!(err, Error::Io(e) if e.kind() == io::ErrorKind::AddrNotAvailable));
+    }
+}
@@ -2,7 +2,11 @@
 
 use std::cell::RefCell;
 use std::io::Write;
+#[cfg(test)]
 use std::io::{BufRead, BufReader};
+#[cfg(test)]
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
 use std::sync::{Arc, Mutex};
 use std::{fmt, io, thread};
@@ -50,6 +54,7 @@ impl Connector for TestConnector {
             tx: tx1,
             rx: SyncReceiver(Mutex::new(rx2)),
             connected: true,
+            remote_addr: details.addrs.first().copied(),
         };
 
         Ok(Some(Box::new(transport)))
@@ -95,6 +100,84 @@ pub fn set_handler(pattern: &'static str, status: u16, headers: &[(&str, &str)],
     HANDLERS.with(|h| (*h).borrow_mut().push(handler));
 }
 
+/// Real socket server for tests that need an actual [`TcpConnector`](super::TcpConnector).
+///
+/// Answers each request with the next of `responses`. Requests are read from the same
+/// connection as long as the client keeps it open, and from a newly accepted one
+/// otherwise. Stops when the responses run out or a client closes the connection in
+/// the middle of a request.
+///
+/// Returns the address to connect to, and a handle giving the raw requests.
+#[cfg(test)]
+pub(crate) fn one_shot_server(
+    responses: Vec<&'static str>,
+) -> (SocketAddr, thread::JoinHandle<Vec<String>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let handle = thread::spawn(move || {
+        let mut requests = vec![];
+        let mut reader: Option<BufReader<TcpStream>> = None;
+
+        for response in responses {
+            let request = loop {
+                let r = reader.get_or_insert_with(|| BufReader::new(listener.accept().unwrap().0));
+
+                // Closed between requests, the next one comes on a new connection.
+                if r.fill_buf().map(|b| b.is_empty()).unwrap_or(true) {
+                    reader = None;
+                    continue;
+                }
+
+                match read_request(r) {
+                    Some(v) => break v,
+                    None => return requests,
+                }
+            };
+
+            let stream = reader.as_mut().unwrap().get_mut();
+            stream.write_all(response.as_bytes()).unwrap();
+            requests.push(request);
+        }
+
+        requests
+    });
+
+    (addr, handle)
+}
+
+/// Read a request head and a Content-Length or chunked body.
+#[cfg(test)]
+fn read_request(reader: &mut impl BufRead) -> Option<String> {
+    let mut request = vec![];
+
+    while !request.ends_with(b"\r\n\r\n") {
+        if reader.read_until(b'\n', &mut request).ok()? == 0 {
+            return None;
+        }
+    }
+
+    let head = String::from_utf8_lossy(&request).to_ascii_lowercase();
+    let content_length = head
+        .lines()
+        .find_map(|l| l.strip_prefix("content-length: "))
+        .map(|v| v.parse::<usize>().unwrap());
+
+    if let Some(len) = content_length {
+        let start = request.len();
+        request.resize(start + len, 0);
+        reader.read_exact(&mut request[start..]).ok()?;
+    } else if head.contains("\r\ntransfer-encoding: chunked\r\n") {
+        while !request.ends_with(b"\r\n0\r\n\r\n") {
+            if reader.read_until(b'\n', &mut request).ok()? == 0 {
+                return None;
+            }
+        }
+    }
+
+    Some(String::from_utf8_lossy(&request).into_owned())
+}
+
 #[derive(Clone)]
 struct TestHandler {
     pattern: &'static str,
@@ -107,22 +190,46 @@ fn test_run(
     tx: mpsc::SyncSender<Vec<u8>>,
     handlers: Vec<TestHandler>,
 ) {
-    let mut reader = BufReader::new(RxRead(rx));
     let mut writer = TxWrite(tx);
     let uri_s = uri.to_string();
 
-    let req = loop {
-        let input = reader.fill_buf().expect("test fill_buf");
+    let mut input = vec![];
+    let mut connect_target = None;
+
+    let mut req = loop {
         let maybe =
-            ureq_proto::parser::try_parse_request::<100>(input).expect("test parse request");
-        if let Some((amount, req)) = maybe {
-            reader.consume(amount);
-            break req;
-        } else {
+            ureq_proto::parser::try_parse_request::<100>(&input).expect("test parse request");
+
+        let Some((amount, req)) = maybe else {
+            // The request can arrive in several writes.
+            match rx.recv() {
+                Ok(v) => input.extend_from_slice(&v),
+                Err(_) => return, // remote side is gone
+            }
+            continue;
+        };
+
+        input.drain(..amount);
+
+        if req.method() == Method::CONNECT {
+            // Pretend to be a CONNECT proxy, the real request follows.
+            write!(writer, "HTTP/1.1 200 Connection established\r\n\r\n")
+                .expect("test write CONNECT response");
+            connect_target = Some(req.uri().to_string());
             continue;
         }
+
+        break req;
     };
 
+    if let Some(target) = connect_target {
+        // Lets handlers tell whether the request came via the CONNECT proxy.
+        req.headers_mut().insert(
+            "x-test-via-connect-proxy",
+            http::HeaderValue::from_str(&target).unwrap(),
+        );
+    }
+
     for handler in handlers {
         if uri_s.contains(handler.pattern) {
             (handler.handler)(uri, req, &mut writer).expect("test handler to not fail");
@@ -288,6 +395,207 @@ fn setup_default_handlers(handlers: &mut Vec<TestHandler>) {
         handlers,
     );
 
+    maybe_add(
+        TestHandler::new("/stream/3", |_uri, _req, w| {
+            let body = "{\"id\": 0}\n{\"id\": 1}\n\n{\"id\": 2}\n";
+            write!(
+                w,
+                "HTTP/1.1 200 OK\r\n\
+                Content-Type: application/json\r\n\
+                Content-Length: {}\r\n\
+                \r\n\
+                {}",
+                body.len(),
+                body
+            )
+        }),
+        handlers,
+    );
+
+    maybe_add(
+        TestHandler::new("/http10-keep-alive", |_uri, _req, w| {
+            write!(
+                w,
+                "HTTP/1.0 200 OK\r\n\
+                Connection: keep-alive\r\n\
+                Content-Length: 2\r\n\
+                \r\n\
+                ok"
+            )
+        }),
+        handlers,
+    );
+
+    maybe_add(
+        TestHandler::new("/http10", |_uri, _req, w| {
+            write!(
+                w,
+                "HTTP/1.0 200 OK\r\n\
+                Content-Length: 2\r\n\
+                \r\n\
+                ok"
+            )
+        }),
+        handlers,
+    );
+
+    maybe_add(
+        TestHandler::new("/digest-auth", |_uri, req, w| {
+            let auth = req
+                .headers()
+                .get("authorization")
+                .and_then(|v| v.to_str().ok())
+                .filter(|v| v.starts_with("Digest "));
+
+            match auth {
+                Some(auth) => write!(
+                    w,
+                    "HTTP/1.1 200 OK\r\n\
+                    Content-Length: {}\r\n\
+                    \r\n\
+                    {}",
+                    auth.len(),
+                    auth
+                ),
+                None => write!(
+                    w,
+                    "HTTP/1.1 401 Unauthorized\r\n\
+                    WWW-Authenticate: Digest realm=\"test\", nonce=\"abc\", qop=\"auth\"\r\n\
+                    Content-Length: 0\r\n\
+                    Connection: close\r\n\
+                    \r\n"
+                ),
+            }
+        }),
+        handlers,
+    );
+
+    maybe_add(
+        TestHandler::new("/bearer-auth", |_uri, req, w| {
+            let valid = req
+                .headers()
+                .get("authorization")
+                .map(|v| v == "Bearer valid")
+                .unwrap_or(false);
+
+            if valid {
+                write!(
+                    w,
+                    "HTTP/1.1 200 OK\r\n\
+                    Content-Length: 2\r\n\
+                    \r\n\
+                    ok"
+                )
+            } else {
+                write!(
+                    w,
+                    "HTTP/1.1 401 Unauthorized\r\n\
+                    Content-Length: 0\r\n\
+                    Connection: close\r\n\
+                    \r\n"
+                )
+            }
+        }),
+        handlers,
+    );
+
+    maybe_add(
+        TestHandler::new("/cache/max-age", |_uri, _req, w| {
+            static COUNT: AtomicUsize = AtomicUsize::new(0);
+            let count = COUNT.fetch_add(1, Ordering::SeqCst) + 1;
+            write!(
+                w,
+                "HTTP/1.1 200 OK\r\n\
+                Cache-Control: max-age=60\r\n\
+                Content-Length: {}\r\n\
+                Connection: close\r\n\
+                \r\n\
+                {}",
+                count.to_string().len(),
+                count
+            )
+        }),
+        handlers,
+    );
+
+    maybe_add(
+        TestHandler::new("/cache/etag", |_uri, req, w| {
+            let matches = req
+                .headers()
+                .get("if-none-match")
+                .map(|v| v == "\"v1\"")
+                .unwrap_or(false);
+
+            if matches {
+                write!(
+                    w,
+                    "HTTP/1.1 304 Not Modified\r\n\
+                    ETag: \"v1\"\r\n\
+                    Keep-Alive: timeout=5\r\n\
+                    Connection: close\r\n\
+                    \r\n"
+                )
+            } else {
+                write!(
+                    w,
+                    "HTTP/1.1 200 OK\r\n\
+                    Cache-Control: no-cache\r\n\
+                    ETag: \"v1\"\r\n\
+                    Content-Length: 5\r\n\
+                    Connection: close\r\n\
+                    \r\n\
+                    hello"
+                )
+            }
+        }),
+        handlers,
+    );
+
+    maybe_add(
+        TestHandler::new("/upgrade", |_uri, _req, w| {
+            write!(
+                w,
+                "HTTP/1.1 101 Switching Protocols\r\n\
+                Connection: upgrade\r\n\
+                Upgrade: test\r\n\
+                \r\n\
+                hello upgraded"
+            )
+        }),
+        handlers,
+    );
+
+    maybe_add(
+        TestHandler::new("/status/", |uri, _req, w| {
+            let status = uri.path().trim_start_matches("/status/");
+            write!(
+                w,
+                "HTTP/1.1 {} OK\r\n\
+                Content-Length: 0\r\n\
+                Connection: close\r\n\
+                \r\n",
+                status
+            )
+        }),
+        handlers,
+    );
+
+    maybe_add(
+        TestHandler::new("/redirect-loop", |_uri, _req, w| {
+            write!(
+                w,
+                "HTTP/1.1 302 FOUND\r\n\
+                Location: /redirect-loop\r\n\
+                Content-Length: 22\r\n\
+                Connection: close\r\n\
+                \r\n\
+                You've been redirected\
+                "
+            )
+        }),
+        handlers,
+    );
+
     maybe_add(
         TestHandler::new("/redirect-to", |uri, _req, w| {
             let location = uri.query().unwrap();
@@ -311,6 +619,171 @@ fn setup_default_handlers(handlers: &mut Vec<TestHandler>) {
         handlers,
     );
 
+    maybe_add(
+        TestHandler::new("/early-hints", |_uri, _req, w| {
+            write!(
+                w,
+                "HTTP/1.1 103 Early Hints\r\n\
+                Link: </style.css>; rel=preload; as=style\r\n\
+                Link: <https://cdn.test/script.js>; rel=\"preload\"; as=script, <other.js>; rel=prefetch\r\n\
+                \r\n"
+            )?;
+            write!(
+                w,
+                "HTTP/1.1 200 OK\r\n\
+                Content-Length: 2\r\n\
+                \r\n\
+                ok"
+            )
+        }),
+        handlers,
+    );
+
+    maybe_add(
+        TestHandler::new("/no-content-with-body", |_uri, _req, w| {
+            write!(
+                w,
+                "HTTP/1.1 204 No Content\r\n\
+                Content-Length: 5\r\n\
+                \r\n\
+                oops!"
+            )
+        }),
+        handlers,
+    );
+
+    maybe_add(
+        TestHandler::new("/no-content", |_uri, _req, w| {
+            write!(
+                w,
+                "HTTP/1.1 204 No Content\r\n\
+                \r\n"
+            )
+        }),
+        handlers,
+    );
+
+    maybe_add(
+        TestHandler::new("/echo-digest", |_uri, req, w| {
+            let header = |name| {
+                req.headers()
+                    .get(name)
+                    .map(|v| v.to_str().unwrap())
+                    .unwrap_or("")
+            };
+            let body = format!("{}|{}", header("content-md5"), header("digest"));
+            write!(
+                w,
+                "HTTP/1.1 200 OK\r\n\
+                Content-Length: {}\r\n\
+                X-Request-Content-Length: {}\r\n\
+                \r\n\
+                {}",
+                body.len(),
+                header("content-length"),
+                body
+            )
+        }),
+        handlers,
+    );
+
+    maybe_add(
+        TestHandler::new("/echo-proxy", |_uri, req, w| {
+            let via = if req.headers().contains_key("x-test-via-connect-proxy") {
+                "connect-proxy"
+            } else {
+                "direct"
+            };
+            write!(
+                w,
+                "HTTP/1.1 200 OK\r\n\
+                Content-Length: {}\r\n\
+                \r\n\
+                {}",
+                via.len(),
+                via
+            )
+        }),
+        handlers,
+    );
+
+    maybe_add(
+        TestHandler::new("/echo-headers", |_uri, req, w| {
+            let body: String = req
+                .headers()
+                .iter()
+                .map(|(k, v)| format!("{}: {}\n", k, v.to_str().unwrap()))
+                .collect();
+            write!(
+                w,
+                "HTTP/1.1 200 OK\r\n\
+                Content-Length: {}\r\n\
+                \r\n\
+                {}",
+                body.len(),
+                body
+            )
+        }),
+        handlers,
+    );
+
+    maybe_add(
+        TestHandler::new("/multipart", |_uri, _req, w| {
+            let body = "--xyz\r\n\
+                Content-Type: text/plain\r\n\
+                \r\n\
+                first part\r\n\
+                --xyz\r\n\
+                Content-Type: application/json\r\n\
+                \r\n\
+                {\"second\":true}\r\n\
+                --xyz--\r\n";
+            write!(
+                w,
+                "HTTP/1.1 200 OK\r\n\
+                Content-Type: multipart/mixed; boundary=\"xyz\"\r\n\
+                Content-Length: {}\r\n\
+                \r\n\
+                {}",
+                body.len(),
+                body
+            )
+        }),
+        handlers,
+    );
+
+    maybe_add(
+        TestHandler::new("/drip", |_uri, _req, w| {
+            write!(
+                w,
+                "HTTP/1.1 200 OK\r\n\
+                Content-Length: 5\r\n\
+                \r\n"
+            )?;
+            for _ in 0..5 {
+                thread::sleep(std::time::Duration::from_millis(40));
+                w.write_all(b"a")?;
+            }
+            Ok(())
+        }),
+        handlers,
+    );
+
+    maybe_add(
+        TestHandler::new("/stall", |_uri, _req, w| {
+            write!(
+                w,
+                "HTTP/1.1 200 OK\r\n\
+                Content-Length: 5\r\n\
+                \r\n"
+            )?;
+            w.write_all(b"a")?;
+            thread::sleep(std::time::Duration::from_millis(500));
+            w.write_all(b"aaaa")
+        }),
+        handlers,
+    );
+
     maybe_add(
         TestHandler::new("/cookie-test", |_uri, req, w| {
             let mut all: Vec<_> = req
@@ -406,21 +879,6 @@ const HTTPBIN_JSON: &str = r#"
   }
 }"#;
 
-struct RxRead(Receiver<Vec<u8>>);
-
-impl io::Read for RxRead {
-    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        let v = match self.0.recv() {
-            Ok(v) => v,
-            Err(_) => return Ok(0), // remote side is gone
-        };
-        assert!(buf.len() > v.len());
-        let max = buf.len().min(v.len());
-        buf[..max].copy_from_slice(&v[..]);
-        Ok(max)
-    }
-}
-
 struct TxWrite(mpsc::SyncSender<Vec<u8>>);
 
 impl io::Write for TxWrite {
@@ -441,6 +899,7 @@ struct TestTransport {
     tx: mpsc::SyncSender<Vec<u8>>,
     rx: SyncReceiver<Vec<u8>>,
     connected: bool,
+    remote_addr: Option<std::net::SocketAddr>,
 }
 
 impl Transport for TestTransport {
@@ -485,6 +944,10 @@ impl Transport for TestTransport {
         // Pretend this is tls to not get TLS wrappers
         true
     }
+
+    fn remote_addr(&self) -> Option<std::net::SocketAddr> {
+        self.remote_addr
+    }
 }
 
 // Workaround for std::mpsc::Receiver not being Sync
@@ -0,0 +1,167 @@
+use std::fmt;
+use std::net::SocketAddr;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::config::ProbeKind;
+use crate::Error;
+
+use super::{Buffers, ConnectionDetails, Connector, NextTimeout, Transport};
+
+/// Connector limiting the bandwidth of the chained transport.
+///
+/// Wraps the transport from the previous connector in the chain, and sleeps
+/// to keep the sending and receiving under the given bytes per second. The limit is
+/// applied separately for each direction, and for each connection.
+///
+/// If the sleep would go past the current timeout, the transport fails with
+/// [`Error::Timeout`] instead.
+///
+/// ```
+/// use ureq::Agent;
+/// use ureq::config::Config;
+/// use ureq::unversioned::resolver::DefaultResolver;
+/// use ureq::unversioned::transport::{DefaultConnector, ThrottleConnector};
+///
+/// let connector = DefaultConnector::builder()
+///     // Max 100kB/s
+///     .after_tcp(ThrottleConnector::new(100 * 1024))
+///     .build();
+///
+/// let agent = Agent::with_parts(Config::default(), connector, DefaultResolver::default());
+/// ```
+#[derive(Debug)]
+pub struct ThrottleConnector {
+    bytes_per_second: u64,
+}
+
+impl ThrottleConnector {
+    /// Creates a connector limiting to `bytes_per_second`.
+    ///
+    /// Panics if `bytes_per_second` is 0.
+    pub fn new(bytes_per_second: u64) -> Self {
+        assert!(bytes_per_second > 0, "bytes_per_second must be > 0");
+        ThrottleConnector { bytes_per_second }
+    }
+}
+
+impl Connector for ThrottleConnector {
+    fn connect(
+        &self,
+        _details: &ConnectionDetails,
+        chained: Option<Box<dyn Transport>>,
+    ) -> Result<Option<Box<dyn Transport>>, Error> {
+        let Some(transport) = chained else {
+            return Ok(None);
+        };
+
+        trace!("Throttle to {} bytes/s", self.bytes_per_second);
+
+        Ok(Some(Box::new(ThrottleTransport {
+            inner: transport,
+            bytes_per_second: self.bytes_per_second,
+            next_send: None,
+            next_recv: None,
+        })))
+    }
+}
+
+struct ThrottleTransport {
+    inner: Box<dyn Transport>,
+    bytes_per_second: u64,
+    /// Earliest time for the next send.
+    next_send: Option<Instant>,
+    /// Earliest time for the next receive.
+    next_recv: Option<Instant>,
+}
+
+impl ThrottleTransport {
+    /// Time it takes to transfer `amount` at the throttled speed.
+    fn duration_for(&self, amount: usize) -> Duration {
+        Duration::from_secs_f64(amount as f64 / self.bytes_per_second as f64)
+    }
+}
+
+/// Sleep until `next`, unless that would be past the timeout.
+fn wait_until(next: Option<Instant>, timeout: &NextTimeout) -> Result<NextTimeout, Error> {
+    let wait = next
+        .map(|n| n.saturating_duration_since(Instant::now()))
+        .unwrap_or_default();
+
+    if wait.is_zero() {
+        return Ok(*timeout);
+    }
+
+    if wait >= *timeout.after {
+        return Err(Error::Timeout(timeout.reason));
+    }
+
+    thread::sleep(wait);
+
+    if timeout.after.is_not_happening() {
+        return Ok(*timeout);
+    }
+
+    Ok(NextTimeout {
+        after: (*timeout.after - wait).into(),
+        reason: timeout.reason,
+    })
+}
+
+impl Transport for ThrottleTransport {
+    fn buffers(&mut self) -> &mut dyn Buffers {
+        self.inner.buffers()
+    }
+
+    fn transmit_output(&mut self, amount: usize, timeout: NextTimeout) -> Result<(), Error> {
+        let timeout = wait_until(self.next_send, &timeout)?;
+
+        self.inner.transmit_output(amount, timeout)?;
+
+        self.next_send = Some(Instant::now() + self.duration_for(amount));
+
+        Ok(())
+    }
+
+    fn await_input(&mut self, timeout: NextTimeout) -> Result<bool, Error> {
+        let timeout = wait_until(self.next_recv, &timeout)?;
+
+        let before = self.inner.buffers().input().len();
+        let made_progress = self.inner.await_input(timeout)?;
+        let after = self.inner.buffers().input().len();
+
+        let amount = after.saturating_sub(before);
+        self.next_recv = Some(Instant::now() + self.duration_for(amount));
+
+        Ok(made_progress)
+    }
+
+    fn is_open(&mut self) -> bool {
+        self.inner.is_open()
+    }
+
+    fn probe(&mut self, kind: ProbeKind) -> bool {
+        self.inner.probe(kind)
+    }
+
+    fn is_tls(&self) -> bool {
+        self.inner.is_tls()
+    }
+
+    fn tls_handshake_duration(&self) -> Option<std::time::Duration> {
+        self.inner.tls_handshake_duration()
+    }
+
+    fn remote_addr(&self) -> Option<SocketAddr> {
+        self.inner.remote_addr()
+    }
+}
+
+impl fmt::Debug for ThrottleTransport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ThrottleTransport")
+            .field("bytes_per_second", &self.bytes_per_second)
+            .field("inner", &self.inner)
+            .finish()
+    }
+}
@@ -0,0 +1,214 @@
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::{fmt, io};
+
+use crate::util::IoResultExt;
+use crate::Error;
+
+use super::tcp::{maybe_update_timeout, probe_stream};
+use super::time::Duration;
+use super::{Buffers, ConnectionDetails, Connector, LazyBuffers, NextTimeout, Transport};
+
+/// Connector for Unix domain sockets.
+///
+/// All connections are made to the socket at the given path, regardless of the
+/// host in the uri. The host is still sent in the `Host` header, and must resolve
+/// (`localhost` is a good choice).
+///
+/// ```no_run
+/// use ureq::{Agent, config::Config};
+/// use ureq::unversioned::resolver::DefaultResolver;
+/// use ureq::unversioned::transport::UnixConnector;
+///
+/// let agent = Agent::with_parts(
+///     Config::default(),
+///     UnixConnector::new("/var/run/docker.sock"),
+///     DefaultResolver::default(),
+/// );
+///
+/// let version = agent.get("http://localhost/version")
+///     .call()?
+///     .body_mut()
+///     .read_to_string()?;
+/// # Ok::<_, ureq::Error>(())
+/// ```
+///
+/// Only available on unix.
+pub struct UnixConnector {
+    path: PathBuf,
+}
+
+impl UnixConnector {
+    /// Creates a connector for the socket at `path`.
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        UnixConnector {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+}
+
+impl Connector for UnixConnector {
+    fn connect(
+        &self,
+        details: &ConnectionDetails,
+        chained: Option<Box<dyn Transport>>,
+    ) -> Result<Option<Box<dyn Transport>>, Error> {
+        if chained.is_some() {
+            // The chained connection overrides whatever we were to open here.
+            trace!("Skip");
+            return Ok(chained);
+        }
+
+        trace!("Try connect UnixStream to {:?}", self.path);
+
+        let stream = UnixStream::connect(&self.path)?;
+
+        debug!("Connected UnixStream to {:?}", self.path);
+
+        let config = &details.config;
+        let buffers = LazyBuffers::new(config.input_buffer_size(), config.output_buffer_size());
+        let transport = UnixTransport {
+            stream,
+            buffers,
+            timeout_read: None,
+            timeout_write: None,
+        };
+
+        Ok(Some(Box::new(transport)))
+    }
+}
+
+struct UnixTransport {
+    stream: UnixStream,
+    buffers: LazyBuffers,
+    timeout_write: Option<Duration>,
+    timeout_read: Option<Duration>,
+}
+
+impl Transport for UnixTransport {
+    fn buffers(&mut self) -> &mut dyn Buffers {
+        &mut self.buffers
+    }
+
+    fn transmit_output(&mut self, amount: usize, timeout: NextTimeout) -> Result<(), Error> {
+        maybe_update_timeout(
+            timeout,
+            &mut self.timeout_write,
+            &self.stream,
+            UnixStream::set_write_timeout,
+        )?;
+
+        let output = &self.buffers.output()[..amount];
+        match self.stream.write_all(output).normalize_would_block() {
+            Ok(v) => Ok(v),
+            Err(e) if e.kind() == io::ErrorKind::TimedOut => Err(Error::Timeout(timeout.reason)),
+            Err(e) => Err(e.into()),
+        }?;
+
+        Ok(())
+    }
+
+    fn await_input(&mut self, timeout: NextTimeout) -> Result<bool, Error> {
+        if self.buffers.can_use_input() {
+            return Ok(true);
+        }
+
+        maybe_update_timeout(
+            timeout,
+            &mut self.timeout_read,
+            &self.stream,
+            UnixStream::set_read_timeout,
+        )?;
+
+        let input = self.buffers.input_append_buf();
+        let amount = match self.stream.read(input).normalize_would_block() {
+            Ok(v) => Ok(v),
+            Err(e) if e.kind() == io::ErrorKind::TimedOut => Err(Error::Timeout(timeout.reason)),
+            Err(e) => Err(e.into()),
+        }?;
+        self.buffers.input_appended(amount);
+
+        Ok(amount > 0)
+    }
+
+    fn is_open(&mut self) -> bool {
+        probe_stream(&mut self.stream, UnixStream::set_nonblocking).unwrap_or(false)
+    }
+}
+
+impl fmt::Debug for UnixConnector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UnixConnector")
+            .field("path", &self.path)
+            .finish()
+    }
+}
+
+impl fmt::Debug for UnixTransport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UnixTransport")
+            .field("addr", &self.stream.peer_addr().ok())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{BufRead, BufReader};
+    use std::os::unix::net::UnixListener;
+    use std::thread;
+
+    use super::*;
+    use crate::config::Config;
+    use crate::resolver::DefaultResolver;
+    use crate::Agent;
+
+    #[test]
+    fn request_over_unix_socket() {
+        let path = std::env::temp_dir().join(format!("ureq-test-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).unwrap();
+
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+
+            // Read the rest of the request head.
+            let mut line = String::new();
+            while line != "\r\n" {
+                line.clear();
+                reader.read_line(&mut line).unwrap();
+            }
+
+            reader
+                .get_mut()
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok")
+                .unwrap();
+
+            request_line
+        });
+
+        let agent = Agent::with_parts(
+            Config::default(),
+            UnixConnector::new(&path),
+            DefaultResolver::default(),
+        );
+
+        let body = agent
+            .get("http://localhost/version")
+            .call()
+            .unwrap()
+            .body_mut()
+            .read_to_string()
+            .unwrap();
+
+        assert_eq!(body, "ok");
+        assert_eq!(server.join().unwrap(), "GET /version HTTP/1.1\r\n");
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -1,6 +1,7 @@
 use std::convert::TryFrom;
 use std::fmt;
 use std::io::{self, ErrorKind};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use http::header::{ACCEPT, ACCEPT_CHARSET, ACCEPT_ENCODING};
 use http::header::{CONNECTION, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE};
@@ -282,6 +283,9 @@ impl<'a> fmt::Debug for DebugAuthority<'a> {
 pub(crate) trait UriExt {
     fn ensure_valid_url(&self) -> Result<(), Error>;
 
+    /// Resolve a (possibly relative) reference against this uri.
+    fn resolve(&self, reference: &str) -> Option<Uri>;
+
     #[cfg(feature = "_url")]
     fn try_into_url(&self) -> Result<url::Url, Error>;
 }
@@ -302,6 +306,34 @@ impl UriExt for Uri {
         Ok(())
     }
 
+    fn resolve(&self, reference: &str) -> Option<Uri> {
+        if let Ok(uri) = reference.parse::<Uri>() {
+            if uri.scheme().is_some() {
+                return Some(uri);
+            }
+        }
+
+        let scheme = self.scheme_str()?;
+
+        let s = if let Some(rest) = reference.strip_prefix("//") {
+            format!("{}://{}", scheme, rest)
+        } else {
+            let authority = self.authority()?;
+
+            if reference.starts_with('/') {
+                format!("{}://{}{}", scheme, authority, reference)
+            } else {
+                // Relative to the "directory" of the current path.
+                let path = self.path();
+                let dir = &path[..path.rfind('/').map(|i| i + 1).unwrap_or(0)];
+                let dir = if dir.is_empty() { "/" } else { dir };
+                format!("{}://{}{}{}", scheme, authority, dir, reference)
+            }
+        };
+
+        s.parse().ok()
+    }
+
     #[cfg(feature = "_url")]
     fn try_into_url(&self) -> Result<url::Url, Error> {
         self.ensure_valid_url()?;
@@ -360,3 +392,100 @@ impl HeaderMapExt for HeaderMap {
         self.contains_key("content-type")
     }
 }
+
+/// Parse a `Link` header value into `(target, params)` pairs.
+///
+/// ```text
+/// <https://example.com/style.css>; rel=preload; as=style, </script.js>; rel=preload
+/// ```
+pub(crate) fn parse_link_header(value: &str) -> Vec<(&str, &str)> {
+    let mut links = vec![];
+    let mut rest = value;
+
+    loop {
+        rest = rest.trim_start_matches(|c: char| c == ',' || c.is_ascii_whitespace());
+
+        let Some(after) = rest.strip_prefix('<') else {
+            break;
+        };
+        let Some(end) = after.find('>') else {
+            break;
+        };
+
+        let target = &after[..end];
+        let after = &after[end + 1..];
+
+        // Params run until the next comma that is not inside a quoted string.
+        let mut quoted = false;
+        let params_end = after
+            .char_indices()
+            .find(|(_, c)| {
+                if *c == '"' {
+                    quoted = !quoted;
+                }
+                *c == ',' && !quoted
+            })
+            .map(|(i, _)| i)
+            .unwrap_or(after.len());
+
+        links.push((target.trim(), after[..params_end].trim()));
+        rest = &after[params_end..];
+    }
+
+    links
+}
+
+/// Parse an HTTP date in the preferred IMF-fixdate format.
+///
+/// ```text
+/// Sun, 06 Nov 1994 08:49:37 GMT
+/// ```
+///
+/// The obsolete RFC 850 and asctime formats are not supported.
+pub(crate) fn parse_http_date(s: &str) -> Option<SystemTime> {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+
+    let s = s.trim();
+    let (_weekday, rest) = s.split_once(", ")?;
+
+    let mut parts = rest.split(' ');
+    let day: u64 = parts.next()?.parse().ok()?;
+    let month = parts.next()?;
+    let year: u64 = parts.next()?.parse().ok()?;
+    let time = parts.next()?;
+    if parts.next()? != "GMT" || parts.next().is_some() {
+        return None;
+    }
+
+    let month = MONTHS.iter().position(|m| *m == month)? as u64 + 1;
+
+    let mut hms = time.split(':');
+    let h: u64 = hms.next()?.parse().ok()?;
+    let m: u64 = hms.next()?.parse().ok()?;
+    let sec: u64 = hms.next()?.parse().ok()?;
+    if hms.next().is_some()
+        || !(1..=31).contains(&day)
+        || year < 1970
+        || h > 23
+        || m > 59
+        || sec > 60
+    {
+        return None;
+    }
+
+    // Days since epoch from civil date.
+    // http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y / 400;
+    let yoe = y - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146_097 + doe - 719_468;
+
+    let secs = days * 86_400 + h * 3600 + m * 60 + sec;
+
+    Some(UNIX_EPOCH + std::time::Duration::from_secs(secs))
+}